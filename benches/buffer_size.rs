@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rcpio::{Compression, CpioBuilder, CpioFormat};
+
+const FILE_SIZE: usize = 64 << 20;
+
+/// Archive one large file with different read buffer sizes, the source stays in the page cache
+/// so this measures the per chunk overhead rather than the disk
+fn buffer_sizes(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large");
+    let content: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, content).unwrap();
+
+    let mut group = c.benchmark_group("archive a 64 MiB file");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(10);
+    for size in [4 << 10, 64 << 10, 1 << 20, 8 << 20] {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.read_buffer_size(size);
+        builder.insert(&path, "large").unwrap();
+
+        group.bench_with_input(BenchmarkId::new("read_buffer_size", size), &builder, |b, builder| {
+            b.iter(|| builder.write_to(std::io::sink(), Compression::None).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, buffer_sizes);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rcpio::{Cpio, CpioBuilder, CpioFormat, ExtractOptions};
+
+const DIRECTORIES: usize = 100;
+const FILES: usize = 10_000;
+
+/// Archive of `FILES` 1 KiB files spread over `DIRECTORIES` directories
+fn archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for dir in 0..DIRECTORIES {
+        builder.insert_dir(&format!("dir{dir}"), 0o755).unwrap();
+    }
+    for file in 0..FILES {
+        builder.insert_data(&format!("dir{}/file{file}", file % DIRECTORIES), &[0xaa; 1024], 0o644).unwrap();
+    }
+    builder.to_vec().unwrap()
+}
+
+/// Serial against parallel extraction of many small files, each run into a fresh directory
+fn extraction(c: &mut Criterion) {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+    let options = ExtractOptions::default();
+
+    let mut group = c.benchmark_group("extract 10k small files");
+    group.sample_size(10);
+    group.bench_function("serial", |b| b.iter_batched(
+        || tempfile::tempdir().unwrap(),
+        |dir| {
+            cpio.unarchive_with_options(&dir.path().join("out"), &options).unwrap();
+            dir
+        },
+        BatchSize::PerIteration
+    ));
+    for threads in [2, 4, 8] {
+        group.bench_function(format!("{threads} threads"), |b| b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |dir| {
+                cpio.unarchive_parallel(&dir.path().join("out"), &options, threads).unwrap();
+                dir
+            },
+            BatchSize::PerIteration
+        ));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, extraction);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fallible_iterator::FallibleIterator;
+use rcpio::{Cpio, CpioBuilder, CpioFormat};
+
+const ENTRIES: usize = 50_000;
+
+fn archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for i in 0..ENTRIES {
+        builder.insert_data(&format!("usr/lib/modules/{}/file{i}.ko", i % 100), b"", 0o644).unwrap();
+    }
+    builder.to_vec().unwrap()
+}
+
+/// Look up entries spread across the archive, the way `cat` or `extract_entry_to` would
+fn lookups(c: &mut Criterion) {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+    let names: Vec<String> = (0..ENTRIES).step_by(ENTRIES / 10)
+        .map(|i| format!("./usr/lib/modules/{}/file{i}.ko", i % 100))
+        .collect();
+
+    c.bench_function("find 10 names in 50k entries", |b| b.iter(|| {
+        for name in &names {
+            black_box(cpio.find(name).unwrap().unwrap());
+        }
+    }));
+
+    // the same lookup decoding and normalizing every name, for reference
+    c.bench_function("normalized name_string scan for 10 names in 50k entries", |b| b.iter(|| {
+        for name in &names {
+            let wanted = rcpio::normalize_internal_path(name).unwrap();
+            let found = cpio.iter_files().find(|entry|
+                Ok(rcpio::normalize_internal_path(&entry.name_string()?).is_ok_and(|name| name == wanted))
+            ).unwrap();
+            black_box(found.unwrap());
+        }
+    }));
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
@@ -1,14 +1,28 @@
 pub(crate) const NEWC_MAGIC: &[u8] = b"070701";
 pub(crate) const CRC_MAGIC: &[u8]  = b"070702";
+pub(crate) const ODC_MAGIC: &[u8]  = b"070707";
 
-pub(crate) const TRAILER: &[u8] = b"00000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000B00000000TRAILER!!!\0";
+pub(crate) const TRAILER_NAME: &[u8] = b"TRAILER!!!";
 
 pub(crate) const CPIO_MAGIC_LEN: usize = 6;
 pub(crate) const CPIO_FIELD_LEN: usize = 8;
 
+/// Default block size the end of an archive is padded to
+pub(crate) const CPIO_BLOCK_SIZE: usize = 512;
+
+/// Default chunk size for reading source files and writing archives
+pub(crate) const READ_BUFFER_SIZE: usize = 64 * 1024;
+
 /// Total size of a NEWC/CRC cpio entry header
 pub(crate) const CPIO_HEADER_LEN: usize = 110;
 
+/// Total size of an odc cpio entry header
+pub(crate) const ODC_HEADER_LEN: usize = 76;
+
+/// Linux limits on the length of a single path component and of a whole path
+pub(crate) const NAME_MAX: usize = 255;
+pub(crate) const PATH_MAX: usize = 4096;
+
 /// POSIX file mode constants
 pub(crate) const S_IFMT   : u64 = 0o170000; // bit mask file type bit field
 pub(crate) const S_IFSOCK : u64 = 0o140000; // socket
@@ -18,6 +32,10 @@ pub(crate) const S_IFBLK  : u64 = 0o060000; // block device
 pub(crate) const S_IFDIR  : u64 = 0o040000; // directory
 pub(crate) const S_IFCHR  : u64 = 0o020000; // character device
 pub(crate) const S_IFIFO  : u64 = 0o010000; // FIFO
+pub(crate) const S_IPERM  : u64 = 0o007777; // permission and setuid/setgid/sticky bits
+pub(crate) const S_ISUID  : u64 = 0o004000; // set user id on execution
+pub(crate) const S_ISGID  : u64 = 0o002000; // set group id on execution
+pub(crate) const S_ISVTX  : u64 = 0o001000; // sticky bit
 pub(crate) const MODE_R: u64 = 0o04;
 pub(crate) const MODE_W: u64 = 0o02;
 pub(crate) const MODE_X: u64 = 0o01;
@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::from_utf8;
+
+use crate::defs::{self, NAME_MAX, PATH_MAX};
+use crate::{identify_format, missing_parents, normalize_internal_path, Cpio, CpioEntry, CpioFormat};
+
+/// How serious a problem found by `Cpio::diagnose` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The archive can still be read and extracted, but consumers may disagree about it
+    Warning,
+
+    /// The archive is damaged or can't be read past this point
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single problem found by `Cpio::diagnose`
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Offset of the entry (or trailing data) the problem was found at
+    pub offset: usize,
+
+    pub severity: Severity,
+
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} at offset {:#x}", self.severity, self.message, self.offset)
+    }
+}
+
+/// Collects diagnostics while walking the archive
+struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    fn warning(&mut self, offset: usize, message: String) {
+        self.0.push(Diagnostic { offset, severity: Severity::Warning, message });
+    }
+
+    fn error(&mut self, offset: usize, message: String) {
+        self.0.push(Diagnostic { offset, severity: Severity::Error, message });
+    }
+}
+
+impl<'a> Cpio<'a> {
+    /// Walk the whole archive and report every problem found instead of stopping at the first:
+    /// bad magic, unparsable header fields, truncated names and contents, unterminated or
+    /// non-UTF-8 names, names too long to extract or escaping the archive root, non-zero
+    /// alignment padding, duplicate names, parent directories without their own entry,
+    /// crc checksum mismatches and a missing or dirty trailer. The walk only stops where the
+    /// following entries can't be located anymore.
+    pub fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostics(vec![]);
+        let mut names = HashSet::new();
+        let mut directories = HashSet::new();
+        let mut previous: Option<String> = None;
+        let mut offset = 0;
+
+        loop {
+            let rest = self.mem.get(offset..).unwrap_or_default();
+            if rest.iter().all(|b| *b == 0) {
+                diagnostics.error(offset, String::from("archive ends without a trailer"));
+                break;
+            }
+
+            let Ok(format) = identify_format(rest) else {
+                let message = match &previous {
+                    Some(name) => format!("no cpio magic after '{name}', its size fields are likely wrong"),
+                    None => String::from("no cpio magic"),
+                };
+                diagnostics.error(offset, message);
+                break;
+            };
+            if rest.len() < format.header_len() {
+                diagnostics.error(offset, String::from("truncated header, the archive ends without a trailer"));
+                break;
+            }
+            if format != self.format {
+                diagnostics.warning(offset, format!("{format:?} entry in a {:?} archive", self.format));
+            }
+
+            let Some(entry) = self.diagnose_entry(offset, format, &mut diagnostics) else {
+                break;
+            };
+            let Ok(name) = entry.name() else {
+                break;
+            };
+            let name = name.strip_suffix(b"\0").unwrap_or(name);
+
+            if name == b"TRAILER!!!" {
+                self.diagnose_trailer(&entry, &mut diagnostics);
+                break;
+            }
+
+            match from_utf8(name) {
+                Ok(name) => {
+                    self.diagnose_name(offset, name, &mut names, &mut directories, &mut diagnostics);
+                    if entry.is_dir().unwrap_or(false) {
+                        if let Ok(name) = normalize_internal_path(name) {
+                            directories.insert(name);
+                        }
+                    }
+                },
+                Err(_) => diagnostics.warning(
+                    offset, format!("name '{}' is not valid UTF-8", name.escape_ascii())
+                ),
+            }
+
+            previous = Some(String::from_utf8_lossy(name).into_owned());
+            match entry.next() {
+                Ok(next) => offset = next,
+                Err(_) => break,
+            }
+        }
+
+        diagnostics.0
+    }
+
+    /// Check the header, name and content layout of the entry at `offset`, `None` if it is too
+    /// broken to find the entry after it
+    fn diagnose_entry(
+        &self,
+        offset: usize,
+        format: CpioFormat,
+        diagnostics: &mut Diagnostics
+    ) -> Option<CpioEntry<'a>> {
+        let entry = CpioEntry::new(offset, format, self.mem).ok()?;
+        if let Err(e) = entry.header_fields() {
+            diagnostics.error(offset, e.to_string());
+            return None
+        }
+
+        let namesize = entry.namesize().ok()?;
+        if namesize == 0 {
+            diagnostics.error(offset, String::from("entry has an empty name"));
+            return None
+        }
+        let Ok(name) = entry.name() else {
+            diagnostics.error(offset, String::from("entry name runs past the end of the archive"));
+            return None
+        };
+        match name.split_last() {
+            Some((0, rest)) if rest.contains(&0) => diagnostics.warning(
+                offset, String::from("entry name contains an embedded null byte")
+            ),
+            Some((0, _)) => {},
+            _ => diagnostics.error(offset, String::from("entry name is not null terminated")),
+        }
+
+        let content_offset = entry.file_content_offset().ok()?;
+        let filesize = entry.filesize().ok()?;
+        // an empty entry at the very end (normally the trailer) may lack its alignment padding
+        if filesize > 0 && offset + content_offset + filesize > self.mem.len() {
+            diagnostics.error(offset, format!(
+                "{filesize} bytes of content run past the end of the archive"
+            ));
+            return None
+        }
+
+        let end = |offset: usize| offset.min(self.mem.len());
+        let name_padding = &self.mem[offset + format.header_len() + namesize..end(offset + content_offset)];
+        let content_end = end(offset + content_offset + filesize);
+        let content_padding = &self.mem[content_end..end(entry.next().ok()?)];
+        if name_padding.iter().chain(content_padding).any(|b| *b != 0) {
+            diagnostics.warning(offset, String::from("non-zero alignment padding"));
+        }
+
+        if format == CpioFormat::Crc && entry.is_reg().unwrap_or(false) {
+            if let (Ok(stored), Ok(computed)) = (entry.checksum(), entry.computed_checksum()) {
+                if stored as u32 != computed {
+                    diagnostics.error(offset, format!(
+                        "checksum mismatch, stored {stored:#010x} but the content sums to {computed:#010x}"
+                    ));
+                }
+            }
+        }
+
+        Some(entry)
+    }
+
+    /// Check a decoded entry name for extraction problems, duplicates and missing parents
+    fn diagnose_name(
+        &self,
+        offset: usize,
+        name: &str,
+        names: &mut HashSet<String>,
+        directories: &mut HashSet<String>,
+        diagnostics: &mut Diagnostics
+    ) {
+        if name.len() + 1 > PATH_MAX || name.split('/').any(|c| c.len() > NAME_MAX) {
+            diagnostics.warning(offset, format!("name '{name}' is too long to extract on Linux"));
+        }
+
+        let normalized = match normalize_internal_path(name) {
+            Ok(normalized) => normalized,
+            Err(e) => {
+                diagnostics.warning(offset, format!("{e}, extraction rejects it"));
+                return
+            },
+        };
+
+        if !names.insert(normalized.clone()) {
+            diagnostics.warning(offset, format!("duplicate name '{normalized}'"));
+        }
+        for parent in missing_parents(&normalized, directories) {
+            diagnostics.warning(offset, format!("'{parent}' has no directory entry before '{normalized}'"));
+        }
+    }
+
+    /// Check the block padding that follows the trailer
+    fn diagnose_trailer(&self, trailer: &CpioEntry, diagnostics: &mut Diagnostics) {
+        let Ok(start) = trailer.next() else {
+            return
+        };
+        let start = start.min(self.mem.len());
+        let end = start.next_multiple_of(defs::CPIO_BLOCK_SIZE).min(self.mem.len());
+        if let Some(dirty) = self.mem[start..end].iter().position(|b| *b != 0) {
+            diagnostics.warning(start + dirty, String::from("non-zero padding after the trailer"));
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::{identify_format, normalize_internal_path, CpioEntry, CpioFormat, Error};
+use crate::defs::{CPIO_MAGIC_LEN, PATH_MAX};
+
+/// Location of one entry's content within an archive file
+#[derive(Debug, Clone)]
+pub struct CpioIndexEntry {
+    /// Entry name without the trailing null terminator
+    pub name: String,
+
+    /// Offset of the entry header in the archive file
+    pub header_offset: u64,
+
+    /// Offset of the entry content in the archive file
+    pub offset: u64,
+
+    /// Length of the entry content
+    pub size: u64,
+}
+
+/// An index of an on-disk archive built by reading only the entry headers, file contents are
+/// read on demand so large archives don't need to be loaded or mapped
+#[derive(Debug)]
+pub struct CpioIndex {
+    file: File,
+    format: CpioFormat,
+    entries: Vec<CpioIndexEntry>,
+    by_name: HashMap<String, usize>,
+}
+
+fn read_at(file: &File, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0u8; len];
+    file.read_exact_at(&mut buf, offset).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => Error::EarlyEOFError,
+        _ => Error::FileSystemError(format!("Failed to read archive at offset {offset:#x}: {e}")),
+    })?;
+    Ok(buf)
+}
+
+impl CpioIndex {
+    /// Scan the headers of the archive at `path`, seeking past each entry's content
+    pub fn build_from_file(path: &Path) -> Result<CpioIndex, Error> {
+        let file = File::open(path).map_err(|e|
+            Error::FileSystemError(format!("Failed to open archive {}: {e}", path.display()))
+        )?;
+
+        let format = identify_format(&read_at(&file, 0, CPIO_MAGIC_LEN)?)?;
+        let file_len = file.metadata().map_err(|e|
+            Error::FileSystemError(format!("Failed to stat archive {}: {e}", path.display()))
+        )?.len();
+
+        let mut entries = vec![];
+        let mut by_name = HashMap::new();
+        let mut offset = 0u64;
+
+        loop {
+            // entries start aligned, so offsets relative to a buffer holding just this entry
+            // give the same alignment as the absolute ones
+            let header = read_at(&file, offset, format.header_len())?;
+            let namesize = CpioEntry::new(0, format, &header)?.namesize()?;
+            // the name size comes from the archive, check it before allocating for the name
+            if namesize > PATH_MAX {
+                return Err(Error::InvalidArchiveError(
+                    format!("Name size {namesize} at offset {offset:#x} exceeds PATH_MAX")
+                ))
+            }
+            if offset + (format.header_len() + namesize) as u64 > file_len {
+                return Err(Error::EarlyEOFError)
+            }
+
+            let mut buf = header;
+            buf.append(&mut read_at(&file, offset + format.header_len() as u64, namesize)?);
+            let entry = CpioEntry::new(0, format, &buf)?;
+
+            if !entry.valid_magic()? {
+                return Err(Error::InvalidArchiveError(
+                    format!("Invalid magic encountered at offset {offset:#x}")
+                ))
+            }
+            if entry.is_trailer()? {
+                break;
+            }
+
+            // the content is read into a buffer of this size later, it has to be in the file
+            let content_offset = offset + entry.file_content_offset()? as u64;
+            let size = entry.filesize()? as u64;
+            if content_offset + size > file_len {
+                return Err(Error::InvalidArchiveError(
+                    format!("File size {size} at offset {offset:#x} runs past the end of the archive")
+                ))
+            }
+
+            let name = entry.name_string()?;
+            if let Ok(normalized) = normalize_internal_path(&name) {
+                by_name.entry(normalized).or_insert(entries.len());
+            }
+            entries.push(CpioIndexEntry {
+                name,
+                header_offset: offset,
+                offset: content_offset,
+                size,
+            });
+
+            offset += entry.next()? as u64;
+        }
+
+        Ok(CpioIndex { file, format, entries, by_name })
+    }
+
+    pub fn format(&self) -> CpioFormat {
+        self.format
+    }
+
+    /// Indexed entries in archive order, excluding the trailer
+    pub fn entries(&self) -> &[CpioIndexEntry] {
+        &self.entries
+    }
+
+    /// Look up an entry by name, `foo/bar` and `./foo/bar` refer to the same entry
+    pub fn get(&self, name: &str) -> Option<&CpioIndexEntry> {
+        let normalized = normalize_internal_path(name).ok()?;
+        self.by_name.get(&normalized).map(|index| &self.entries[*index])
+    }
+
+    /// Read the content of the entry named `name` from the archive file
+    pub fn read_content(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let entry = self.get(name).ok_or_else(|| Error::NoSuchFile(name.to_string()))?;
+        read_at(&self.file, entry.offset, entry.size as usize)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::defs::{self, CPIO_HEADER_LEN, PATH_MAX};
+use crate::{CpioEntry, CpioFormat, Error};
+
+fn invalid(offset: usize, message: &str) -> Error {
+    Error::InvalidArchiveError(format!("{message} at offset {offset:#x}"))
+}
+
+/// Check that `mem` is an uncompressed initramfs the Linux kernel's unpacker (init/initramfs.c)
+/// accepts: one or more newc/crc segments, each ended by a trailer and separated by zero padding
+/// that keeps headers 4-byte aligned. Names must be null terminated and relative, and entries the
+/// kernel would silently drop (oversized names or symlink targets) are reported. `c_check` is
+/// ignored just like the kernel does.
+pub fn check_initramfs(mem: &[u8]) -> Result<(), Error> {
+    let mut offset = 0;
+    let mut segments = 0;
+
+    while offset < mem.len() {
+        // the kernel skips zero bytes between segments one at a time, but the next header still
+        // has to be aligned
+        if mem[offset] == 0 {
+            offset += 1;
+            continue;
+        }
+        if !offset.is_multiple_of(4) {
+            return Err(invalid(offset, "broken padding, segment does not start 4-byte aligned"))
+        }
+
+        offset = check_segment(mem, offset)?;
+        segments += 1;
+    }
+
+    if segments == 0 {
+        return Err(Error::InvalidArchiveError(String::from("no cpio segment found")))
+    }
+    Ok(())
+}
+
+/// Check the entries of a single segment starting at `start`, returning the offset just past
+/// its trailer
+fn check_segment(mem: &[u8], start: usize) -> Result<usize, Error> {
+    let mut offset = start;
+
+    loop {
+        if mem.len().saturating_sub(offset) < CPIO_HEADER_LEN {
+            return Err(invalid(offset, "truncated header, segment ends without a trailer"))
+        }
+
+        let magic = &mem[offset..offset + defs::CPIO_MAGIC_LEN];
+        let format = if magic == defs::NEWC_MAGIC {
+            CpioFormat::Newc
+        } else if magic == defs::CRC_MAGIC {
+            CpioFormat::Crc
+        } else if magic == defs::ODC_MAGIC {
+            return Err(invalid(offset, "odc header, the kernel only accepts newc/crc"))
+        } else {
+            return Err(invalid(offset, "no cpio magic"))
+        };
+
+        let entry = CpioEntry::new(offset, format, mem)?;
+        let namesize = entry.namesize()?;
+        if namesize == 0 || namesize > PATH_MAX {
+            return Err(invalid(offset, &format!("name size {namesize} is skipped by the kernel")))
+        }
+
+        let name = entry.name().map_err(|_| invalid(offset, "truncated entry name"))?;
+        let Some((0, name)) = name.split_last() else {
+            return Err(invalid(offset, "entry name is not null terminated"))
+        };
+        if name.contains(&0) {
+            return Err(invalid(offset, "entry name contains an embedded null byte"))
+        }
+        if name.starts_with(b"/") {
+            return Err(invalid(offset, &format!(
+                "absolute entry name '{}'", String::from_utf8_lossy(name)
+            )))
+        }
+
+        if entry.is_link()? && entry.filesize()? > PATH_MAX {
+            return Err(invalid(offset, "symlink target is longer than the kernel accepts"))
+        }
+
+        if offset + entry.file_content_offset()? + entry.filesize()? > mem.len() {
+            return Err(invalid(offset, "truncated entry content"))
+        }
+        let next = entry.next()?;
+
+        if name == b"TRAILER!!!" {
+            return Ok(next.min(mem.len()))
+        }
+        offset = next;
+    }
+}
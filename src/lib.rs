@@ -1,15 +1,51 @@
 mod defs;
+mod diagnose;
+mod index;
+mod initramfs;
+mod lz4;
+mod manifest;
+mod stream;
+mod tarball;
+pub use diagnose::{Diagnostic, Severity};
+pub use index::{CpioIndex, CpioIndexEntry};
+pub use initramfs::check_initramfs;
+pub use manifest::from_manifest;
+pub use stream::CpioStream;
+pub use tarball::from_tar;
+
 use defs::{CPIO_FIELD_LEN, CPIO_HEADER_LEN, CPIO_MAGIC_LEN};
 
-use std::fs::{create_dir, read_link, symlink_metadata, File};
-use std::io::{Read, Write};
+use std::cmp::Ordering;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::CString;
+use std::fs::{
+    create_dir, create_dir_all, hard_link, read_link, remove_file, rename, set_permissions,
+    symlink_metadata, File, Metadata, OpenOptions, Permissions
+};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::os::linux::fs::MetadataExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{lchown, symlink, PermissionsExt};
 use std::str::from_utf8;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use bzip2::bufread::MultiBzDecoder;
+use bzip2::write::BzEncoder;
 use fallible_iterator::FallibleIterator;
+use flate2::read::MultiGzDecoder;
 use flate2::write::GzEncoder;
-use flate2::Compression;
+use flate2::Compression as GzLevel;
+use xz2::read::XzDecoder;
+use xz2::stream::{Check, Stream};
+use xz2::write::XzEncoder;
+use lz4_flex::frame::FrameDecoder as Lz4Decoder;
+use lz4::{LegacyLz4Decoder, LegacyLz4Encoder};
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
 
 /// Error type for parsing cpio archives
 #[derive(thiserror::Error, Debug)]
@@ -32,20 +68,154 @@ pub enum Error {
     #[error("File system error: {0}")]
     FileSystemError(String),
 
-    #[error("Gzip encoder error: {0}")]
-    GzEncoderError(String),
+    #[error("Compression error: {0}")]
+    CompressionError(String),
+
+    #[error("Decompression error: {0}")]
+    DecompressionError(String),
 
     #[error("No such file in archive: {0}")]
     NoSuchFile(String),
 
+    #[error("Path already exists in archive: {0}")]
+    DuplicatePath(String),
+
     #[error("String encoding errror: {0}")]
     StringEncodingError(String),
+
+    #[error("Invalid entry path: {0}")]
+    InvalidPathError(String),
+
+    #[error("Path name too long: {0}")]
+    NameTooLongError(String),
+
+    #[error("Tar archive error: {0}")]
+    TarError(String),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(String),
+
+    #[error("Insufficient space for extraction: {0}")]
+    InsufficientSpaceError(String),
+
+    #[error("Output path is a directory: {0}")]
+    OutputIsDirectoryError(String),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CpioFormat {
     Newc,
     Crc,
+
+    /// The old portable format (`cpio -H odc`): 76 byte headers of octal fields, combined
+    /// device numbers, no checksum and no alignment padding
+    Odc,
+}
+
+impl CpioFormat {
+    pub(crate) fn magic(&self) -> &'static [u8] {
+        match self {
+            CpioFormat::Newc => defs::NEWC_MAGIC,
+            CpioFormat::Crc => defs::CRC_MAGIC,
+            CpioFormat::Odc => defs::ODC_MAGIC,
+        }
+    }
+
+    /// Size of an entry header
+    pub(crate) fn header_len(&self) -> usize {
+        match self {
+            CpioFormat::Newc | CpioFormat::Crc => CPIO_HEADER_LEN,
+            CpioFormat::Odc => defs::ODC_HEADER_LEN,
+        }
+    }
+
+    /// Alignment of entry names and contents, odc packs them without padding
+    pub(crate) fn alignment(&self) -> usize {
+        match self {
+            CpioFormat::Newc | CpioFormat::Crc => 4,
+            CpioFormat::Odc => 1,
+        }
+    }
+}
+
+/// Compression applied to a written archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+    Lz4,
+    Bzip2,
+}
+
+impl Compression {
+    /// Identify the compression of a file from its first bytes, `Compression::None` when it
+    /// isn't compressed in a format that can be read back
+    pub fn detect(magic: &[u8]) -> Compression {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(b"\xfd7zXZ\x00") {
+            Compression::Xz
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(&[0x04, 0x22, 0x4d, 0x18])
+            || magic.starts_with(&lz4::LEGACY_MAGIC.to_le_bytes()) {
+            Compression::Lz4
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wrap `reader` in a decoder for this compression. Concatenated gzip members, xz streams
+    /// and zstd, lz4 or bzip2 frames are decoded one after another, lz4 can be either the frame
+    /// or the legacy format.
+    pub(crate) fn decoder<'r, R: BufRead + 'r>(self, mut reader: R) -> Result<Box<dyn Read + 'r>, Error> {
+        match self {
+            Compression::None => Ok(Box::new(reader)),
+            Compression::Gzip => Ok(Box::new(MultiGzDecoder::new(reader))),
+            Compression::Xz => Ok(Box::new(XzDecoder::new_multi_decoder(reader))),
+            Compression::Zstd => Ok(Box::new(zstd::Decoder::with_buffer(reader).map_err(|e|
+                Error::DecompressionError(format!("failed to create zstd decoder: {e}"))
+            )?)),
+            Compression::Lz4 => {
+                let magic = reader.fill_buf().map_err(|e|
+                    Error::DecompressionError(format!("failed to read lz4 magic: {e}"))
+                )?;
+                if magic.starts_with(&lz4::LEGACY_MAGIC.to_le_bytes()) {
+                    Ok(Box::new(LegacyLz4Decoder::new(reader)))
+                } else {
+                    Ok(Box::new(Lz4Decoder::new(reader)))
+                }
+            },
+            Compression::Bzip2 => Ok(Box::new(MultiBzDecoder::new(reader))),
+        }
+    }
+}
+
+/// Magics of formats commonly mistaken for a raw cpio archive: offset, magic and description
+const FOREIGN_MAGICS: &[(usize, &[u8], &str)] = &[
+    (0,   b"\x1f\x8b",                 "a gzip compressed file"),
+    (0,   b"\x28\xb5\x2f\xfd",         "a zstd compressed file"),
+    (0,   b"\xfd7zXZ\x00",              "an xz compressed file"),
+    (0,   b"BZh",                      "a bzip2 compressed file"),
+    (0,   b"\x04\x22\x4d\x18",         "an lz4 compressed file"),
+    (0,   b"\x02\x21\x4c\x18",         "an lz4 compressed file"),
+    (0,   b"\x89LZO",                  "an lzo compressed file"),
+    (0,   b"PK\x03\x04",               "a zip archive"),
+    (257, b"ustar",                    "a tar archive"),
+    (0,   b"\xc7\x71",                 "an old binary cpio archive"),
+    (0,   b"\x71\xc7",                 "an old binary cpio archive"),
+];
+
+/// Describe what `mem` looks like if it starts with the magic of a known non-cpio format
+fn sniff_foreign_format(mem: &[u8]) -> Option<&'static str> {
+    FOREIGN_MAGICS.iter()
+        .find(|(offset, magic, _)| mem.get(*offset..).is_some_and(|rest| rest.starts_with(magic)))
+        .map(|(_, _, description)| *description)
 }
 
 fn identify_format(mem: &[u8]) -> Result<CpioFormat, Error> {
@@ -53,13 +223,22 @@ fn identify_format(mem: &[u8]) -> Result<CpioFormat, Error> {
         Ok(CpioFormat::Newc)
     } else if mem.starts_with(defs::CRC_MAGIC){
         Ok(CpioFormat::Crc)
+    } else if mem.starts_with(defs::ODC_MAGIC) {
+        Ok(CpioFormat::Odc)
+    } else if let Some(description) = sniff_foreign_format(mem) {
+        Err(Error::InvalidArchiveError(format!(
+            "Unrecognized Format: this looks like {description}, not a raw cpio archive"
+        )))
     } else {
         Err(Error::InvalidArchiveError(String::from("Unrecognized Format")))
     }
 }
 
-/// Convert the file permissions portion of a file mode to a representative string
-fn mode_perm_to_str(mode: u64, shift: usize) -> String {
+/// Convert the file permissions portion of a file mode to a representative string. `special`
+/// is the setuid, setgid or sticky bit sharing this triple's execute slot, shown as `symbol`
+/// (uppercase when the execute bit itself is clear).
+fn mode_perm_to_str(mode: u64, shift: usize, special: u64, symbol: char) -> String {
+    let special = mode & special != 0;
     let mode = (mode >> shift) & 0o7;
     let mut perm_string = String::new();
 
@@ -75,10 +254,11 @@ fn mode_perm_to_str(mode: u64, shift: usize) -> String {
         perm_string.push('-');
     }
 
-    if mode & defs::MODE_X != 0 {
-        perm_string.push('x');
-    } else {
-        perm_string.push('-');
+    match (mode & defs::MODE_X != 0, special) {
+        (true, true) => perm_string.push(symbol),
+        (false, true) => perm_string.push(symbol.to_ascii_uppercase()),
+        (true, false) => perm_string.push('x'),
+        (false, false) => perm_string.push('-'),
     }
 
     perm_string
@@ -99,46 +279,71 @@ fn mode_to_str(mode: u64) -> Result<String, Error> {
         defs::S_IFBLK  => mode_str.push('b'),
         defs::S_IFDIR  => mode_str.push('d'),
         defs::S_IFCHR  => mode_str.push('c'),
-        defs::S_IFIFO  => mode_str.push('b'),
+        defs::S_IFIFO  => mode_str.push('p'),
         _ => {
             return Err(Error::FileModeError(format!("{mode:o}")))
         }
     }
 
-    mode_str.push_str(&mode_perm_to_str(mode, 6));
-    mode_str.push_str(&mode_perm_to_str(mode, 3));
-    mode_str.push_str(&mode_perm_to_str(mode, 0));
+    mode_str.push_str(&mode_perm_to_str(mode, 6, defs::S_ISUID, 's'));
+    mode_str.push_str(&mode_perm_to_str(mode, 3, defs::S_ISGID, 's'));
+    mode_str.push_str(&mode_perm_to_str(mode, 0, defs::S_ISVTX, 't'));
 
     Ok(mode_str)
 }
 
-struct CpioBuilderEntry {
-    c_ino       : u32,
-    c_mode      : u32,
-    c_uid       : u32,
-    c_gid       : u32,
-    c_nlink     : u32,
-    c_mtime     : u32,
-    c_filesize  : u32,
-    c_devmajor  : u32,
-    c_devminor  : u32,
-    c_rdevmajor : u32,
-    c_rdevminor : u32,
-    c_namesize  : u32,
-    c_check     : u32,
+/// The numeric fields of an entry header. Odc headers store a single combined device number
+/// for each of dev and rdev and have no check field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpioHeader {
+    pub c_ino       : u32,
+    pub c_mode      : u32,
+    pub c_uid       : u32,
+    pub c_gid       : u32,
+    pub c_nlink     : u32,
+    pub c_mtime     : u32,
+    pub c_filesize  : u32,
+    pub c_devmajor  : u32,
+    pub c_devminor  : u32,
+    pub c_rdevmajor : u32,
+    pub c_rdevminor : u32,
+    pub c_namesize  : u32,
+    pub c_check     : u32,
+}
+
+/// Encode the header for `fields` in the given format: the magic followed by each field as eight
+/// lowercase hex digits for newc/crc, or as fixed width octal for odc
+pub fn encode_header(fields: &CpioHeader, format: CpioFormat) -> Vec<u8> {
+    fields.to_bytes(&format)
 }
 
-impl CpioBuilderEntry {
+impl CpioHeader {
+    /// Header for an entry with no filesystem backing, owned by root with a zero mtime
+    pub fn synthetic(ino: u32, mode: u32) -> Self {
+        let nlink = if mode as u64 & defs::S_IFMT == defs::S_IFDIR { 2 } else { 1 };
+        CpioHeader {
+            c_ino       : ino,
+            c_mode      : mode,
+            c_uid       : 0,
+            c_gid       : 0,
+            c_nlink     : nlink,
+            c_mtime     : 0,
+            c_filesize  : 0,
+            c_devmajor  : 0,
+            c_devminor  : 0,
+            c_rdevmajor : 0,
+            c_rdevminor : 0,
+            c_namesize  : 0,
+            c_check     : 0,
+        }
+    }
+
     pub(crate) fn to_bytes(&self, format: &CpioFormat) -> Vec<u8> {
-        let mut out = vec![];
+        let mut out = format.magic().to_vec();
 
-        match format {
-            CpioFormat::Newc => {
-                out.append(&mut defs::NEWC_MAGIC.to_vec());
-            },
-            CpioFormat::Crc => {
-                out.append(&mut defs::CRC_MAGIC.to_vec());
-            },
+        if *format == CpioFormat::Odc {
+            out.append(&mut self.to_odc_fields());
+            return out
         }
 
         let mut entry_str = String::new();
@@ -157,9 +362,38 @@ impl CpioBuilderEntry {
         entry_str.push_str(&format!("{:08x}", &self.c_namesize));
         entry_str.push_str(&format!("{:08x}", &self.c_check));
 
-        out.append(&mut entry_str.to_uppercase().as_bytes().to_vec());
+        out.append(&mut entry_str.as_bytes().to_vec());
         out
     }
+
+    /// The odc header fields as fixed width octal. Values too wide for their field keep only
+    /// their low bits, the 6 digit fields hold 18 bits so large inode numbers need
+    /// `CpioBuilder::sequential_inodes` to stay unique.
+    fn to_odc_fields(&self) -> Vec<u8> {
+        fn octal(value: u64, width: usize) -> String {
+            format!("{:0width$o}", value & ((1 << (3 * width)) - 1))
+        }
+
+        let dev = makedev(self.c_devmajor, self.c_devminor);
+        let rdev = makedev(self.c_rdevmajor, self.c_rdevminor);
+        [
+            octal(dev as u64, 6),
+            octal(self.c_ino as u64, 6),
+            octal(self.c_mode as u64, 6),
+            octal(self.c_uid as u64, 6),
+            octal(self.c_gid as u64, 6),
+            octal(self.c_nlink as u64, 6),
+            octal(rdev as u64, 6),
+            octal(self.c_mtime as u64, 11),
+            octal(self.c_namesize as u64, 6),
+            octal(self.c_filesize as u64, 11),
+        ].concat().into_bytes()
+    }
+}
+
+/// Combine a major and minor number the way glibc's `makedev` does for 32-bit values
+fn makedev(major: u32, minor: u32) -> u32 {
+    ((major & 0xfff) << 8) | (minor & 0xff) | ((minor & 0xfff00) << 12)
 }
 
 fn major(dev: u32) -> u32 {
@@ -170,19 +404,403 @@ fn minor(dev: u32) -> u32 {
     (dev & 0xff) | ((dev >> 12) & 0xfff00) // minor is bits 0–7 and 20–31
 }
 
+/// Walk `dir` parents first, returning each file's path along with its archive path below
+/// `prefix` and whether it is a directory
+fn walk_tree(dir: &Path, prefix: &str) -> Result<Vec<(PathBuf, String, bool)>, Error> {
+    let prefix = prefix.trim_matches('/');
+    let mut files = vec![];
+
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry.map_err(|e|
+            Error::FileSystemError(format!("Failed to walk {}: {e}", dir.display()))
+        )?;
+
+        let relative = entry.path().strip_prefix(dir).map_err(|_|
+            Error::FileSystemError(format!("Walked outside of {}", dir.display()))
+        )?;
+        let relative = relative.to_str().ok_or_else(||
+            Error::StringEncodingError(format!("Non UTF-8 path {}", entry.path().display()))
+        )?;
+
+        let archive_path = match (prefix.is_empty(), relative.is_empty()) {
+            (true, true) => String::from("."),
+            (true, false) => relative.to_string(),
+            (false, true) => prefix.to_string(),
+            (false, false) => format!("{prefix}/{relative}"),
+        };
+
+        files.push((entry.path().to_path_buf(), archive_path, entry.file_type().is_dir()));
+    }
+
+    Ok(files)
+}
+
+/// Archive the directory tree at `dir` with `dir` itself as the archive root, returning what
+/// was written
+pub fn archive_directory(
+    dir: &Path,
+    output: &Path,
+    format: CpioFormat,
+    compression: Compression
+) -> Result<ArchiveSummary, Error> {
+    archive_trees(&[(dir.to_path_buf(), String::new())], output, format, compression)
+}
+
+/// Build a single archive from several directory trees, each placed under its archive path
+/// prefix. Trees are overlaid in order: when two trees provide the same path the later one
+/// wins, and unless both are directories the path is listed in `ArchiveSummary::overridden`.
+pub fn archive_trees(
+    sources: &[(PathBuf, String)],
+    output: &Path,
+    format: CpioFormat,
+    compression: Compression
+) -> Result<ArchiveSummary, Error> {
+    let mut files: Vec<(PathBuf, String, bool)> = vec![];
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut overridden = vec![];
+
+    for (dir, prefix) in sources {
+        for file in walk_tree(dir, prefix)? {
+            if let Some(&index) = positions.get(&file.1) {
+                if !(files[index].2 && file.2) {
+                    overridden.push(file.1.clone());
+                }
+                // replace in place so parents still come before their children
+                files[index] = file;
+            } else {
+                positions.insert(file.1.clone(), files.len());
+                files.push(file);
+            }
+        }
+    }
+
+    let mut builder = CpioBuilder::new(format);
+    for (fs_path, archive_path, _) in &files {
+        builder.insert(fs_path, archive_path)?;
+    }
+    let summary = builder.write(output, compression)?;
+    Ok(ArchiveSummary { overridden, ..summary })
+}
+
+/// Where the metadata and content of a builder entry come from
+enum CpioBuilderSource {
+    /// Stat and read a file from the filesystem when the archive is written
+    Path(PathBuf),
+
+    /// Metadata and content supplied directly by the caller
+    Data {
+        header: CpioHeader,
+        content: Vec<u8>,
+
+        /// Precomputed crc checksum of the content
+        check: Option<u32>,
+    },
+}
+
+/// Content of a builder entry being written: held in memory, or the first `len` bytes of a
+/// file which are copied over in chunks
+enum SourceContent<'s> {
+    Memory(Cow<'s, [u8]>),
+    File(&'s Path, u64),
+}
+
+impl SourceContent<'_> {
+    fn len(&self) -> u64 {
+        match self {
+            SourceContent::Memory(content) => content.len() as u64,
+            SourceContent::File(_, len) => *len,
+        }
+    }
+}
+
+/// Header, content and precomputed crc check of a builder entry, ready to encode
+type SourceEntry<'s> = (CpioHeader, SourceContent<'s>, Option<u32>);
+
+/// Writes entries to an archive stream, keeping track of the offset for alignment
+struct ArchiveWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    format: CpioFormat,
+    buffer_size: usize,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(bytes).map_err(|e|
+            Error::FileSystemError(format!("failed to write data to archive: {e}"))
+        )?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Zero-pad to the next multiple of `align`
+    fn pad(&mut self, align: u64) -> Result<(), Error> {
+        let padding = self.offset.next_multiple_of(align) - self.offset;
+        self.write(&vec![0; padding as usize])
+    }
+
+    /// Write a complete entry record like `encode_entry` does, copying file content in chunks.
+    /// Without a precomputed `check`, a crc entry's content is read twice: once for the
+    /// checksum, which the header needs up front, and once to copy it.
+    fn write_entry(
+        &mut self,
+        mut entry: CpioHeader,
+        name: &[u8],
+        content: &SourceContent,
+        check: Option<u32>
+    ) -> Result<(), Error> {
+        entry.c_filesize = content.len() as u32;
+        entry.c_namesize = (name.len() + 1) as u32;
+        entry.c_check = match (self.format, check, content) {
+            (CpioFormat::Newc | CpioFormat::Odc, _, _) => 0,
+            (CpioFormat::Crc, Some(check), SourceContent::Memory(content)) => {
+                debug_assert_eq!(check, entry_checksum(&entry, content), "precomputed checksum mismatch");
+                check
+            },
+            (CpioFormat::Crc, Some(check), _) => check,
+            (CpioFormat::Crc, None, SourceContent::Memory(content)) => entry_checksum(&entry, content),
+            (CpioFormat::Crc, None, SourceContent::File(fs_path, len)) => {
+                let mut sum = 0u32;
+                read_chunks(fs_path, self.buffer_size, *len, |chunk| {
+                    sum = sum.wrapping_add(checksum(chunk));
+                    Ok(())
+                })?;
+                sum
+            },
+        };
+
+        let align = self.format.alignment() as u64;
+        self.write(&entry.to_bytes(&self.format))?;
+        self.write(name)?;
+        self.write(&[0])?;
+        self.pad(align)?;
+
+        match content {
+            SourceContent::Memory(content) => self.write(content)?,
+            SourceContent::File(fs_path, len) => {
+                // a file that grew since it was stat'ed is cut at the size in the header
+                let copied = read_chunks(fs_path, self.buffer_size, *len, |chunk| self.write(chunk))?;
+                if copied != *len {
+                    return Err(Error::FileSystemError(format!(
+                        "{} shrank while it was archived", fs_path.display()
+                    )))
+                }
+            },
+        }
+        self.pad(align)
+    }
+}
+
+/// Builds a cpio archive from files on disk and/or data held in memory
+///
+/// Archives can be produced without touching the filesystem at all:
+///
+/// ```
+/// use rcpio::{Cpio, CpioBuilder, CpioFormat};
+///
+/// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+/// builder.insert_dir("etc", 0o755).unwrap();
+/// builder.insert_data("etc/hostname", b"rcpio\n", 0o644).unwrap();
+/// builder.insert_symlink("hostname", "etc/hostname").unwrap();
+///
+/// let bytes = builder.to_vec().unwrap();
+/// let cpio = Cpio::load(&bytes).unwrap();
+/// assert_eq!(cpio.entries_by_owner(None, None).unwrap(), ["etc", "etc/hostname", "hostname"]);
+/// ```
 pub struct CpioBuilder {
     format: CpioFormat,
-    entries: Vec<(PathBuf, String)>
+    entries: Vec<(CpioBuilderSource, String)>,
+
+    /// Block size the end of the archive is padded to, `None` for no padding
+    block_size: Option<usize>,
+
+    /// Write the `.` entry for the archive root
+    include_root: bool,
+
+    /// Chunk size for reading source files and writing the archive
+    read_buffer_size: usize,
+
+    /// Number inodes 1..N in write order instead of using the source inodes
+    sequential_inodes: bool,
+
+    /// Normalized archive paths written as hardlink sets
+    hardlink_groups: Vec<Vec<String>>,
+
+    /// Store names as `./path` the way `find . | cpio -o` does
+    gnu_dot_prefix: bool,
+
+    /// Zero the mtime, owner and device of every entry and number inodes sequentially
+    reproducible: bool,
 }
 
-fn entry_bytes(
-    fs_path: &Path,
-    internal_path: &str,
+/// The SVR4 crc checksum: a wrapping 32-bit sum of the content bytes, so contents whose byte
+/// sum exceeds `u32::MAX` wrap around instead of overflowing
+pub fn checksum(content: &[u8]) -> u32 {
+    let mut res = 0u32;
+    for b in content {
+        res = res.wrapping_add(*b as u32);
+    }
+    res
+}
+
+/// Reject an archive output path that is an existing directory before doing any work
+fn check_output_path(path: &Path) -> Result<(), Error> {
+    if path.is_dir() {
+        return Err(Error::OutputIsDirectoryError(
+            format!("{} is a directory, specify a file", path.display())
+        ))
+    }
+    Ok(())
+}
+
+/// A sibling temporary file that is renamed over `path` by `commit` once everything has been
+/// written and flushed, so a partially written archive is never published. Dropping it without
+/// committing removes the temporary file and leaves `path` untouched.
+struct AtomicFile {
+    file: File,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    fn create(path: &Path) -> Result<Self, Error> {
+        check_output_path(path)?;
+
+        let file_name = path.file_name().ok_or_else(||
+            Error::FileSystemError(format!("Invalid output file path {}", path.to_string_lossy()))
+        )?;
+
+        let nonce = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let mut tmp_name = file_name.to_os_string();
+        tmp_name.push(format!(".tmp-{}-{nonce:08x}", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let file = File::create(&tmp_path).map_err(|_|
+            Error::FileSystemError(
+                format!("Failed to create output file {}", tmp_path.to_string_lossy())
+            )
+        )?;
+        Ok(AtomicFile { file, path: path.to_path_buf(), tmp_path, committed: false })
+    }
+
+    fn commit(mut self) -> Result<(), Error> {
+        self.file.sync_all().map_err(|_|
+            Error::FileSystemError(
+                format!("Failed to flush output file {}", self.tmp_path.to_string_lossy())
+            )
+        )?;
+        rename(&self.tmp_path, &self.path).map_err(|_|
+            Error::FileSystemError(
+                format!("Failed to rename output file into place {}", self.path.to_string_lossy())
+            )
+        )?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = remove_file(&self.tmp_path);
+        }
+    }
+}
+
+/// Write an archive to a sibling temporary file and rename it over `path` only once `write`
+/// succeeds and the data is flushed, so a partially written archive is never published
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> Result<(), Error>
+) -> Result<(), Error> {
+    let mut out = AtomicFile::create(path)?;
+    write(&mut out.file)?;
+    out.commit()
+}
+
+/// Atomically replace `path` with `data`
+fn write_archive_file(path: &Path, data: &[u8]) -> Result<(), Error> {
+    write_atomic(path, |out_fp| {
+        out_fp.write_all(data).map_err(|_|
+            Error::FileSystemError(String::from("failed to write data to archive file"))
+        )
+    })
+}
+
+/// The crc check field for an entry, only regular file data is checksummed, symlink targets
+/// and other types get a zero check
+fn entry_checksum(entry: &CpioHeader, content: &[u8]) -> u32 {
+    if entry.c_mode as u64 & defs::S_IFMT == defs::S_IFREG {
+        checksum(content)
+    } else {
+        0
+    }
+}
+
+/// Encode a complete entry record: header, null-terminated name, content and alignment padding.
+/// A precomputed crc `check` is used as-is instead of summing the content again.
+fn encode_entry(
+    mut entry: CpioHeader,
+    name: &[u8],
+    content: &[u8],
     curr_len: usize,
     format: CpioFormat,
-    inode_override: Option<u32>
-) -> Result<Vec<u8>, Error> {
-    let symlink_meta = symlink_metadata(fs_path).map_err(|e| {
+    check: Option<u32>,
+) -> Vec<u8> {
+    entry.c_filesize = content.len() as u32;
+    entry.c_namesize = (name.len() + 1) as u32;
+    entry.c_check = match (format, check) {
+        (CpioFormat::Newc | CpioFormat::Odc, _) => 0,
+        (CpioFormat::Crc, Some(check)) => {
+            debug_assert_eq!(check, entry_checksum(&entry, content), "precomputed checksum mismatch");
+            check
+        },
+        (CpioFormat::Crc, None) => entry_checksum(&entry, content),
+    };
+
+    let mut entry_data: Vec<u8> = vec![];
+
+    entry_data.append(&mut entry.to_bytes(&format));
+
+    // null-terminated internal path
+    entry_data.extend_from_slice(name);
+    entry_data.push(0);
+
+    // pad to the format's alignment before start of file contents
+    let align = format.alignment();
+    let curr = curr_len + entry_data.len();
+    entry_data.resize(entry_data.len() + (curr.next_multiple_of(align) - curr), 0);
+
+    entry_data.extend_from_slice(content);
+
+    // pad to the format's alignment at the end of file contents
+    let curr = curr_len + entry_data.len();
+    entry_data.resize(entry_data.len() + (curr.next_multiple_of(align) - curr), 0);
+
+    entry_data
+}
+
+/// Stat a file on the filesystem (without following symlinks) and gather its header fields,
+/// the size, name and check fields are filled in when the entry is encoded
+fn fs_header(fs_path: &Path, inode_override: Option<u32>) -> Result<(CpioHeader, Metadata), Error> {
+    let meta = symlink_metadata(fs_path).map_err(|e| {
         Error::FileSystemError(
             format!(
                 "Failed to get metadata for symlink, {e}: {}",
@@ -191,275 +809,3023 @@ fn entry_bytes(
         )
     })?;
 
-    let mut content = vec![];
-    let meta = if !symlink_meta.is_symlink() {
-        let mut fp = File::open(fs_path).map_err(|_|
-            Error::FileSystemError(
-                format!("failed to read to end of file {}", fs_path.to_string_lossy())
-            )
-        )?;
-        let meta = fp.metadata().map_err(|_| {
-            Error::FileSystemError(
-                format!("Failed to get metadata for file {}", fs_path.to_string_lossy())
-            )
-        })?;
+    let inode = if let Some(inode) = inode_override {
+        inode
+    } else {
+        meta.st_ino() as u32
+    };
 
-        // nothing to read if path is "."
-        if internal_path != "." && meta.is_file() {
-            fp.read_to_end(&mut content).map_err(|_|
-                Error::FileSystemError(
-                    format!("failed to read to end of file {}", fs_path.to_string_lossy())
-                )
-            )?;
+    let entry = CpioHeader {
+        c_ino       : inode,
+        c_mode      : meta.st_mode(),
+        c_uid       : meta.st_uid(),
+        c_gid       : meta.st_gid(),
+        c_nlink     : meta.st_nlink() as u32,
+        c_mtime     : meta.st_mtime() as u32,
+        c_filesize  : 0,
+        c_devmajor  : major(meta.st_dev() as u32),
+        c_devminor  : minor(meta.st_dev() as u32),
+        c_rdevmajor : major(meta.st_rdev() as u32),
+        c_rdevminor : minor(meta.st_rdev() as u32),
+        c_namesize  : 0,
+        c_check     : 0,
+    };
+
+    Ok((entry, meta))
+}
+
+/// The target of the symlink at `fs_path`, which is stored where the file content would be
+fn symlink_target(fs_path: &Path) -> Result<Vec<u8>, Error> {
+    let target_path = read_link(fs_path).map_err(|_| {
+        Error::FileSystemError(
+            format!("Failed to read symlink target for {}", fs_path.to_string_lossy())
+        )
+    })?;
+    Ok(target_path.to_string_lossy().as_bytes().to_vec())
+}
+
+/// Whether a filesystem entry has content to read from the file itself. Only regular files are
+/// opened, opening a FIFO would block until a writer appears.
+fn has_file_content(meta: &Metadata, internal_path: &str) -> bool {
+    meta.is_file() && internal_path != "."
+}
+
+/// Open `fs_path` and feed its content to `chunk` in pieces of `buffer_size`, stopping after
+/// `limit` bytes. Returns the number of bytes read, which is short of `limit` if the file ended
+/// first.
+fn read_chunks(
+    fs_path: &Path,
+    buffer_size: usize,
+    limit: u64,
+    mut chunk: impl FnMut(&[u8]) -> Result<(), Error>
+) -> Result<u64, Error> {
+    let read_error = || Error::FileSystemError(
+        format!("failed to read to end of file {}", fs_path.to_string_lossy())
+    );
+
+    let mut fp = File::open(fs_path).map_err(|_| read_error())?.take(limit);
+    let mut buf = vec![0u8; buffer_size];
+    let mut total = 0;
+    loop {
+        let read = fp.read(&mut buf).map_err(|_| read_error())?;
+        if read == 0 {
+            return Ok(total)
         }
-        meta
+        chunk(&buf[..read])?;
+        total += read as u64;
+    }
+}
+
+/// Gather the header fields and content for a file on the filesystem, a regular file's content
+/// is left on disk to be copied when the entry is written
+fn fs_source_entry<'s>(fs_path: &'s Path, internal_path: &str) -> Result<SourceEntry<'s>, Error> {
+    let (header, meta) = fs_header(fs_path, None)?;
+    let content = if meta.is_symlink() {
+        SourceContent::Memory(Cow::Owned(symlink_target(fs_path)?))
+    } else if has_file_content(&meta, internal_path) {
+        SourceContent::File(fs_path, meta.len())
     } else {
-        // for symlinks the target path goes where the file content would
-        let target_path = read_link(fs_path).map_err(|_| {
-            Error::FileSystemError(
-                format!("Failed to read symlink target for {}", fs_path.to_string_lossy())
-            )
-        })?;
-        content.append(&mut target_path.to_string_lossy().to_string().as_bytes().to_vec());
-        symlink_meta
+        // directories, devices, FIFOs and sockets have no content
+        SourceContent::Memory(Cow::Borrowed(&[]))
+    };
+    Ok((header, content, None))
+}
+
+/// A writer that discards its input, only counting the bytes written to it
+#[derive(Default)]
+struct CountingWriter {
+    count: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.count += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Zero-pad `out` to a multiple of `block_size`
+fn pad_to_block(out: &mut Vec<u8>, block_size: Option<usize>) {
+    if let Some(block_size) = block_size.filter(|size| *size != 0) {
+        if !out.len().is_multiple_of(block_size) {
+            out.resize(out.len() + (block_size - (out.len() % block_size)), 0)
+        }
+    }
+}
+
+fn trailer_bytes(format: CpioFormat) -> Vec<u8> {
+    let header = CpioHeader {
+        c_nlink: 1,
+        c_namesize: (defs::TRAILER_NAME.len() + 1) as u32,
+        ..Default::default()
     };
 
-    let check: u32 = match format {
-        CpioFormat::Newc => 0,
-        CpioFormat::Crc => {
-            let mut res = 0u32;
-            for b in &content {
-                res = res.wrapping_add(*b as u32);
-            }
-            res
-        }
-    };
+    let mut out = header.to_bytes(&format);
+    out.extend_from_slice(defs::TRAILER_NAME);
+    out.push(0);
+    out
+}
+
+impl CpioBuilder {
+    pub fn new(format: CpioFormat) -> Self {
+        CpioBuilder {
+            format,
+            entries: vec![],
+            block_size: Some(defs::CPIO_BLOCK_SIZE),
+            include_root: true,
+            read_buffer_size: defs::READ_BUFFER_SIZE,
+            sequential_inodes: false,
+            hardlink_groups: vec![],
+            gnu_dot_prefix: false,
+            reproducible: false,
+        }
+    }
+
+    /// Whether to write the `.` entry for the archive root (default true, as GNU cpio does).
+    /// Without it, extraction creates the parents of the remaining entries as needed.
+    pub fn include_root(&mut self, include_root: bool) -> &mut Self {
+        self.include_root = include_root;
+        self
+    }
+
+    /// Chunk size used when reading source files and writing the archive out (default 64 KiB).
+    /// Larger chunks mean fewer syscalls on fast storage, smaller ones less memory on
+    /// constrained devices. A size of zero is treated as one byte.
+    pub fn read_buffer_size(&mut self, size: usize) -> &mut Self {
+        self.read_buffer_size = size.max(1);
+        self
+    }
+
+    /// Number entries 1..N in write order instead of storing the inodes of the source files, so
+    /// the same tree archives identically wherever it lives. Hardlinked files still share one
+    /// number (hardlinks are only preserved because of that), so N is the number of distinct
+    /// files rather than entries.
+    pub fn sequential_inodes(&mut self, sequential: bool) -> &mut Self {
+        self.sequential_inodes = sequential;
+        self
+    }
+
+    /// Write every entry with a zero mtime, uid, gid and device number and with sequential
+    /// inodes (see `sequential_inodes`, hardlinks keep sharing theirs), so the same tree gives
+    /// a byte-identical archive on any machine and checkout, like `cpio --reproducible` or
+    /// `gen_init_cpio`. Entries are still written in insertion order, sort them with `sort_by`
+    /// if that order comes from the filesystem.
+    pub fn reproducible(&mut self, reproducible: bool) -> &mut Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// Store every name with a leading `./` (the root stays `.`), matching the names GNU cpio
+    /// writes for `find . | cpio -o -H newc`. Paths given to the other builder methods stay
+    /// unprefixed.
+    pub fn gnu_dot_prefix(&mut self, dot_prefix: bool) -> &mut Self {
+        self.gnu_dot_prefix = dot_prefix;
+        self
+    }
+
+    /// Archive paths of the entries that will be written, in order
+    pub fn archive_paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter()
+            .map(|(_, internal_path)| internal_path.as_str())
+            .filter(|internal_path| self.include_root || *internal_path != ".")
+    }
+
+    /// Set the block size the end of the archive is padded to (default 512, the block size
+    /// the kernel's initramfs loader expects), `None` disables the trailing padding. An
+    /// archive that already ends on a block boundary gets no extra block.
+    pub fn block_size(&mut self, block_size: Option<usize>) -> &mut Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn insert(
+        &mut self, fs_path: &Path,
+        archive_path: &str
+    ) -> Result<(), Error>{
+        let archive_path = if archive_path.is_empty() {
+            "."
+        } else {
+            archive_path
+        };
+
+        self.entries.push((CpioBuilderSource::Path(fs_path.to_path_buf()), archive_path.to_string()));
+
+        Ok(())
+    }
+
+    /// Insert every file below `dir`, including `dir` itself, rebased under the archive path
+    /// `prefix` (an empty prefix places the tree at the archive root)
+    pub fn insert_tree(&mut self, dir: &Path, prefix: &str) -> Result<(), Error> {
+        for (fs_path, archive_path, _) in walk_tree(dir, prefix)? {
+            self.insert(&fs_path, &archive_path)?;
+        }
+        Ok(())
+    }
+
+    /// Write the already inserted regular files at `paths` as one hardlink set: they share the
+    /// inode of the first member written, `c_nlink` is the size of the group and only the last
+    /// member written carries data (its own), the others are empty. The group is resolved when
+    /// the archive is written so it follows any later `sort_by`.
+    pub fn hardlink_group(&mut self, paths: &[&str]) -> Result<(), Error> {
+        if paths.is_empty() {
+            return Err(Error::InvalidPathError(String::from("A hardlink group needs at least one path")))
+        }
+
+        let mut group = vec![];
+        for path in paths {
+            let name = normalize_internal_path(path)?;
+            let inserted = self.entries.iter()
+                .any(|(_, internal_path)| normalize_internal_path(internal_path).is_ok_and(|p| p == name));
+            if !inserted {
+                return Err(Error::NoSuchFile(path.to_string()))
+            }
+            if group.contains(&name) || self.hardlink_groups.iter().any(|group| group.contains(&name)) {
+                return Err(Error::InvalidPathError(format!("'{path}' is already in a hardlink group")))
+            }
+            group.push(name);
+        }
+
+        self.hardlink_groups.push(group);
+        Ok(())
+    }
+
+    /// Insert an entry from explicit header fields, the size and check fields are computed when
+    /// the archive is written
+    pub fn insert_header(
+        &mut self,
+        archive_path: &str,
+        header: CpioHeader,
+        content: Vec<u8>
+    ) -> Result<(), Error> {
+        if archive_path.is_empty() {
+            return Err(Error::InvalidArchiveError(String::from("Archive path must not be empty")))
+        }
+
+        let source = CpioBuilderSource::Data { header, content, check: None };
+        self.entries.push((source, archive_path.to_string()));
+
+        Ok(())
+    }
+
+    /// Insert an entry from explicit metadata, `mode` must include the file type bits
+    fn insert_synthetic(&mut self, archive_path: &str, mode: u32, content: Vec<u8>) -> Result<(), Error> {
+        // synthetic entries have no backing inode, number them by position so each is unique
+        let ino = self.entries.len() as u32 + 1;
+        self.insert_header(archive_path, CpioHeader::synthetic(ino, mode), content)
+    }
+
+    /// Insert a regular file with the given content and permission bits
+    pub fn insert_data(&mut self, archive_path: &str, content: &[u8], mode: u32) -> Result<(), Error> {
+        let mode = defs::S_IFREG as u32 | (mode & 0o7777);
+        self.insert_synthetic(archive_path, mode, content.to_vec())
+    }
+
+    /// Insert a regular file whose crc checksum is already known, the checksum is written to the
+    /// check field as-is rather than recomputed (debug builds assert that it matches)
+    pub fn insert_data_with_checksum(
+        &mut self,
+        archive_path: &str,
+        content: &[u8],
+        mode: u32,
+        check: u32
+    ) -> Result<(), Error> {
+        self.insert_data(archive_path, content, mode)?;
+        if let Some((CpioBuilderSource::Data { check: entry_check, .. }, _)) = self.entries.last_mut() {
+            *entry_check = Some(check);
+        }
+        Ok(())
+    }
+
+    /// Insert a regular file generated in memory with explicit metadata, `mode` gives its
+    /// permission bits:
+    ///
+    /// ```
+    /// use rcpio::{Cpio, CpioBuilder, CpioFormat};
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// builder.insert_bytes(b"#!/bin/sh\nexec /bin/sh\n".to_vec(), "init", 0o755, 1700000000, 0, 0).unwrap();
+    ///
+    /// let archive = builder.to_vec().unwrap();
+    /// let init = Cpio::load(&archive).unwrap().find("init").unwrap().unwrap();
+    /// assert_eq!(init.mtime().unwrap(), 1700000000);
+    /// assert_eq!(init.mode_str().unwrap(), "-rwxr-xr-x");
+    /// ```
+    pub fn insert_bytes(
+        &mut self,
+        content: Vec<u8>,
+        archive_path: &str,
+        mode: u32,
+        mtime: u32,
+        uid: u32,
+        gid: u32
+    ) -> Result<(), Error> {
+        let ino = self.entries.len() as u32 + 1;
+        let header = CpioHeader {
+            c_mtime: mtime,
+            c_uid: uid,
+            c_gid: gid,
+            ..CpioHeader::synthetic(ino, defs::S_IFREG as u32 | (mode & 0o7777))
+        };
+        self.insert_header(archive_path, header, content)
+    }
+
+    /// Insert a directory with the given permission bits
+    pub fn insert_dir(&mut self, archive_path: &str, mode: u32) -> Result<(), Error> {
+        let mode = defs::S_IFDIR as u32 | (mode & 0o7777);
+        self.insert_synthetic(archive_path, mode, vec![])
+    }
+
+    /// Insert a symbolic link pointing at `target`
+    pub fn insert_symlink(&mut self, archive_path: &str, target: &str) -> Result<(), Error> {
+        let mode = defs::S_IFLNK as u32 | 0o777;
+        self.insert_synthetic(archive_path, mode, target.as_bytes().to_vec())
+    }
+
+    /// Reorder the entries by comparing their archive paths, entries are written in the resulting
+    /// order. Directories are still kept ahead of anything inside them so the archive remains
+    /// extractable whatever the comparator does.
+    pub fn sort_by(&mut self, cmp: impl Fn(&str, &str) -> Ordering) {
+        self.entries.sort_by(|a, b| cmp(&a.1, &b.1));
+
+        let names: Vec<String> = self.entries.iter()
+            .map(|(_, name)| normalize_internal_path(name).unwrap_or_else(|_| name.clone()))
+            .collect();
+        let positions: HashMap<&str, usize> = names.iter()
+            .enumerate()
+            .map(|(index, name)| (name.as_str(), index))
+            .collect();
+
+        let parent_of = |index: usize| -> Option<usize> {
+            let name = &names[index];
+            let parent = match name.rsplit_once('/') {
+                Some((parent, _)) => parent,
+                None if name != "." => ".",
+                None => return None,
+            };
+            positions.get(parent).copied().filter(|parent| *parent != index)
+        };
+
+        let mut placed = vec![false; names.len()];
+        let mut order = Vec::with_capacity(names.len());
+        for index in 0..names.len() {
+            // place any ancestors that sorted after this entry first, outermost first
+            let mut chain = vec![];
+            let mut next = Some(index);
+            while let Some(current) = next.filter(|current| !placed[*current]) {
+                placed[current] = true;
+                chain.push(current);
+                next = parent_of(current);
+            }
+            order.extend(chain.into_iter().rev());
+        }
+
+        let mut entries: Vec<Option<(CpioBuilderSource, String)>> =
+            std::mem::take(&mut self.entries).into_iter().map(Some).collect();
+        self.entries = order.into_iter().filter_map(|index| entries[index].take()).collect();
+    }
+
+    /// Header, content and any precomputed check of a single builder entry
+    fn source_entry<'s>(
+        &self,
+        source: &'s CpioBuilderSource,
+        internal_path: &str
+    ) -> Result<SourceEntry<'s>, Error> {
+        match source {
+            CpioBuilderSource::Path(fs_path) => fs_source_entry(fs_path, internal_path),
+            CpioBuilderSource::Data { header, content, check } => {
+                Ok((header.clone(), SourceContent::Memory(Cow::Borrowed(content)), *check))
+            },
+        }
+    }
+
+    /// Replace the inode of `header` with the next sequential number when `sequential_inodes`
+    /// or `reproducible` is set. Entries sharing an inode on the same device keep sharing their
+    /// new number, filesystem and in-memory entries never share one.
+    fn renumber(
+        &self,
+        inodes: &mut HashMap<(bool, u32, u32, u32), u32>,
+        source: &CpioBuilderSource,
+        header: &mut CpioHeader
+    ) {
+        if self.sequential_inodes || self.reproducible {
+            let from_fs = matches!(source, CpioBuilderSource::Path(_));
+            let next = inodes.len() as u32 + 1;
+            let key = (from_fs, header.c_devmajor, header.c_devminor, header.c_ino);
+            header.c_ino = *inodes.entry(key).or_insert(next);
+        }
+    }
+
+    /// Map the index of each entry in an explicit hardlink group to its group and whether it is
+    /// the last member written, which is the one carrying the data
+    fn hardlink_members(&self) -> Result<HashMap<usize, (usize, bool)>, Error> {
+        let mut members = HashMap::new();
+        let mut last_member = HashMap::new();
+
+        for (index, (_, internal_path)) in self.entries.iter().enumerate() {
+            let name = normalize_internal_path(internal_path)?;
+            if let Some(group) = self.hardlink_groups.iter().position(|group| group.contains(&name)) {
+                members.insert(index, (group, false));
+                last_member.insert(group, index);
+            }
+        }
+        for index in last_member.values() {
+            if let Some(member) = members.get_mut(index) {
+                member.1 = true;
+            }
+        }
+        Ok(members)
+    }
+
+    /// Find filesystem entries that are hardlinks of each other (the same device and inode with
+    /// a link count above one) and map each of their indices to whether it is the last one
+    /// written, which is the one carrying the data. Files whose other links are all outside the
+    /// archive are left alone.
+    fn fs_hardlinks(&self) -> HashMap<usize, bool> {
+        let mut sets: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+        for (index, (source, internal_path)) in self.entries.iter().enumerate() {
+            let CpioBuilderSource::Path(fs_path) = source else {
+                continue;
+            };
+            if !self.include_root && internal_path == "." {
+                continue;
+            }
+            // unreadable paths are reported when the entry itself is encoded
+            if let Ok(meta) = symlink_metadata(fs_path) {
+                if meta.is_file() && meta.st_nlink() > 1 {
+                    sets.entry((meta.st_dev(), meta.st_ino())).or_default().push(index);
+                }
+            }
+        }
+
+        let mut links = HashMap::new();
+        for set in sets.values().filter(|set| set.len() > 1) {
+            let last = set[set.len() - 1];
+            links.extend(set.iter().map(|index| (*index, *index == last)));
+        }
+        links
+    }
+
+    /// Build the complete archive in memory
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        self.write_to(&mut out, Compression::None)?;
+        Ok(out)
+    }
+
+    /// Stream the archive to `writer` through the chosen compressor, returning what was written.
+    /// Entries are written one at a time and file contents are copied in chunks of
+    /// `read_buffer_size`, so memory use doesn't grow with the size of the archive. Writes are
+    /// buffered, `writer` doesn't need to be.
+    pub fn write_to<W: Write>(&self, writer: W, compression: Compression) -> Result<ArchiveStats, Error> {
+        match compression {
+            Compression::None => {
+                let writer = BufWriter::with_capacity(self.read_buffer_size, writer);
+                let (stats, mut writer) = self.write_entries(writer)?;
+                writer.flush().map_err(|e|
+                    Error::FileSystemError(format!("failed to write data to archive: {e}"))
+                )?;
+                Ok(stats)
+            },
+            Compression::Gzip => {
+                let encoder = GzEncoder::new(writer, GzLevel::default());
+                let (stats, encoder) = self.write_compressed(encoder)?;
+                encoder.finish().map_err(|e|
+                    Error::CompressionError(format!("failed to finish gzip stream: {e}"))
+                )?;
+                Ok(stats)
+            },
+            Compression::Zstd => {
+                let encoder = zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL).map_err(|e|
+                    Error::CompressionError(format!("failed to create zstd encoder: {e}"))
+                )?;
+                let (stats, encoder) = self.write_compressed(encoder)?;
+                encoder.finish().map_err(|e|
+                    Error::CompressionError(format!("failed to finish zstd stream: {e}"))
+                )?;
+                Ok(stats)
+            },
+            Compression::Xz => {
+                // the kernel's xz decoder only verifies crc32 checks
+                let stream = Stream::new_easy_encoder(6, Check::Crc32).map_err(|e|
+                    Error::CompressionError(format!("failed to create xz encoder: {e}"))
+                )?;
+                let (stats, encoder) = self.write_compressed(XzEncoder::new_stream(writer, stream))?;
+                encoder.finish().map_err(|e|
+                    Error::CompressionError(format!("failed to finish xz stream: {e}"))
+                )?;
+                Ok(stats)
+            },
+            Compression::Bzip2 => {
+                let encoder = BzEncoder::new(writer, bzip2::Compression::best());
+                let (stats, encoder) = self.write_compressed(encoder)?;
+                encoder.finish().map_err(|e|
+                    Error::CompressionError(format!("failed to finish bzip2 stream: {e}"))
+                )?;
+                Ok(stats)
+            },
+            // the kernel only reads the legacy lz4 format, not the frames lz4 tools write now
+            Compression::Lz4 => {
+                let (stats, encoder) = self.write_compressed(LegacyLz4Encoder::new(writer))?;
+                encoder.finish().map_err(|e|
+                    Error::CompressionError(format!("failed to finish lz4 stream: {e}"))
+                )?;
+                Ok(stats)
+            },
+        }
+    }
+
+    /// Write the entries through a buffer in front of `encoder`, handing the encoder back so the
+    /// caller can finish its stream
+    fn write_compressed<E: Write>(&self, encoder: E) -> Result<(ArchiveStats, E), Error> {
+        let writer = BufWriter::with_capacity(self.read_buffer_size, encoder);
+        let (stats, writer) = self.write_entries(writer)?;
+        let encoder = writer.into_inner().map_err(|e|
+            Error::CompressionError(format!("failed when writing to encoder: {}", e.error()))
+        )?;
+        Ok((stats, encoder))
+    }
+
+    /// Write every entry, the trailer and the block padding to `writer`, handing it back once
+    /// done so the caller can finish it
+    fn write_entries<W: Write>(&self, writer: W) -> Result<(ArchiveStats, W), Error> {
+        let mut out = ArchiveWriter {
+            writer,
+            offset: 0,
+            format: self.format,
+            buffer_size: self.read_buffer_size,
+        };
+        let mut stats = ArchiveStats::default();
+        let mut inodes = HashMap::new();
+        let members = self.hardlink_members()?;
+        let fs_links = self.fs_hardlinks();
+        let mut group_inodes: HashMap<usize, (u32, u32, u32)> = HashMap::new();
+
+        for (index, (source, internal_path)) in self.entries.iter().enumerate() {
+            if !self.include_root && internal_path == "." {
+                continue;
+            }
+            let (mut header, mut content, mut check) = self.source_entry(source, internal_path)?;
+
+            if let Some(&(group, last)) = members.get(&index) {
+                if header.c_mode as u64 & defs::S_IFMT != defs::S_IFREG {
+                    return Err(Error::FileModeError(format!(
+                        "{:o} can't be hardlinked, only regular files can: {internal_path}",
+                        header.c_mode
+                    )))
+                }
+
+                // the whole group takes the inode of its first member
+                let (ino, devmajor, devminor) = match group_inodes.get(&group) {
+                    Some(inode) => *inode,
+                    None => {
+                        self.renumber(&mut inodes, source, &mut header);
+                        let inode = (header.c_ino, header.c_devmajor, header.c_devminor);
+                        group_inodes.insert(group, inode);
+                        inode
+                    },
+                };
+                header.c_ino = ino;
+                header.c_devmajor = devmajor;
+                header.c_devminor = devminor;
+                header.c_nlink = self.hardlink_groups[group].len() as u32;
+                if !last {
+                    content = SourceContent::Memory(Cow::Borrowed(&[]));
+                    check = None;
+                }
+            } else {
+                self.renumber(&mut inodes, source, &mut header);
+                // hardlinked files on disk are stored once, with the last link written
+                if fs_links.get(&index) == Some(&false) {
+                    content = SourceContent::Memory(Cow::Borrowed(&[]));
+                    check = None;
+                }
+            }
+
+            // the device only matters for telling inodes apart, which renumbering took care of
+            if self.reproducible {
+                header.c_mtime = 0;
+                header.c_uid = 0;
+                header.c_gid = 0;
+                header.c_devmajor = 0;
+                header.c_devminor = 0;
+            }
+
+            let name = if self.gnu_dot_prefix && internal_path != "." {
+                Cow::Owned(format!("./{internal_path}"))
+            } else {
+                Cow::Borrowed(internal_path.as_str())
+            };
+            stats.add(header.c_mode as u64, content.len());
+            out.write_entry(header, name.as_bytes(), &content, check)?;
+        }
+
+        out.write(&trailer_bytes(self.format))?;
+        if let Some(block_size) = self.block_size.filter(|size| *size != 0) {
+            out.pad(block_size as u64)?;
+        }
+
+        Ok((stats, out.writer))
+    }
+
+    /// Size the archive would have on disk with `compression`, without writing it anywhere.
+    /// The archive is streamed through the compressor like `write` does, only the compressed
+    /// output is counted rather than kept.
+    pub fn estimated_compressed_size(&self, compression: Compression) -> Result<u64, Error> {
+        let mut counter = CountingWriter::default();
+        self.write_to(&mut counter, compression)?;
+        Ok(counter.count)
+    }
+
+    /// Write the archive to `archive_path`, returning a summary of what was written
+    pub fn write(&self, archive_path: &Path, compression: Compression) -> Result<ArchiveSummary, Error> {
+        let mut stats = ArchiveStats::default();
+        write_atomic(archive_path, |out_fp| {
+            stats = self.write_to(out_fp, compression)?;
+            Ok(())
+        })?;
+
+        let archive_size = std::fs::metadata(archive_path).map_err(|e|
+            Error::FileSystemError(format!("Unable to stat {}: {e}", archive_path.display()))
+        )?.len();
+        Ok(ArchiveSummary { stats, archive_size, overridden: vec![] })
+    }
+}
+
+/// Appends entries to an uncompressed archive file. The file is parsed once, new entries go
+/// after the last entry of its first segment and `finish` writes a new trailer and block
+/// padding, so a batch of inserts costs one copy of the archive rather than a rewrite per file:
+///
+/// ```no_run
+/// use rcpio::CpioAppender;
+///
+/// let mut appender = CpioAppender::open("initramfs.cpio".as_ref()).unwrap();
+/// appender.append("build/init".as_ref(), "init").unwrap();
+/// appender.append("build/busybox".as_ref(), "bin/busybox").unwrap();
+/// appender.finish().unwrap();
+/// ```
+///
+/// Like archives written by `CpioBuilder`, the result goes to a temporary file that replaces
+/// the archive once `finish` succeeds, an appender dropped before that leaves the archive as it
+/// was. Segments concatenated after the first one, e.g. the main image following early
+/// microcode, are kept after the new trailer.
+pub struct CpioAppender {
+    out: ArchiveWriter<BufWriter<AtomicFile>>,
+
+    /// The archive being appended to
+    source: Mmap,
+
+    /// Offset in `source` of the data following the first segment, kept at the same alignment
+    rest: usize,
+
+    /// Normalized paths already in the archive, including those appended, and whether each is a
+    /// directory
+    names: HashMap<String, bool>,
+}
+
+impl CpioAppender {
+    /// Open the archive at `archive_path` for appending
+    pub fn open(archive_path: &Path) -> Result<Self, Error> {
+        let fp = File::open(archive_path).map_err(|e|
+            Error::FileSystemError(format!("Failed to open archive {}: {e}", archive_path.display()))
+        )?;
+        let source = unsafe { Mmap::map(&fp) }.map_err(|e|
+            Error::FileSystemError(format!("Failed to map archive {}: {e}", archive_path.display()))
+        )?;
+        let cpio = Cpio::load(&source)?;
+        let format = cpio.format;
+
+        let mut names = HashMap::new();
+        let mut trailer = None;
+        let mut iter = cpio.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                trailer = Some(file);
+                break;
+            }
+            let name = file.name_string()?;
+            names.insert(normalize_internal_path(&name).unwrap_or(name), file.is_dir()?);
+        }
+        let Some(trailer) = trailer else {
+            return Err(Error::InvalidArchiveError("Input archive missing trailer?".to_string()))
+        };
+        let offset = trailer.index;
+        let rest = cpio.following_segments(&trailer)?;
+
+        let mut out = ArchiveWriter {
+            writer: BufWriter::new(AtomicFile::create(archive_path)?),
+            offset: 0,
+            format,
+            buffer_size: defs::READ_BUFFER_SIZE,
+        };
+        out.write(&source[..offset])?;
+        Ok(CpioAppender { out, source, rest, names })
+    }
+
+    /// Append the file at `fs_path` as `internal_path`, regular file content is copied in chunks.
+    /// A path that is already in the archive is a `DuplicatePath` error.
+    pub fn append(&mut self, fs_path: &Path, internal_path: &str) -> Result<(), Error> {
+        let name = normalize_internal_path(internal_path).unwrap_or(internal_path.to_string());
+        if self.names.contains_key(&name) {
+            return Err(Error::DuplicatePath(name))
+        }
+
+        let start = self.out.offset;
+        let result = fs_source_entry(fs_path, internal_path).and_then(|(header, content, check)| {
+            let is_dir = header.c_mode as u64 & defs::S_IFMT == defs::S_IFDIR;
+            self.out.write_entry(header, internal_path.as_bytes(), &content, check)?;
+            Ok(is_dir)
+        });
+        match result {
+            Ok(is_dir) => {
+                self.names.insert(name, is_dir);
+                Ok(())
+            },
+            Err(e) => {
+                // drop whatever part of the entry made it out so the next one follows the last
+                // complete entry
+                self.out.writer.seek(SeekFrom::Start(start)).map_err(|e|
+                    Error::FileSystemError(format!("Failed to seek in archive: {e}"))
+                )?;
+                self.out.offset = start;
+                Err(e)
+            },
+        }
+    }
+
+    /// Append every file below `fs_dir`, including `fs_dir` itself, rebased under the archive
+    /// path `prefix` the way `CpioBuilder::insert_tree` does. Directories the archive already
+    /// has are kept as they are, any other path that is already in the archive is a
+    /// `DuplicatePath` error and nothing is appended.
+    pub fn append_tree(&mut self, fs_dir: &Path, prefix: &str) -> Result<(), Error> {
+        let mut entries = vec![];
+        for (fs_path, internal_path, is_dir) in walk_tree(fs_dir, prefix)? {
+            let internal_path = normalize_internal_path(&internal_path)?;
+            match self.names.get(&internal_path) {
+                Some(true) if is_dir => continue,
+                Some(_) => return Err(Error::DuplicatePath(internal_path)),
+                None => entries.push((fs_path, internal_path)),
+            }
+        }
+
+        for (fs_path, internal_path) in &entries {
+            self.append(fs_path, internal_path)?;
+        }
+        Ok(())
+    }
+
+    /// Write the trailer, block padding and any following segments, then replace the archive
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.out.write(&trailer_bytes(self.out.format))?;
+        self.out.pad(defs::CPIO_BLOCK_SIZE as u64)?;
+        self.out.write(&self.source[self.rest..])?;
+
+        let len = self.out.offset;
+        let file = self.out.writer.into_inner().map_err(|e|
+            Error::FileSystemError(format!("failed to write data to archive: {}", e.error()))
+        )?;
+        // a failed append may have left bytes past the end
+        file.file.set_len(len).map_err(|e|
+            Error::FileSystemError(format!("failed to write data to archive: {e}"))
+        )?;
+        file.commit()
+    }
+}
+
+/// Identity of the inode an entry belongs to
+fn hardlink_key(entry: &CpioEntry) -> Result<(u64, u64, u64), Error> {
+    Ok((entry.devmajor()?, entry.devminor()?, entry.inode()?))
+}
+
+/// The hardlink groups of an archive, built once by `Cpio::hardlinks` so resolving every member
+/// doesn't rescan the archive
+pub struct Hardlinks<'a> {
+    /// First member of each group and the first member carrying its data, by `hardlink_key`
+    groups: HashMap<(u64, u64, u64), (CpioEntry<'a>, Option<CpioEntry<'a>>)>,
+}
+
+impl<'a> Hardlinks<'a> {
+    /// Content of an entry, following hardlink placeholders. In newc/crc archives the members of
+    /// a hardlink group share an inode and only one of them (normally the last) carries the
+    /// data, the others are zero length placeholders.
+    pub fn content(&self, entry: &CpioEntry<'a>) -> Result<&'a [u8], Error> {
+        let content = entry.file_content()?;
+        if !entry.is_reg()? || entry.nlink()? <= 1 || !content.is_empty() {
+            return Ok(content)
+        }
+        match self.groups.get(&hardlink_key(entry)?) {
+            Some((_, Some(data))) => data.file_content(),
+            // every member is empty, the file itself is empty
+            _ => Ok(content),
+        }
+    }
+
+    /// The first regular file in the archive sharing `entry`'s inode when `entry` is a later
+    /// member of a hardlink group, `None` for the first member and for files that aren't
+    /// hardlinked
+    pub fn first(&self, entry: &CpioEntry<'a>) -> Result<Option<&CpioEntry<'a>>, Error> {
+        if !entry.is_reg()? || entry.nlink()? <= 1 {
+            return Ok(None)
+        }
+        Ok(self.groups.get(&hardlink_key(entry)?)
+            .map(|(first, _)| first)
+            .filter(|first| first.index < entry.index))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cpio<'a> {
+    mem: &'a [u8],
+    format: CpioFormat
+}
+
+/// An archive that owns its bytes, e.g. inflated from a compressed file, so it can be returned
+/// from the function that read it. Borrow it as a `Cpio` to work with it:
+///
+/// ```
+/// use rcpio::{CpioBuilder, CpioFormat, OwnedCpio};
+///
+/// fn build() -> OwnedCpio {
+///     let mut builder = CpioBuilder::new(CpioFormat::Newc);
+///     builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+///     OwnedCpio::new(builder.to_vec().unwrap()).unwrap()
+/// }
+///
+/// let archive = build();
+/// assert!(archive.as_cpio().contains("init").unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct OwnedCpio {
+    mem: ArchiveMemory,
+    format: CpioFormat
+}
+
+/// Bytes held by an `OwnedCpio`, an uncompressed archive file is mapped rather than copied
+#[derive(Debug, Clone)]
+enum ArchiveMemory {
+    Owned(Vec<u8>),
+    Mapped(Arc<Mmap>),
+}
+
+impl std::ops::Deref for ArchiveMemory {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ArchiveMemory::Owned(buf) => buf,
+            ArchiveMemory::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+impl OwnedCpio {
+    /// Parse an uncompressed archive, taking ownership of its bytes
+    pub fn new(mem: Vec<u8>) -> Result<Self, Error> {
+        let format = Cpio::load(&mem)?.format;
+        Ok(OwnedCpio { mem: ArchiveMemory::Owned(mem), format })
+    }
+
+    /// Read a whole archive from `reader`, decompressing it first if it is compressed, see
+    /// `Cpio::load_compressed`:
+    ///
+    /// ```
+    /// use rcpio::{Compression, CpioBuilder, CpioFormat, OwnedCpio};
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    /// let mut compressed = vec![];
+    /// builder.write_to(&mut compressed, Compression::Xz).unwrap();
+    ///
+    /// let cpio = OwnedCpio::load_compressed(&compressed[..]).unwrap();
+    /// assert_eq!(cpio.into_inner(), builder.to_vec().unwrap());
+    /// ```
+    pub fn load_compressed<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut mem = vec![];
+        let format = Cpio::load_compressed(reader, &mut mem)?.format;
+        Ok(OwnedCpio { mem: ArchiveMemory::Owned(mem), format })
+    }
+
+    pub fn as_cpio(&self) -> Cpio<'_> {
+        Cpio { mem: &self.mem, format: self.format }
+    }
+
+    /// The uncompressed archive bytes
+    pub fn into_inner(self) -> Vec<u8> {
+        match self.mem {
+            ArchiveMemory::Owned(buf) => buf,
+            ArchiveMemory::Mapped(mmap) => mmap.to_vec(),
+        }
+    }
+}
+
+impl<'a> Cpio<'a> {
+    /// Open the archive file at `path`, whatever it is compressed with. The compression is
+    /// detected from the leading magic: gzip, xz, zstd, lz4 and bzip2 compressed files are
+    /// decompressed into memory, uncompressed archives are mapped as they are.
+    ///
+    /// ```no_run
+    /// use rcpio::Cpio;
+    ///
+    /// let archive = Cpio::open("/boot/initramfs.img".as_ref()).unwrap();
+    /// let cpio = archive.as_cpio();
+    /// ```
+    pub fn open(path: &Path) -> Result<OwnedCpio, Error> {
+        if path.is_dir() {
+            return Err(Error::FileSystemError(
+                format!("{} is a directory, not a cpio archive", path.display())
+            ))
+        }
+        let fp = File::open(path).map_err(|e|
+            Error::FileSystemError(format!("Failed to open archive {}: {e}", path.display()))
+        )?;
+        let mmap = unsafe { Mmap::map(&fp) }.map_err(|e|
+            Error::FileSystemError(format!("Failed to map archive {}: {e}", path.display()))
+        )?;
+
+        if Compression::detect(&mmap) == Compression::None {
+            let format = Cpio::load(&mmap)?.format;
+            return Ok(OwnedCpio { mem: ArchiveMemory::Mapped(Arc::new(mmap)), format })
+        }
+        OwnedCpio::load_compressed(&mmap[..])
+    }
+
+    /// Open the archive file at `path` for sequential reading with bounded memory, compressed
+    /// archives are decompressed on the fly. Listing entries only decompresses
+    /// content to skip over it, nothing is buffered beyond the current header.
+    pub fn open_compressed(path: &Path) -> Result<CpioStream<Box<dyn Read>>, Error> {
+        stream::open_compressed(path)
+    }
+
+    /// Parse an uncompressed archive held in `mem`. The archive borrows the buffer rather than
+    /// owning it, so a caller that wants control over decompression can decompress into its own
+    /// buffer and load from that:
+    ///
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+    /// use rcpio::{Cpio, CpioBuilder, CpioFormat};
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    /// let mut encoder = GzEncoder::new(vec![], Compression::default());
+    /// encoder.write_all(&builder.to_vec().unwrap()).unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    ///
+    /// let mut decompressed = vec![];
+    /// GzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+    ///
+    /// let cpio = Cpio::load(&decompressed).unwrap();
+    /// assert!(cpio.contains("init").unwrap());
+    /// ```
+    pub fn load(mem: &'a [u8]) -> Result<Self, Error> {
+        let format = identify_format(mem)?;
+
+        if mem.len() < format.header_len() {
+            return Err(Error::InvalidArchiveError(format!(
+                "archive too small / truncated header: {} bytes, a header is {} bytes",
+                mem.len(), format.header_len()
+            )))
+        }
+
+        Ok(Cpio { mem, format })
+    }
+
+    /// Read a whole archive from `reader` into `buf` and parse it, decompressing it first if it
+    /// is compressed in one of the formats `Compression::detect` recognizes (concatenated
+    /// streams are decompressed one after another). `buf` holds
+    /// the uncompressed archive and has to outlive the returned `Cpio`:
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use rcpio::Cpio;
+    ///
+    /// let mut buf = vec![];
+    /// let cpio = Cpio::load_compressed(File::open("initramfs.cpio.gz").unwrap(), &mut buf).unwrap();
+    /// ```
+    pub fn load_compressed<R: Read>(reader: R, buf: &'a mut Vec<u8>) -> Result<Self, Error> {
+        let mut reader = BufReader::new(reader);
+        let compression = Compression::detect(reader.fill_buf().map_err(|e|
+            Error::FileSystemError(format!("Failed to read archive: {e}"))
+        )?);
+
+        buf.clear();
+        compression.decoder(reader)?.read_to_end(buf).map_err(|e| match compression {
+            Compression::None => Error::FileSystemError(format!("Failed to read archive: {e}")),
+            _ => Error::DecompressionError(e.to_string()),
+        })?;
+        Cpio::load(buf)
+    }
+
+    /// Iterate the entries of the archive. Iteration never mutates the `Cpio`, each call returns
+    /// an independent iterator starting at the first entry, even if a previous one stopped early
+    /// or failed partway through.
+    pub fn iter_files(&self) -> CpioEntryIter<'a> {
+        CpioEntryIter {
+            index: 0,
+            archive_mem: self.mem,
+            format: self.format,
+            trailer_seen: false,
+            strict: false,
+            all_segments: false,
+        }
+    }
+
+    /// Iterate entries, additionally checking that each entry's content ends where a valid header
+    /// begins so an inflated `c_filesize` cannot silently swallow the following entry
+    pub fn iter_files_strict(&self) -> CpioEntryIter<'a> {
+        CpioEntryIter { strict: true, ..self.iter_files() }
+    }
+
+    /// Iterate the name (without its null terminator) and content of each regular file, skipping
+    /// every other entry type and the trailer. Both are borrowed from the archive. Members of a
+    /// hardlink group without data yield empty content, the member carrying the data is yielded
+    /// as well.
+    pub fn iter_regular_files(
+        &self
+    ) -> impl FallibleIterator<Item = (&'a [u8], &'a [u8]), Error = Error> + 'a {
+        self.iter_files().filter_map(|entry| {
+            if !entry.is_reg()? {
+                return Ok(None)
+            }
+            let name = entry.name()?;
+            let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+            Ok(Some((&name[..end], entry.file_content()?)))
+        })
+    }
+
+    /// Copy every regular file into a map from its name, as stored in the archive, to its
+    /// content. Hardlinked files each get the shared content. Everything is copied out of the
+    /// archive, so the map needs as much memory as the archive's file contents; use
+    /// `iter_regular_files` to borrow instead.
+    pub fn to_map(&self) -> Result<BTreeMap<String, Vec<u8>>, Error> {
+        self.collect_map(false)
+    }
+
+    /// Like `to_map`, but symlinks are included as well, mapped to their target
+    pub fn to_map_with_symlinks(&self) -> Result<BTreeMap<String, Vec<u8>>, Error> {
+        self.collect_map(true)
+    }
+
+    fn collect_map(&self, include_symlinks: bool) -> Result<BTreeMap<String, Vec<u8>>, Error> {
+        let mut map = BTreeMap::new();
+        let hardlinks = self.hardlinks()?;
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_reg()? || (include_symlinks && file.is_link()?) {
+                map.insert(file.name_string()?, hardlinks.content(&file)?.to_vec());
+            }
+        }
+        Ok(map)
+    }
+
+    /// Offset of the first non-zero byte between the end of the trailer and the end of its
+    /// 512-byte block. The padding should be zeros but some tools leave garbage behind, which
+    /// doesn't affect reading so it is worth a warning rather than an error. `None` if the
+    /// padding is clean or there is no trailer.
+    pub fn check_trailer_padding(&self) -> Result<Option<usize>, Error> {
+        let Some(trailer) = self.iter_files().find(|entry| entry.is_trailer())? else {
+            return Ok(None)
+        };
+
+        let start = trailer.next()?.min(self.mem.len());
+        let end = start.next_multiple_of(defs::CPIO_BLOCK_SIZE).min(self.mem.len());
+        Ok(self.mem[start..end].iter().position(|b| *b != 0).map(|offset| start + offset))
+    }
+
+    /// Iterate the entries of every segment of a concatenated archive (e.g. an early microcode
+    /// archive followed by the main initramfs). Each segment's trailer is yielded, then the zero
+    /// padding after it is skipped and iteration resumes if valid magic follows, stopping only at
+    /// the end of the buffer or at data that is not a cpio header.
+    pub fn iter_all_segments(&self) -> CpioEntryIter<'a> {
+        CpioEntryIter { all_segments: true, ..self.iter_files() }
+    }
+
+    /// Metadata of the first entry, `None` if the archive holds only a trailer. Only the first
+    /// header is parsed, which makes this cheap for identifying an image (e.g. checking for an
+    /// early microcode segment under `kernel/x86/microcode`).
+    pub fn peek_first(&self) -> Result<Option<CpioEntryInfo>, Error> {
+        match self.iter_files().next()? {
+            Some(entry) if !entry.is_trailer()? => Ok(Some(entry.info()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Find the first entry named `path`. Names are compared as `normalize_internal_path` sees
+    /// them, so `foo/bar`, `./foo/bar` and `foo//bar/` match the same entry, and a `path` it
+    /// rejects is an `InvalidPathError`.
+    pub fn find(&self, path: &str) -> Result<Option<CpioEntry<'a>>, Error> {
+        let path = normalize_internal_path(path)?;
+        let mut iter = self.iter_files();
+        while let Some(entry) = iter.next()? {
+            // iteration ends at the trailer, it only needs excluding when it is what matched
+            if entry.name_matches(&path)? && !entry.is_trailer()? {
+                return Ok(Some(entry))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Content of the regular file named `path`, borrowed from the archive. Hardlink
+    /// placeholders resolve to the data of their group.
+    pub fn read_file_borrowed(&self, path: &str) -> Result<&'a [u8], Error> {
+        let entry = self.find(path)?.ok_or_else(|| Error::NoSuchFile(path.to_string()))?;
+        if !entry.is_reg()? {
+            return Err(Error::FileModeError(
+                format!("{:o} is not a regular file: {path}", entry.mode()?)
+            ))
+        }
+        self.hardlinks()?.content(&entry)
+    }
+
+    /// Owned copy of the content of the regular file named `path`
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Error> {
+        Ok(self.read_file_borrowed(path)?.to_vec())
+    }
+
+    /// Write the body of the entry named `internal_path` to `writer`: a regular file's content,
+    /// resolved through its hardlink group, or a symlink's target path. Other entry types have
+    /// no body to write and are a `FileModeError`.
+    ///
+    /// ```
+    /// use rcpio::{Cpio, CpioBuilder, CpioFormat, Error};
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// builder.insert_dir("etc", 0o755).unwrap();
+    /// builder.insert_data("etc/hostname", b"rcpio\n", 0o644).unwrap();
+    /// builder.insert_symlink("hostname", "etc/hostname").unwrap();
+    /// let bytes = builder.to_vec().unwrap();
+    /// let cpio = Cpio::load(&bytes).unwrap();
+    ///
+    /// let mut out = vec![];
+    /// cpio.extract_entry_to("etc/hostname", &mut out).unwrap();
+    /// cpio.extract_entry_to("hostname", &mut out).unwrap();
+    /// assert_eq!(out, b"rcpio\netc/hostname");
+    ///
+    /// assert!(matches!(cpio.extract_entry_to("etc", &mut out), Err(Error::FileModeError(_))));
+    /// assert!(matches!(cpio.extract_entry_to("missing", &mut out), Err(Error::NoSuchFile(_))));
+    /// ```
+    pub fn extract_entry_to(&self, internal_path: &str, writer: &mut impl Write) -> Result<(), Error> {
+        let entry = self.find(internal_path)?.ok_or_else(|| Error::NoSuchFile(internal_path.to_string()))?;
+        let body = if entry.is_reg()? {
+            self.hardlinks()?.content(&entry)?
+        } else if entry.is_link()? {
+            entry.file_content()?
+        } else {
+            return Err(Error::FileModeError(
+                format!("{:o} has no content to extract: {internal_path}", entry.mode()?)
+            ))
+        };
+
+        writer.write_all(body).map_err(|e|
+            Error::FileSystemError(format!("Unable to write {internal_path}: {e}"))
+        )
+    }
+
+    /// Check whether the archive has an entry named `path`
+    pub fn contains(&self, path: &str) -> Result<bool, Error> {
+        Ok(self.find(path)?.is_some())
+    }
+
+    /// The entry immediately following `entry`, `None` if `entry` is the trailer or the end of
+    /// the archive has been reached
+    pub fn entry_after(&self, entry: &CpioEntry<'a>) -> Result<Option<CpioEntry<'a>>, Error> {
+        if entry.is_trailer()? {
+            return Ok(None)
+        }
+
+        let index = entry.next()?;
+        if index >= self.mem.len() {
+            return Ok(None)
+        }
+
+        Ok(Some(CpioEntry::new(index, entry.format, self.mem)?))
+    }
+
+    pub fn extract_one(&self, output_path: &Path, entry: &CpioEntry<'a>) -> Result<(), Error> {
+        self.extract_one_with_options(output_path, entry, &ExtractOptions::default())?;
+        Ok(())
+    }
+
+    /// Extract a single entry below `output_path`, returning the path that was written or `None`
+    /// if the entry was left out
+    pub fn extract_one_with_options(
+        &self,
+        output_path: &Path,
+        entry: &CpioEntry<'a>,
+        options: &ExtractOptions
+    ) -> Result<Option<PathBuf>, Error> {
+        Ok(self.extract_entry(output_path, entry, options, &self.hardlinks()?, false)?.path)
+    }
+
+    /// Extract a single entry, resolving hardlink placeholders through `hardlinks`. With
+    /// `defer_directory_modes` directories stay writable by their owner so later entries can be
+    /// created below them, the caller sets their real mode once everything has been extracted
+    fn extract_entry(
+        &self,
+        output_path: &Path,
+        entry: &CpioEntry<'a>,
+        options: &ExtractOptions,
+        hardlinks: &Hardlinks<'a>,
+        defer_directory_modes: bool
+    ) -> Result<EntryOutcome, Error> {
+        let path = String::from_utf8(entry.name()?.to_vec()).map_err(|e|
+            Error::StringEncodingError(e.to_string())
+        )?;
+        let trimmed_path = path.trim_end_matches('\0');
+
+        let normalized = normalize_internal_path(trimmed_path)?;
+        let Some(normalized) = options.output_name(&normalized) else {
+            return Ok(EntryOutcome::default())
+        };
+
+        let joined_path = output_path.join(&normalized);
+        if !joined_path.starts_with(output_path) {
+            return Err(Error::FileSystemError(
+                format!("Entry path escapes the output directory: {trimmed_path}")
+            ))
+        }
+
+        check_name_length(trimmed_path, &joined_path)?;
+        check_symlinked_parents(output_path, &joined_path)?;
+
+        if let Some(parent) = joined_path.parent() {
+            create_dir_all(parent).map_err(|e|
+                Error::FileSystemError(
+                    format!("Unable to create parent directory {}: {e}", parent.display())
+                )
+            )?;
+        }
+
+        // never write through a symlink already sitting at the target path, it may point
+        // outside the output directory
+        if entry.is_dir()? || entry.is_reg()? {
+            remove_symlink(&joined_path)?;
+        }
+        if options.overwrite && !entry.is_dir()? {
+            remove_non_directory(&joined_path)?;
+        }
+
+        let mut outcome = EntryOutcome::default();
+        if entry.is_dir()? {
+            if !joined_path.is_dir() {
+                create_dir(&joined_path).map_err(|e|
+                    Error::FileSystemError(
+                        format!("Unable to create directory {}: {e}", joined_path.display())
+                    )
+                )?;
+            }
+            outcome.ownership_error = restore_ownership(&joined_path, entry, options)?;
+            let mut mode = entry.permissions()?;
+            if defer_directory_modes {
+                mode |= 0o700;
+            }
+            set_permissions(&joined_path, Permissions::from_mode(mode)).map_err(|e|
+                Error::FileSystemError(
+                    format!("Unable to set permissions on {}: {e}", joined_path.display())
+                )
+            )?;
+            restore_mtime(&joined_path, entry, options)?;
+        } else if entry.is_reg()? {
+            if let Some(original) = extracted_hardlink(output_path, entry, options, hardlinks)? {
+                hard_link(&original, &joined_path).map_err(|e|
+                    Error::FileSystemError(format!(
+                        "Unable to link {} to {}: {e}", joined_path.display(), original.display()
+                    ))
+                )?;
+                return Ok(EntryOutcome::written(joined_path))
+            }
+
+            let mut fp = OpenOptions::new().write(true).create_new(true).open(&joined_path).map_err(|e|
+                Error::FileSystemError(
+                    format!("Unable to create file {}: {e}", joined_path.display())
+                )
+            )?;
+            if !options.metadata_only {
+                fp.write_all(hardlinks.content(entry)?).map_err(|e|
+                    Error::FileSystemError(
+                        format!("Unable to write file {}: {e}", joined_path.display())
+                    )
+                )?;
+            }
+            // chown clears setuid/setgid, so the ownership has to be set before the mode
+            outcome.ownership_error = restore_ownership(&joined_path, entry, options)?;
+            fp.set_permissions(Permissions::from_mode(entry.permissions()?)).map_err(|e|
+                Error::FileSystemError(
+                    format!("Unable to set permissions on {}: {e}", joined_path.display())
+                )
+            )?;
+            restore_mtime(&joined_path, entry, options)?;
+        } else if entry.is_link()? {
+            let target = from_utf8(entry.file_content()?).map_err(|e|
+                Error::StringEncodingError(e.to_string())
+            )?;
+            let escaping = options.symlink_policy != SymlinkPolicy::AllowAny
+                && link_target_escapes(&normalized, target);
+            if escaping && options.symlink_policy == SymlinkPolicy::RejectEscaping {
+                return Err(Error::FileSystemError(
+                    format!("symlink {normalized} -> {target} escapes the output directory")
+                ))
+            }
+            symlink(target, &joined_path).map_err(|e|
+                Error::FileSystemError(
+                    format!("Unable to create symlink {}: {e}", joined_path.display())
+                )
+            )?;
+            outcome.ownership_error = restore_ownership(&joined_path, entry, options)?;
+            restore_mtime(&joined_path, entry, options)?;
+            if escaping {
+                outcome.escaping_target = Some(target.to_string());
+            }
+        } else if entry.is_chr()? || entry.is_blk()? || entry.is_fifo()? || entry.is_sock()? {
+            if (entry.is_chr()? || entry.is_blk()?) && options.device_policy != DevicePolicy::Create {
+                return Ok(EntryOutcome::default())
+            }
+            make_node(&joined_path, entry)?;
+            outcome.ownership_error = restore_ownership(&joined_path, entry, options)?;
+            set_permissions(&joined_path, Permissions::from_mode(entry.permissions()?)).map_err(|e|
+                Error::FileSystemError(
+                    format!("Unable to set permissions on {}: {e}", joined_path.display())
+                )
+            )?;
+            restore_mtime(&joined_path, entry, options)?;
+        } else {
+            let message = format!("{:o} is not supported for extraction: {trimmed_path}", entry.mode()?);
+            match options.unknown_type_policy {
+                UnknownTypePolicy::Error => return Err(Error::FileModeError(message)),
+                UnknownTypePolicy::Skip => return Ok(EntryOutcome::default()),
+                UnknownTypePolicy::SkipWithWarning => {
+                    return Ok(EntryOutcome { skipped: true, ..Default::default() })
+                },
+            }
+        }
+
+        outcome.path = Some(joined_path);
+        Ok(outcome)
+    }
+
+    /// Append the file at `fs_path` as `internal_path` to the archive file at `archive_path`,
+    /// which has to hold this archive. A path that is already in the archive is a
+    /// `DuplicatePath` error. See `CpioAppender` to append several files at once.
+    pub fn push(&self, archive_path: &Path, fs_path: &Path, internal_path: &str) -> Result<(), Error> {
+        let mut appender = self.appender(archive_path)?;
+        appender.append(fs_path, internal_path)?;
+        appender.finish()
+    }
+
+    /// Append every file below `fs_dir`, including `fs_dir` itself, to the archive file at
+    /// `archive_path`, which has to hold this archive. The tree is rebased under the archive
+    /// path `prefix` the way `CpioBuilder::insert_tree` does. Directories the archive already
+    /// has are kept as they are, any other path that is already in the archive is a
+    /// `DuplicatePath` error and nothing is written.
+    pub fn push_tree(&self, archive_path: &Path, fs_dir: &Path, prefix: &str) -> Result<(), Error> {
+        let mut appender = self.appender(archive_path)?;
+        appender.append_tree(fs_dir, prefix)?;
+        appender.finish()
+    }
+
+    /// Offset of the data following the first segment, whose trailer is `trailer`: the segments
+    /// concatenated after it, without the zero padding in front of them beyond what keeps them
+    /// at the same 4 byte alignment. The end of the archive if only padding follows.
+    fn following_segments(&self, trailer: &CpioEntry) -> Result<usize, Error> {
+        let end = trailer.next()?.min(self.mem.len());
+        match self.mem[end..].iter().position(|b| *b != 0) {
+            Some(zeros) => Ok(end + zeros - zeros % 4),
+            None => Ok(self.mem.len()),
+        }
+    }
+
+    /// Offset of the trailer that ends the first segment of the archive
+    fn trailer_offset(&self) -> Result<usize, Error> {
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                return Ok(file.index)
+            }
+        }
+        Err(Error::InvalidArchiveError("Input archive missing trailer?".to_string()))
+    }
+
+    /// Open `archive_path` for appending, it has to hold this archive
+    fn appender(&self, archive_path: &Path) -> Result<CpioAppender, Error> {
+        let appender = CpioAppender::open(archive_path)?;
+        if appender.out.offset != self.trailer_offset()? as u64 {
+            return Err(Error::InvalidArchiveError(
+                format!("{} doesn't hold the archive being pushed to", archive_path.display())
+            ))
+        }
+        Ok(appender)
+    }
+
+    /// The segments of a concatenated archive, each a `Cpio` starting at its first header. Data
+    /// after a trailer that isn't another uncompressed cpio archive (e.g. a compressed initramfs
+    /// behind an early microcode archive) is an error rather than being left out.
+    fn segments(&self) -> Result<Vec<Cpio<'a>>, Error> {
+        let mut segments = vec![];
+        let mut segment = Cpio { mem: self.mem, format: self.format };
+        loop {
+            let trailer = match segment.iter_files().last()? {
+                Some(trailer) if trailer.is_trailer()? => trailer,
+                _ => return Err(Error::InvalidArchiveError("Input archive missing trailer?".to_string())),
+            };
+            let rest = &segment.mem[segment.following_segments(&trailer)?..];
+            segments.push(segment);
+            if rest.is_empty() {
+                return Ok(segments)
+            }
+
+            let offset = self.mem.len() - rest.len();
+            segment = Cpio::load(rest).map_err(|e| Error::InvalidArchiveError(
+                format!("data following the trailer at offset {offset:#x} is not a segment: {e}")
+            ))?;
+        }
+    }
+
+    /// Re-encode every entry in the `to` format and write the result to `output`, only the magic
+    /// and check fields change, alignment is recomputed for the new records. Each segment of a
+    /// concatenated archive is converted into a segment of its own, with its own trailer.
+    pub fn convert_format(&self, output: &Path, to: CpioFormat) -> Result<(), Error> {
+        let mut out = vec![];
+
+        for segment in self.segments()? {
+            let mut iter = segment.iter_files();
+            while let Some(file) = iter.next()? {
+                if file.is_trailer()? {
+                    break;
+                }
+
+                let name = file.name()?;
+                let name = name.strip_suffix(b"\0").unwrap_or(name);
+                let entry = file.header_fields()?;
+                out.append(&mut encode_entry(entry, name, file.file_content()?, out.len(), to, None));
+            }
+
+            out.append(&mut trailer_bytes(to));
+            pad_to_block(&mut out, Some(defs::CPIO_BLOCK_SIZE));
+        }
+
+        write_archive_file(output, &out)
+    }
+
+    /// Write the entries of this archive followed by those of `other` to `output` as a single
+    /// archive in this archive's format. A path present in both takes its place from this
+    /// archive and its entry from `other`, unless `duplicates` rejects it; directories present
+    /// in both are never a conflict. Every segment of concatenated inputs is merged, in order,
+    /// with later segments replacing paths of earlier ones the way the kernel unpacks them.
+    /// Inodes are renumbered sequentially so files from different archives or segments can't be
+    /// mistaken for hardlinks of each other, and the data of a hardlink group moves to its last
+    /// remaining member.
+    pub fn merge(&self, other: &Cpio, output: &Path, duplicates: DuplicatePolicy) -> Result<(), Error> {
+        let mut segments = vec![];
+        for (archive, cpio) in [self, other].into_iter().enumerate() {
+            segments.extend(cpio.segments()?.into_iter().map(|segment| (archive, segment)));
+        }
+
+        let mut kept: Vec<(usize, CpioEntry)> = vec![];
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        for (index, (archive, segment)) in segments.iter().enumerate() {
+            let mut iter = segment.iter_files();
+            while let Some(file) = iter.next()? {
+                if file.is_trailer()? {
+                    break;
+                }
+
+                let name = file.name_string()?;
+                let name = normalize_internal_path(&name).unwrap_or(name);
+                if let Some(&position) = positions.get(&name) {
+                    let both_dirs = kept[position].1.is_dir()? && file.is_dir()?;
+                    let other_archive = segments[kept[position].0].0 != *archive;
+                    if duplicates == DuplicatePolicy::Reject && other_archive && !both_dirs {
+                        return Err(Error::InvalidArchiveError(
+                            format!("{name} is in both archives")
+                        ))
+                    }
+                    // replace in place so parents still come before their children
+                    kept[position] = (index, file);
+                } else {
+                    positions.insert(name, kept.len());
+                    kept.push((index, file));
+                }
+            }
+        }
+
+        // the members of each hardlink group that survived, the last one carries the data
+        let hardlink_key = |segment: usize, file: &CpioEntry| -> Result<_, Error> {
+            Ok((segment, file.devmajor()?, file.devminor()?, file.inode()?))
+        };
+        let mut groups: HashMap<(usize, u64, u64, u64), Vec<usize>> = HashMap::new();
+        for (position, (segment, file)) in kept.iter().enumerate() {
+            if file.is_reg()? && file.nlink()? > 1 {
+                groups.entry(hardlink_key(*segment, file)?).or_default().push(position);
+            }
+        }
+
+        let hardlinks = segments.iter()
+            .map(|(_, segment)| segment.hardlinks())
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut out = vec![];
+        let mut inodes = HashMap::new();
+        for (position, (segment, file)) in kept.iter().enumerate() {
+            let key = hardlink_key(*segment, file)?;
+            let next = inodes.len() as u32 + 1;
+
+            let mut header = file.header_fields()?;
+            header.c_ino = *inodes.entry(key).or_insert(next);
+            let content = match groups.get(&key) {
+                Some(members) => {
+                    header.c_nlink = members.len() as u32;
+                    if members.last() == Some(&position) {
+                        hardlinks[*segment].content(file)?
+                    } else {
+                        &[]
+                    }
+                },
+                None => file.file_content()?,
+            };
+
+            let name = file.name()?;
+            let name = name.strip_suffix(b"\0").unwrap_or(name);
+            out.append(&mut encode_entry(header, name, content, out.len(), self.format, None));
+        }
+
+        out.append(&mut trailer_bytes(self.format));
+        pad_to_block(&mut out, Some(defs::CPIO_BLOCK_SIZE));
+
+        write_archive_file(output, &out)
+    }
+
+    /// Write every entry except those named `internal_path` to `output`. Only the entry itself
+    /// is dropped, removing a directory leaves its contents in place. When a member of a
+    /// hardlink group is removed the remaining members keep the group's data, it moves to the
+    /// last of them, and their link count is lowered. Entries are removed from the first
+    /// segment, segments concatenated after it are copied unchanged.
+    pub fn remove(&self, output: &Path, internal_path: &str) -> Result<(), Error> {
+        let target = normalize_internal_path(internal_path)?;
+
+        let mut kept = vec![];
+        let mut removed = false;
+        let mut trailer = None;
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                trailer = Some(file);
+                break;
+            }
+
+            let name = file.name_string()?;
+            if normalize_internal_path(&name).is_ok_and(|name| name == target) {
+                removed = true;
+            } else {
+                kept.push(file);
+            }
+        }
+        if !removed {
+            return Err(Error::NoSuchFile(internal_path.to_string()))
+        }
+
+        // the members of each hardlink group that remain, the last one carries the data
+        let mut groups: HashMap<(u64, u64, u64), Vec<usize>> = HashMap::new();
+        for (position, file) in kept.iter().enumerate() {
+            if file.is_reg()? && file.nlink()? > 1 {
+                groups.entry(hardlink_key(file)?).or_default().push(position);
+            }
+        }
+
+        let hardlinks = self.hardlinks()?;
+        let mut out = vec![];
+        for (position, file) in kept.iter().enumerate() {
+            let mut header = file.header_fields()?;
+            let group = if file.is_reg()? { groups.get(&hardlink_key(file)?) } else { None };
+            let content = match group {
+                Some(members) => {
+                    header.c_nlink = members.len() as u32;
+                    if members.last() == Some(&position) {
+                        hardlinks.content(file)?
+                    } else {
+                        &[]
+                    }
+                },
+                None => file.file_content()?,
+            };
+
+            let name = file.name()?;
+            let name = name.strip_suffix(b"\0").unwrap_or(name);
+            out.append(&mut encode_entry(header, name, content, out.len(), self.format, None));
+        }
+
+        out.append(&mut trailer_bytes(self.format));
+        pad_to_block(&mut out, Some(defs::CPIO_BLOCK_SIZE));
+        if let Some(trailer) = trailer {
+            out.extend_from_slice(&self.mem[self.following_segments(&trailer)?..]);
+        }
+
+        write_archive_file(output, &out)
+    }
+
+    pub fn unarchive(&self, output_path: &Path) -> Result<(), Error> {
+        self.unarchive_with_options(output_path, &ExtractOptions::default())?;
+        Ok(())
+    }
+
+    pub fn unarchive_with_options(
+        &self,
+        output_path: &Path,
+        options: &ExtractOptions
+    ) -> Result<ExtractReport, Error> {
+        let output_path = self.prepare_output(output_path, options)?;
+
+        let mut report = ExtractReport::default();
+
+        let hardlinks = self.hardlinks()?;
+        // writing into a directory updates its mtime and a read-only directory can't receive its
+        // children, so directory modes and mtimes are set once everything has been extracted
+        let mut directories = vec![];
+
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                continue;
+            }
+
+            let extracted = self.extract_entry(&output_path, &file, options, &hardlinks, true)?;
+            self.record_extracted(
+                &output_path, &file, extracted, options, &mut report, &mut directories
+            )?;
+        }
+
+        restore_directories(directories, options)?;
+        Ok(report)
+    }
+
+    /// Like `unarchive_with_options`, but regular files and symlinks are written by `threads`
+    /// worker threads. Directories, device nodes and every other entry type are created first,
+    /// serially and in archive order, then all regular files, then the later members of
+    /// hardlink groups (linked to the files written before them), then all symlinks. Symlinks are
+    /// created last so no file is written through one, which also means entries stored below a
+    /// symlinked directory aren't supported, extract those archives serially. With duplicate
+    /// paths which copy fails is not deterministic.
+    pub fn unarchive_parallel(
+        &self,
+        output_path: &Path,
+        options: &ExtractOptions,
+        threads: usize
+    ) -> Result<ExtractReport, Error> {
+        let output_path = self.prepare_output(output_path, options)?;
+
+        // what extracting each entry did, keyed by its position in the archive so the report and
+        // directories are recorded in archive order like a serial extraction
+        let mut outcomes = vec![];
+        let mut files = vec![];
+        let mut later_members = vec![];
+        let mut symlinks = vec![];
+
+        let hardlinks = self.hardlinks()?;
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                continue;
+            }
+
+            if hardlinks.first(&file)?.is_some() {
+                later_members.push(file);
+            } else if file.is_reg()? {
+                files.push(file);
+            } else if file.is_link()? {
+                symlinks.push(file);
+            } else {
+                let extracted = self.extract_entry(&output_path, &file, options, &hardlinks, true)?;
+                outcomes.push((file, extracted));
+            }
+        }
+
+        // later hardlink members go once the files they link to are written
+        for batch in [files, later_members, symlinks] {
+            let results = self.extract_concurrently(&output_path, &batch, options, &hardlinks, threads)?;
+            outcomes.extend(batch.into_iter().zip(results));
+        }
+        outcomes.sort_by_key(|(file, _)| file.index);
+
+        let mut report = ExtractReport::default();
+        let mut directories = vec![];
+        for (file, extracted) in outcomes {
+            self.record_extracted(
+                &output_path, &file, extracted, options, &mut report, &mut directories
+            )?;
+        }
+
+        restore_directories(directories, options)?;
+        Ok(report)
+    }
+
+    /// Extract `entries` across `threads` workers, returning what extracting each entry did in the
+    /// order of `entries`. Workers pull the next entry from a shared counter so a few large files
+    /// don't hold up the rest.
+    fn extract_concurrently(
+        &self,
+        output_path: &Path,
+        entries: &[CpioEntry<'a>],
+        options: &ExtractOptions,
+        hardlinks: &Hardlinks<'a>,
+        threads: usize
+    ) -> Result<Vec<EntryOutcome>, Error> {
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<EntryOutcome> = entries.iter().map(|_| EntryOutcome::default()).collect();
+
+        let worker_results = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.clamp(1, entries.len().max(1))).map(|_| {
+                scope.spawn(|| -> Result<Vec<(usize, EntryOutcome)>, Error> {
+                    let mut extracted = vec![];
+                    loop {
+                        let index = next.fetch_add(1, AtomicOrdering::Relaxed);
+                        let Some(entry) = entries.get(index) else {
+                            return Ok(extracted)
+                        };
+                        match self.extract_entry(output_path, entry, options, hardlinks, true) {
+                            Ok(outcome) => extracted.push((index, outcome)),
+                            Err(e) => {
+                                // stop the other workers at their next entry
+                                next.store(entries.len(), AtomicOrdering::Relaxed);
+                                return Err(e)
+                            },
+                        }
+                    }
+                })
+            }).collect();
+
+            workers.into_iter().map(|worker|
+                worker.join().unwrap_or_else(|_|
+                    Err(Error::FileSystemError(String::from("Extraction worker panicked")))
+                )
+            ).collect::<Vec<_>>()
+        });
+
+        for worker in worker_results {
+            for (index, outcome) in worker? {
+                results[index] = outcome;
+            }
+        }
+        Ok(results)
+    }
+
+    /// Create the output directory if needed, returning its canonical path
+    fn prepare_output(&self, output_path: &Path, options: &ExtractOptions) -> Result<PathBuf, Error> {
+        // check before creating anything, so a failed check leaves no empty directory behind
+        if options.check_space {
+            let existing = output_path.ancestors()
+                .find(|ancestor| ancestor.exists())
+                .filter(|ancestor| !ancestor.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            self.check_space(existing)?;
+        }
+
+        if !output_path.exists() {
+            create_dir(output_path).map_err(|_|
+                Error::FileSystemError(
+                    format!("Unable to create output directory: {}", output_path.display())
+                )
+            )?
+        }
+        output_path.canonicalize().map_err(|e| {
+            Error::FileSystemError(e.to_string())
+        })
+    }
+
+    /// Account for the outcome of extracting `file` in the report
+    fn record_extracted(
+        &self,
+        output_path: &Path,
+        file: &CpioEntry<'a>,
+        extracted: EntryOutcome,
+        options: &ExtractOptions,
+        report: &mut ExtractReport,
+        directories: &mut Vec<(PathBuf, u32, u64)>
+    ) -> Result<(), Error> {
+        if extracted.skipped {
+            report.skipped.push(file.name_string()?);
+        }
+        if let Some(target) = extracted.escaping_target {
+            report.escaping_symlinks.push((file.name_string()?, target));
+        }
+        if let Some(message) = extracted.ownership_error {
+            report.ownership_failures.push(message);
+        }
+        if extracted.path.is_none()
+            && options.device_policy == DevicePolicy::Record
+            && (file.is_chr()? || file.is_blk()?)
+        {
+            let name = normalize_internal_path(&file.name_string()?)?;
+            if let Some(path) = options.output_name(&name) {
+                report.devices.push(DeviceRecord {
+                    path,
+                    mode: file.mode()?,
+                    uid: file.uid()?,
+                    gid: file.gid()?,
+                    major: file.rdevmajor()?,
+                    minor: file.rdevminor()?,
+                });
+            }
+        }
+
+        if let Some(path) = extracted.path {
+            if file.is_dir()? {
+                directories.push((path.clone(), file.permissions()?, file.mtime()?));
+            }
+            let relative = match path.strip_prefix(output_path) {
+                Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => path,
+            };
+            report.entries.push(ExtractedEntry { path: relative, mode: file.mode()? });
+        }
+        Ok(())
+    }
+
+    /// Names of the entries owned by `uid` and/or `gid`, a `None` filter matches any owner
+    pub fn entries_by_owner(&self, uid: Option<u32>, gid: Option<u32>) -> Result<Vec<String>, Error> {
+        let mut names = vec![];
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+
+            if let Some(uid) = uid {
+                if file.uid()? != uid as u64 {
+                    continue;
+                }
+            }
+            if let Some(gid) = gid {
+                if file.gid()? != gid as u64 {
+                    continue;
+                }
+            }
+
+            names.push(file.name_string()?);
+        }
+        Ok(names)
+    }
+
+    /// Names of the entries with an mtime strictly after `epoch`
+    pub fn entries_modified_after(&self, epoch: u32) -> Result<Vec<String>, Error> {
+        self.entries_by_mtime(|mtime| mtime > epoch as u64)
+    }
+
+    /// Names of the entries with an mtime in `start..end`, the start is inclusive and the end
+    /// exclusive so adjacent ranges never report an entry twice
+    pub fn entries_modified_between(&self, start: u32, end: u32) -> Result<Vec<String>, Error> {
+        self.entries_by_mtime(|mtime| (start as u64..end as u64).contains(&mtime))
+    }
+
+    fn entries_by_mtime(&self, matches: impl Fn(u64) -> bool) -> Result<Vec<String>, Error> {
+        let mut names = vec![];
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            if matches(file.mtime()?) {
+                names.push(file.name_string()?);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Compare two archives entry by entry, ignoring the metadata selected by `ignore`. Names,
+    /// modes, device numbers, link counts and content (including symlink targets) are always
+    /// compared, entries must appear in the same order.
+    pub fn equivalent(&self, other: &Cpio, ignore: EquivalenceMask) -> Result<bool, Error> {
+        let mut ours = self.iter_files();
+        let mut theirs = other.iter_files();
+
+        loop {
+            let (a, b) = match (ours.next()?, theirs.next()?) {
+                (None, None) => return Ok(true),
+                (Some(a), Some(b)) => (a, b),
+                _ => return Ok(false),
+            };
+
+            let same = a.name()? == b.name()?
+                && a.mode()? == b.mode()?
+                && a.nlink()? == b.nlink()?
+                && a.rdevmajor()? == b.rdevmajor()?
+                && a.rdevminor()? == b.rdevminor()?
+                && a.file_content()? == b.file_content()?
+                && (ignore.mtime || a.mtime()? == b.mtime()?)
+                && (ignore.owner || (a.uid()?, a.gid()?) == (b.uid()?, b.gid()?))
+                && (ignore.inode || (a.inode()?, a.devmajor()?, a.devminor()?)
+                    == (b.inode()?, b.devmajor()?, b.devminor()?));
+
+            if !same {
+                return Ok(false)
+            }
+        }
+    }
+
+    /// SHA-256 over the logical content of the archive: each entry's name, mode, device
+    /// numbers, owner, mtime, inode and content (symlink targets included), minus the metadata
+    /// selected by `ignore`. Entries are hashed in name order and hardlink placeholders with the
+    /// content they share, so archives `equivalent` under the same mask that merely order or
+    /// link their entries differently share a digest.
+    pub fn content_digest(&self, ignore: EquivalenceMask) -> Result<[u8; 32], Error> {
+        let mut entries = vec![];
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            entries.push(file);
+        }
+        entries.sort_by(|a, b| a.name().ok().cmp(&b.name().ok()));
+
+        let hardlinks = self.hardlinks()?;
+        let mut hasher = Sha256::new();
+        for entry in &entries {
+            let content = hardlinks.content(entry)?;
+            let mut fields = vec![entry.mode()?, entry.rdevmajor()?, entry.rdevminor()?];
+            if !ignore.owner {
+                fields.extend([entry.uid()?, entry.gid()?]);
+            }
+            if !ignore.mtime {
+                fields.push(entry.mtime()?);
+            }
+            if !ignore.inode {
+                fields.extend([entry.inode()?, entry.devmajor()?, entry.devminor()?]);
+            }
+
+            // length prefixes keep the variable sized name and content from running together
+            let name = entry.name()?;
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name);
+            for field in fields {
+                hasher.update(field.to_le_bytes());
+            }
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(content);
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    /// Collect the hardlink groups of the archive in one pass, to resolve the members of any
+    /// number of entries without rescanning the archive:
+    ///
+    /// ```
+    /// use rcpio::{Cpio, CpioBuilder, CpioFormat};
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// builder.insert_data("bin/sh", b"busybox", 0o755).unwrap();
+    /// builder.insert_data("bin/ls", b"busybox", 0o755).unwrap();
+    /// builder.hardlink_group(&["bin/sh", "bin/ls"]).unwrap();
+    /// let bytes = builder.to_vec().unwrap();
+    /// let cpio = Cpio::load(&bytes).unwrap();
+    ///
+    /// let hardlinks = cpio.hardlinks().unwrap();
+    /// let (sh, ls) = (cpio.find("bin/sh").unwrap().unwrap(), cpio.find("bin/ls").unwrap().unwrap());
+    /// assert_eq!(sh.file_content().unwrap(), b"");
+    /// assert_eq!(hardlinks.content(&sh).unwrap(), b"busybox");
+    /// assert_eq!(hardlinks.first(&ls).unwrap().unwrap().name_string().unwrap(), "bin/sh");
+    /// ```
+    pub fn hardlinks(&self) -> Result<Hardlinks<'a>, Error> {
+        let mut groups: HashMap<(u64, u64, u64), (CpioEntry<'a>, Option<CpioEntry<'a>>)> = HashMap::new();
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            if !file.is_reg()? || file.nlink()? <= 1 {
+                continue;
+            }
+
+            let has_data = file.filesize()? != 0;
+            let group = groups.entry(hardlink_key(&file)?).or_insert_with(|| (file.clone(), None));
+            if has_data && group.1.is_none() {
+                group.1 = Some(file);
+            }
+        }
+        Ok(Hardlinks { groups })
+    }
+
+    /// Content of an entry, following hardlink placeholders. In newc/crc archives the members of
+    /// a hardlink group share an inode and only one of them (normally the last) carries the
+    /// data, the others are zero length placeholders.
+    #[deprecated(note = "scans the whole archive on every call, resolve entries through `Cpio::hardlinks` instead")]
+    pub fn resolve_content(&self, entry: &CpioEntry<'a>) -> Result<&'a [u8], Error> {
+        self.hardlinks()?.content(entry)
+    }
+
+    /// The first regular file in the archive sharing `entry`'s inode when `entry` is a later
+    /// member of a hardlink group, `None` for the first member and for files that aren't
+    /// hardlinked
+    #[deprecated(note = "scans the whole archive on every call, resolve entries through `Cpio::hardlinks` instead")]
+    pub fn first_hardlink(&self, entry: &CpioEntry<'a>) -> Result<Option<CpioEntry<'a>>, Error> {
+        Ok(self.hardlinks()?.first(entry)?.cloned())
+    }
+
+    /// Follow `entry` through any chain of symlinks to the entry it finally refers to, resolving
+    /// targets against the archive root. `None` if a target is missing from the archive or the
+    /// chain doesn't end within 40 links.
+    pub fn follow_symlink(&self, entry: &CpioEntry<'a>) -> Result<Option<CpioEntry<'a>>, Error> {
+        let mut current = entry.clone();
+
+        for _ in 0..40 {
+            if !current.is_link()? {
+                return Ok(Some(current))
+            }
+
+            let name = normalize_internal_path(&current.name_string()?)?;
+            let target = from_utf8(current.file_content()?).map_err(|e|
+                Error::StringEncodingError(e.to_string())
+            )?;
+            current = match self.find(&resolve_link_target(&name, target))? {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+        }
+        Ok(None)
+    }
+
+    /// Every symlink in the archive with its target and where that target leads. Targets are
+    /// resolved lexically against the archive root like `follow_symlink`, dangling links and
+    /// cycles of links are flagged. Entries whose names extraction rejects are listed in
+    /// `invalid_names` and left out of the graph.
+    pub fn symlink_report(&self) -> Result<SymlinkReport, Error> {
+        let mut report = SymlinkReport::default();
+        let mut names = std::collections::HashSet::new();
+        let mut links: Vec<(String, String)> = vec![];
+
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+
+            let name = match normalize_internal_path(&file.name_string()?) {
+                Ok(name) => name,
+                Err(_) => {
+                    report.invalid_names.push(file.name_string()?);
+                    continue
+                },
+            };
+            if file.is_link()? {
+                let target = from_utf8(file.file_content()?).map_err(|e|
+                    Error::StringEncodingError(e.to_string())
+                )?;
+                links.push((name.clone(), target.to_string()));
+            }
+            names.insert(name);
+        }
+        let targets: HashMap<&str, &str> = links.iter()
+            .map(|(name, target)| (name.as_str(), target.as_str()))
+            .collect();
+
+        for (name, target) in &links {
+            let mut chain = vec![name.clone()];
+            let mut current = resolve_link_target(name, target);
+
+            let status = loop {
+                if let Some(start) = chain.iter().position(|link| *link == current) {
+                    // rotate so the same cycle found from another member compares equal
+                    let mut cycle = chain[start..].to_vec();
+                    let first = cycle.iter().enumerate()
+                        .min_by_key(|(_, link)| *link)
+                        .map_or(0, |(index, _)| index);
+                    cycle.rotate_left(first);
+                    if !report.cycles.contains(&cycle) {
+                        report.cycles.push(cycle);
+                    }
+                    break SymlinkStatus::Cycle;
+                }
+                if !names.contains(&current) {
+                    break SymlinkStatus::Dangling;
+                }
+                match targets.get(current.as_str()) {
+                    Some(next) => {
+                        let next = resolve_link_target(&current, next);
+                        chain.push(std::mem::replace(&mut current, next));
+                    },
+                    None => break SymlinkStatus::Resolved(current),
+                }
+            };
+
+            report.links.push(SymlinkInfo { name: name.clone(), target: target.clone(), status });
+        }
+        Ok(report)
+    }
+
+    /// Break down where the space in the archive goes: headers, names, content and alignment
+    /// padding. The trailer entry counts like any other, and everything after it (block
+    /// padding) counts as padding.
+    pub fn overhead(&self) -> Result<OverheadReport, Error> {
+        let mut report = OverheadReport { archive_size: self.mem.len(), ..Default::default() };
+
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            let (name_len, content_len) = (file.namesize()?, file.filesize()?);
+            report.header_bytes += file.format.header_len();
+            report.name_bytes += name_len;
+            report.content_bytes += content_len;
+            // an archive may end right after the trailer name, without its alignment padding
+            let end = file.next()?.min(self.mem.len());
+            report.padding_bytes += (end - file.index)
+                .saturating_sub(file.format.header_len() + name_len + content_len);
+            if file.is_trailer()? {
+                report.padding_bytes += self.mem.len().saturating_sub(file.next()?);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Size of an entry for size accounting, symlinks are sized according to `policy` and every
+    /// other entry by its stored content
+    pub fn logical_size(&self, entry: &CpioEntry<'a>, policy: SizePolicy) -> Result<u64, Error> {
+        if !entry.is_link()? {
+            return Ok(entry.filesize()? as u64)
+        }
+
+        match policy {
+            SizePolicy::TargetLength => Ok(entry.filesize()? as u64),
+            SizePolicy::Zero => Ok(0),
+            SizePolicy::Target => match self.follow_symlink(entry)? {
+                Some(target) if target.is_reg()? => Ok(self.hardlinks()?.content(&target)?.len() as u64),
+                _ => Ok(0),
+            },
+        }
+    }
+
+    /// Entry counts by type and the total logical size of the archive
+    pub fn stats(&self, policy: SizePolicy) -> Result<ArchiveStats, Error> {
+        let mut stats = ArchiveStats::default();
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+
+            stats.add(file.mode()?, self.logical_size(&file, policy)?);
+        }
+        Ok(stats)
+    }
+
+    /// The `count` largest entries by logical size, largest first
+    pub fn largest(&self, count: usize, policy: SizePolicy) -> Result<Vec<(String, u64)>, Error> {
+        let mut sizes = vec![];
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            sizes.push((file.name_string()?, self.logical_size(&file, policy)?));
+        }
+
+        sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        sizes.truncate(count);
+        Ok(sizes)
+    }
+
+    /// Content size of the archive grouped by each entry's first path component, like
+    /// `du --max-depth=1` at the archive root. Top-level files are their own group, the root
+    /// entry is left out. Largest groups come first, ties are ordered by name.
+    pub fn size_by_toplevel(&self) -> Result<Vec<(String, u64)>, Error> {
+        let mut groups: HashMap<String, u64> = HashMap::new();
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            let name = file.name_string()?;
+            let Some(toplevel) = name.split('/').find(|c| !c.is_empty() && *c != ".") else {
+                continue
+            };
+            *groups.entry(toplevel.to_string()).or_default() += file.filesize()? as u64;
+        }
+
+        let mut sizes: Vec<(String, u64)> = groups.into_iter().collect();
+        sizes.sort_by(|(a_name, a_size), (b_name, b_size)|
+            b_size.cmp(a_size).then_with(|| a_name.cmp(b_name))
+        );
+        Ok(sizes)
+    }
+
+    /// Byte offsets of the entries whose name (without its null terminator) isn't valid in
+    /// `encoding`, for vetting archives before handing them to tools that only accept e.g.
+    /// UTF-8 file names. The trailer is not checked.
+    pub fn check_names(&self, encoding: NameEncoding) -> Result<Vec<usize>, Error> {
+        let mut invalid = vec![];
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            let name = file.name()?;
+            let name = name.strip_suffix(b"\0").unwrap_or(name);
+            let valid = match encoding {
+                NameEncoding::Utf8 => from_utf8(name).is_ok(),
+                NameEncoding::Ascii => name.is_ascii(),
+                NameEncoding::AnyBytes => true,
+            };
+            if !valid {
+                invalid.push(file.index);
+            }
+        }
+        Ok(invalid)
+    }
+
+    /// Names of the entries whose content doesn't match their crc checksum, empty for an intact
+    /// archive and for formats without checksums
+    pub fn verify(&self) -> Result<Vec<String>, Error> {
+        let mut corrupt = vec![];
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            if !file.verify_checksum()? {
+                corrupt.push(file.name_string()?);
+            }
+        }
+        Ok(corrupt)
+    }
+
+    /// Parent directories that entries are stored under without a directory entry of their own
+    /// earlier in the archive, in the order they are first needed. Strict consumers and minimal
+    /// unpackers expect every parent to be created before the entries inside it, the archive
+    /// root `.` is not required. Entries whose names extraction rejects are listed in
+    /// `invalid_names` and otherwise ignored.
+    pub fn check_directory_entries(&self) -> Result<DirectoryReport, Error> {
+        let mut directories = HashSet::new();
+        let mut report = DirectoryReport::default();
+
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            let name = match normalize_internal_path(&file.name_string()?) {
+                Ok(name) => name,
+                Err(_) => {
+                    report.invalid_names.push(file.name_string()?);
+                    continue
+                },
+            };
+
+            report.missing.append(&mut missing_parents(&name, &mut directories));
+
+            if file.is_dir()? {
+                directories.insert(name);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Sum of the content sizes of every entry in the archive
+    pub fn content_size(&self) -> Result<u64, Error> {
+        let mut total = 0u64;
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            total += file.filesize()? as u64;
+        }
+        Ok(total)
+    }
+
+    /// Ensure the filesystem holding `output_path` has room for the archive contents,
+    /// this does not account for block rounding or sparse files
+    fn check_space(&self, output_path: &Path) -> Result<(), Error> {
+        let needed = self.content_size()?;
+        let available = available_space(output_path)?;
+
+        if needed > available {
+            return Err(Error::InsufficientSpaceError(
+                format!(
+                    "extracting requires {needed} bytes but only {available} bytes are available on {}",
+                    output_path.display()
+                )
+            ))
+        }
+        Ok(())
+    }
+}
+
+/// Resolve the target of the symlink `link_name` (a normalized archive path) to an archive path.
+/// Relative targets are taken from the link's directory, absolute targets from the archive root,
+/// and `..` stops at the root the way it does inside a chroot.
+fn resolve_link_target(link_name: &str, target: &str) -> String {
+    let mut components: Vec<&str> = if target.starts_with('/') || link_name == "." {
+        vec![]
+    } else {
+        link_name.split('/').collect()
+    };
+    // drop the link itself, leaving its directory
+    components.pop();
+
+    for component in target.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => {
+                components.pop();
+            },
+            component => components.push(component),
+        }
+    }
+
+    if components.is_empty() {
+        String::from(".")
+    } else {
+        components.join("/")
+    }
+}
+
+/// Whether the relative symlink target `target`, taken from the directory of `link_name` (a
+/// normalized path relative to the extraction root), climbs above that root. Absolute targets
+/// are left to the system the archive is meant for and never count as escaping.
+fn link_target_escapes(link_name: &str, target: &str) -> bool {
+    if target.starts_with('/') {
+        return false
+    }
+
+    // depth of the link's directory below the root
+    let mut depth = if link_name == "." { 0 } else { link_name.split('/').count() - 1 };
+    for component in target.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => {
+                if depth == 0 {
+                    return true
+                }
+                depth -= 1;
+            },
+            _ => depth += 1,
+        }
+    }
+    false
+}
+
+/// Normalize an archive entry name: repeated slashes are collapsed and `.` components dropped,
+/// the archive root is returned as `.`. Names with `..` components or a leading `/` are
+/// rejected rather than resolved since they could point outside the archive root.
+pub fn normalize_internal_path(name: &str) -> Result<String, Error> {
+    if name.starts_with('/') {
+        return Err(Error::InvalidPathError(format!("absolute path '{name}'")))
+    }
+
+    let mut components = vec![];
+    for component in name.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(Error::InvalidPathError(format!("parent directory reference in '{name}'"))),
+            component => components.push(component),
+        }
+    }
+
+    if components.is_empty() {
+        Ok(String::from("."))
+    } else {
+        Ok(components.join("/"))
+    }
+}
+
+/// Parents of the normalized entry name `name` that aren't in `directories` yet. They are added
+/// to it, so a missing parent is only reported once, the first time it is needed.
+fn missing_parents(name: &str, directories: &mut HashSet<String>) -> Vec<String> {
+    let mut missing = vec![];
+    let mut parent = String::new();
+    let components: Vec<&str> = name.split('/').collect();
+    for component in &components[..components.len() - 1] {
+        if !parent.is_empty() {
+            parent.push('/');
+        }
+        parent.push_str(component);
+        if directories.insert(parent.clone()) {
+            missing.push(parent.clone());
+        }
+    }
+    missing
+}
+
+/// Reject an entry whose name cannot be created on Linux, instead of failing with a bare
+/// ENAMETOOLONG partway through writing it
+fn check_name_length(name: &str, joined_path: &Path) -> Result<(), Error> {
+    if let Some(component) = Path::new(name).components()
+        .map(|c| c.as_os_str())
+        .find(|c| c.len() > defs::NAME_MAX)
+    {
+        return Err(Error::NameTooLongError(format!(
+            "component of '{name}' is {} bytes, the limit is {}", component.len(), defs::NAME_MAX
+        )))
+    }
+
+    // the null terminator counts towards PATH_MAX
+    let path_len = joined_path.as_os_str().len() + 1;
+    if path_len > defs::PATH_MAX {
+        return Err(Error::NameTooLongError(format!(
+            "'{name}' extracts to a {path_len} byte path, the limit is {}", defs::PATH_MAX
+        )))
+    }
+
+    Ok(())
+}
+
+/// Metadata to disregard when comparing archives with `Cpio::equivalent`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EquivalenceMask {
+    /// Ignore modification times
+    pub mtime: bool,
+
+    /// Ignore uid and gid
+    pub owner: bool,
+
+    /// Ignore inode and device numbers
+    pub inode: bool,
+}
+
+/// Set the collected directory modes and mtimes deepest first, so neither a read-only parent
+/// nor setting a child's metadata gets in the way
+fn restore_directories(
+    mut directories: Vec<(PathBuf, u32, u64)>,
+    options: &ExtractOptions
+) -> Result<(), Error> {
+    directories.sort_by_key(|(path, _, _)| std::cmp::Reverse(path.components().count()));
+    for (path, mode, mtime) in directories {
+        set_permissions(&path, Permissions::from_mode(mode)).map_err(|e|
+            Error::FileSystemError(format!("Unable to set permissions on {}: {e}", path.display()))
+        )?;
+        if !options.skip_mtime {
+            set_mtime(&path, mtime)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unlink `path` if it is a symlink, without following it
+fn remove_symlink(path: &Path) -> Result<(), Error> {
+    let is_symlink = symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink());
+    if is_symlink {
+        remove_file(path).map_err(|e|
+            Error::FileSystemError(
+                format!("Unable to remove existing symlink {}: {e}", path.display())
+            )
+        )?;
+    }
+    Ok(())
+}
+
+/// Refuse `joined_path` if one of its parent directories below `output_path` is a symlink that
+/// resolves outside `output_path`, e.g. an archive holding `evil -> /etc` followed by
+/// `evil/passwd`. Symlinks that stay inside the output directory are followed as usual.
+fn check_symlinked_parents(output_path: &Path, joined_path: &Path) -> Result<(), Error> {
+    let Some(relative) = joined_path.parent().and_then(|parent| parent.strip_prefix(output_path).ok()) else {
+        return Ok(())
+    };
+
+    let mut root = None;
+    let mut current = output_path.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        let Ok(meta) = symlink_metadata(&current) else {
+            // nothing exists below a missing directory, it gets created as a real directory
+            break;
+        };
+        if !meta.file_type().is_symlink() {
+            continue;
+        }
+
+        let root = match &root {
+            Some(root) => root,
+            None => root.insert(output_path.canonicalize().map_err(|e|
+                Error::FileSystemError(format!("Unable to resolve {}: {e}", output_path.display()))
+            )?),
+        };
+        if !current.canonicalize().is_ok_and(|resolved| resolved.starts_with(root)) {
+            return Err(Error::FileSystemError(format!(
+                "{} is a symlink leading outside the output directory, refusing to extract {} through it",
+                current.display(), joined_path.display()
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Unlink whatever non-directory sits at `path` so an entry can replace it. Unlinking rather
+/// than truncating leaves other hardlinks to an existing file untouched.
+fn remove_non_directory(path: &Path) -> Result<(), Error> {
+    let is_dir = symlink_metadata(path).map(|meta| meta.is_dir());
+    if is_dir.is_ok_and(|is_dir| !is_dir) {
+        remove_file(path).map_err(|e|
+            Error::FileSystemError(format!("Unable to replace {}: {e}", path.display()))
+        )?;
+    }
+    Ok(())
+}
+
+/// Create the device node, FIFO or socket described by `entry` at `path`, device nodes need
+/// CAP_MKNOD. A socket created this way is only a placeholder, nothing is listening on it.
+fn make_node(path: &Path, entry: &CpioEntry) -> Result<(), Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e|
+        Error::StringEncodingError(e.to_string())
+    )?;
+    let dev = libc::makedev(entry.rdevmajor()? as u32, entry.rdevminor()? as u32);
+
+    if unsafe { libc::mknod(c_path.as_ptr(), entry.mode()? as libc::mode_t, dev) } != 0 {
+        return Err(Error::FileSystemError(
+            format!(
+                "Unable to create special file {}: {}",
+                path.display(), std::io::Error::last_os_error()
+            )
+        ))
+    }
+    Ok(())
+}
+
+/// Where the first member of `entry`'s hardlink group was extracted, so `entry` can be linked to
+/// it. `None` if `entry` isn't a later member or the first member was filtered out or hasn't
+/// been written.
+fn extracted_hardlink(
+    output_path: &Path,
+    entry: &CpioEntry,
+    options: &ExtractOptions,
+    hardlinks: &Hardlinks
+) -> Result<Option<PathBuf>, Error> {
+    let Some(first) = hardlinks.first(entry)? else {
+        return Ok(None)
+    };
+    let name = normalize_internal_path(&first.name_string()?)?;
+    let Some(name) = options.output_name(&name) else {
+        return Ok(None)
+    };
+
+    let path = output_path.join(name);
+    Ok(symlink_metadata(&path).is_ok_and(|meta| meta.is_file()).then_some(path))
+}
+
+/// Apply the archived uid/gid to an extracted path (without following symlinks) as the
+/// ownership policy asks, returning why that failed when the policy tolerates failures
+fn restore_ownership(
+    path: &Path,
+    entry: &CpioEntry,
+    options: &ExtractOptions
+) -> Result<Option<String>, Error> {
+    let is_root = unsafe { libc::geteuid() } == 0;
+    if options.ownership_policy == OwnershipPolicy::Skip
+        || (options.ownership_policy == OwnershipPolicy::Auto && !is_root)
+    {
+        return Ok(None)
+    }
+
+    let (uid, gid) = (entry.uid()? as u32, entry.gid()? as u32);
+    let Err(e) = lchown(path, Some(uid), Some(gid)) else {
+        return Ok(None)
+    };
+    let message = format!("unable to set owner of {} to {uid}:{gid}: {e}", path.display());
+    if options.ownership_policy == OwnershipPolicy::Strict {
+        return Err(Error::FileSystemError(message))
+    }
+    Ok(Some(message))
+}
+
+/// Set the access and modification times of `path` (without following symlinks) to the
+/// archived mtime unless `skip_mtime` is set. The stored value is applied as is, a zero mtime
+/// gives the epoch rather than leaving the current time in place.
+fn restore_mtime(path: &Path, entry: &CpioEntry, options: &ExtractOptions) -> Result<(), Error> {
+    if options.skip_mtime {
+        return Ok(())
+    }
+    set_mtime(path, entry.mtime()?)
+}
+
+/// Set the access and modification times of `path` to `mtime` without following symlinks
+fn set_mtime(path: &Path, mtime: u64) -> Result<(), Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e|
+        Error::StringEncodingError(e.to_string())
+    )?;
+    let time = libc::timespec { tv_sec: mtime as libc::time_t, tv_nsec: 0 };
+    let times = [time, time];
+
+    let ret = unsafe {
+        libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW)
+    };
+    if ret != 0 {
+        return Err(Error::FileSystemError(
+            format!(
+                "Unable to set mtime on {}: {}",
+                path.display(), std::io::Error::last_os_error()
+            )
+        ))
+    }
+    Ok(())
+}
+
+/// How symlinks contribute to reported sizes. Every size report (`Cpio::stats`,
+/// `Cpio::largest` and `ls --total`) defaults to `TargetLength`, the size stored in the archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizePolicy {
+    /// The length of the target path, which is what `c_filesize` holds for a symlink
+    #[default]
+    TargetLength,
+
+    /// Symlinks take up no space
+    Zero,
+
+    /// The size of the regular file the symlink resolves to within the archive, zero if it
+    /// dangles or resolves to something else
+    Target,
+}
+
+/// Where a symlink leads within the archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymlinkStatus {
+    /// The chain of links ends at this archive path, which is not a symlink
+    Resolved(String),
+
+    /// The chain of links reaches a path missing from the archive
+    Dangling,
+
+    /// The chain of links loops back on itself
+    Cycle,
+}
+
+/// A symlink entry and the result of resolving it
+#[derive(Debug, Clone)]
+pub struct SymlinkInfo {
+    /// Normalized archive path of the symlink
+    pub name: String,
+
+    /// Target as stored in the archive
+    pub target: String,
+
+    pub status: SymlinkStatus,
+}
+
+/// The symlink graph of an archive, see `Cpio::symlink_report`
+#[derive(Debug, Clone, Default)]
+pub struct SymlinkReport {
+    /// Every symlink in archive order
+    pub links: Vec<SymlinkInfo>,
+
+    /// Each distinct cycle of links, as the archive paths of its members
+    pub cycles: Vec<Vec<String>>,
+
+    /// Raw names of entries that are absolute or reference a parent directory
+    pub invalid_names: Vec<String>,
+}
+
+/// Directory entries an archive lacks, see `Cpio::check_directory_entries`
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryReport {
+    /// Parent directories without a directory entry before the entries inside them, in the
+    /// order they are first needed
+    pub missing: Vec<String>,
+
+    /// Raw names of entries that are absolute or reference a parent directory
+    pub invalid_names: Vec<String>,
+}
+
+/// Where the bytes of an archive go, see `Cpio::overhead`
+#[derive(Debug, Clone, Default)]
+pub struct OverheadReport {
+    /// Total size of the archive
+    pub archive_size: usize,
+
+    /// File content, including symlink targets
+    pub content_bytes: usize,
+
+    /// Fixed-size entry headers, including the trailer's
+    pub header_bytes: usize,
+
+    /// Entry names including their null terminators
+    pub name_bytes: usize,
+
+    /// Alignment padding after names and content, plus the block padding after the trailer
+    pub padding_bytes: usize,
+}
+
+/// Summary of an archive's contents
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveStats {
+    /// Number of entries, excluding the trailer
+    pub entries: usize,
+    pub regular_files: usize,
+    pub directories: usize,
+    pub symlinks: usize,
+
+    /// Character and block devices
+    pub devices: usize,
+
+    /// FIFOs and sockets
+    pub other: usize,
+
+    /// Sum of the logical sizes of all entries
+    pub total_size: u64,
+}
+
+impl ArchiveStats {
+    /// Count one entry with file mode `mode` and logical size `size`
+    fn add(&mut self, mode: u64, size: u64) {
+        self.entries += 1;
+        match mode & defs::S_IFMT {
+            defs::S_IFREG => self.regular_files += 1,
+            defs::S_IFDIR => self.directories += 1,
+            defs::S_IFLNK => self.symlinks += 1,
+            defs::S_IFCHR | defs::S_IFBLK => self.devices += 1,
+            _ => self.other += 1,
+        }
+        self.total_size += size;
+    }
+}
+
+/// What writing an archive produced
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveSummary {
+    /// Entry counts by type and total content bytes (symlinks counted by target length)
+    pub stats: ArchiveStats,
+
+    /// Size of the archive file on disk, after compression
+    pub archive_size: u64,
 
-    let mut entry_data: Vec<u8> = vec![];
+    /// Archive paths `archive_trees` found in more than one source tree, the entry of the last
+    /// of them was written. Directories present in several trees aren't listed.
+    pub overridden: Vec<String>,
+}
 
-    let inode = if let Some(inode) = inode_override {
-        inode
-    } else {
-        meta.st_ino() as u32
-    };
+/// Options controlling how an archive is extracted
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Verify the output filesystem has room for all file contents before writing anything
+    pub check_space: bool,
 
-    let entry = CpioBuilderEntry {
-        c_ino       : meta.st_ino() as u32,
-        c_mode      : meta.st_mode(),
-        c_uid       : meta.st_uid(),
-        c_gid       : meta.st_gid(),
-        c_nlink     : meta.st_nlink() as u32,
-        c_mtime     : meta.st_mtime() as u32,
-        c_filesize  : content.len() as u32,
-        c_devmajor  : major(meta.st_dev() as u32),
-        c_devminor  : minor(meta.st_dev() as u32),
-        c_rdevmajor : major(meta.st_rdev() as u32),
-        c_rdevminor : minor(meta.st_rdev() as u32),
-        c_namesize  : (internal_path.len() + 1) as u32,
-        c_check     : check,
-    };
+    /// Create the directory layout, symlinks and empty regular files without writing any file
+    /// content
+    pub metadata_only: bool,
 
-    entry_data.append(&mut entry.to_bytes(&format));
+    /// Only extract entries at or below this archive path
+    pub subtree: Option<String>,
 
-    // null-terminated internal path
-    entry_data.append(&mut internal_path.as_bytes().to_vec());
-    entry_data.push(0);
+    /// Extract subtree entries at their full archive path instead of rebasing them so the
+    /// subtree root becomes the output directory
+    pub keep_subtree_path: bool,
 
-    // pad to four byte alignment before start of file contents
-    let curr = curr_len + entry_data.len();
-    if curr % 4 != 0 {
-        entry_data.resize(entry_data.len() + (4 - (curr % 4)), 0)
-    }
+    /// Give extracted entries the uid/gid stored in the archive or leave them owned by the
+    /// current user
+    pub ownership_policy: OwnershipPolicy,
 
-    entry_data.append(&mut content);
+    /// What to do with entries of a type that can't be extracted
+    pub unknown_type_policy: UnknownTypePolicy,
 
-    // pad to four byte alignment at the end of file contents
-    let curr = curr_len + entry_data.len();
-    if curr % 4 != 0 {
-        entry_data.resize(entry_data.len() + (4 - (curr % 4)), 0)
-    }
+    /// What to do with character and block device entries
+    pub device_policy: DevicePolicy,
+
+    /// Drop this many leading components from each entry's path, like tar's
+    /// `--strip-components`. Entries with this many components or fewer (including the root
+    /// `.`) are skipped, so stripping 1 extracts `usr/bin` as `bin` and skips `usr` itself.
+    pub strip_components: usize,
 
-    Ok(entry_data)
+    /// Leave extracted entries with the current time instead of the archived `c_mtime`, e.g. for
+    /// archives whose mtimes were zeroed. By default the mtime is restored, a zero mtime as the
+    /// epoch.
+    pub skip_mtime: bool,
+
+    /// What to do with symlinks whose relative target leads outside the output directory
+    pub symlink_policy: SymlinkPolicy,
+
+    /// Replace files, symlinks and other non-directories already present at an entry's path
+    /// instead of failing. Existing directories are kept and get the archived mode.
+    pub overwrite: bool,
 }
 
-fn trailer_bytes(format: CpioFormat) -> Vec<u8> {
-    let mut out = vec![];
-    let magic = match format {
-        CpioFormat::Newc => defs::NEWC_MAGIC,
-        CpioFormat::Crc => defs::CRC_MAGIC,
-    };
+/// Handling of device node entries during extraction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DevicePolicy {
+    /// Create the device nodes, this needs root (CAP_MKNOD)
+    #[default]
+    Create,
 
-    out.append(&mut magic.to_vec());
-    out.append(&mut defs::TRAILER.to_vec());
+    /// Leave device entries out
+    Skip,
 
-    out
+    /// Leave device entries out but list them in `ExtractReport::devices` so they can be created
+    /// later, e.g. by a privileged pass or from a makedevs table
+    Record,
 }
 
-impl CpioBuilder {
-    pub fn new(format: CpioFormat) -> Self {
-        CpioBuilder { format, entries: vec![] }
-    }
+/// A device entry that was left out of an extraction with `DevicePolicy::Record`
+#[derive(Debug, Clone)]
+pub struct DeviceRecord {
+    /// Path of the device relative to the output directory
+    pub path: String,
 
-    pub fn insert(
-        &mut self, fs_path: &Path,
-        archive_path: &str
-    ) -> Result<(), Error>{
-        let archive_path = if archive_path.is_empty() {
-            "."
-        } else {
-            archive_path
-        };
+    /// Full file mode including the file type bits
+    pub mode: u64,
 
-        self.entries.push((fs_path.to_path_buf(), archive_path.to_string()));
+    pub uid: u64,
+    pub gid: u64,
+    pub major: u64,
+    pub minor: u64,
+}
 
-        Ok(())
+impl DeviceRecord {
+    /// The device as a line of a Buildroot style makedevs table:
+    /// `<name> <type> <mode> <uid> <gid> <major> <minor> <start> <inc> <count>`
+    pub fn makedevs_line(&self) -> String {
+        let kind = if self.mode & defs::S_IFMT == defs::S_IFBLK { 'b' } else { 'c' };
+        format!(
+            "/{} {kind} {:o} {} {} {} {} 0 0 -",
+            self.path, self.mode & defs::S_IPERM, self.uid, self.gid, self.major, self.minor
+        )
     }
+}
 
-    pub fn write(&self, archive_path: &PathBuf, gzip: bool) -> Result<(), Error> {
+/// Encoding entry names are expected to be in, see `Cpio::check_names`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameEncoding {
+    Utf8,
 
-        let mut out: Vec<u8> = vec![];
+    /// 7-bit ASCII only
+    Ascii,
 
-        let mut encoder = if gzip {
-            let out_fp = File::create(archive_path).map_err(|_|
-                Error::FileSystemError(
-                    format!("Failed to create output file for gzip stream {}", archive_path.to_string_lossy())
-                )
-            )?;
-            Some(GzEncoder::new(out_fp, Compression::default()))
-        } else {
-            None
-        };
+    /// Any bytes, as Unix file names allow, every name is valid
+    AnyBytes,
+}
 
-        for (fs_path, internal_path) in &self.entries {
-            out.append(&mut entry_bytes(fs_path, internal_path, out.len(), self.format, None)?);
-        }
+/// Handling of entries whose file type extraction doesn't support
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownTypePolicy {
+    /// Abort the extraction
+    #[default]
+    Error,
 
-        // write trailer
-        out.append(&mut trailer_bytes(self.format));
+    /// Silently leave the entry out
+    Skip,
 
-        // pad to 0x100 alignment
-        let mut padding = vec![];
-        if out.len() % 100 != 0 {
-            padding.resize(4 - (out.len() % 4), 0)
-        }
-        out.append(&mut padding);
+    /// Leave the entry out and list it in `ExtractReport::skipped`
+    SkipWithWarning,
+}
 
-        if let Some(ref mut encoder) = encoder {
-            encoder.write_all(&out).map_err(|_|
-                Error::GzEncoderError(String::from("failed when writing to encoder"))
-            )?;
-        }
+/// Handling of symlinks whose relative target, resolved from the link's location, climbs out of
+/// the output directory. Creating such a link is harmless by itself and extraction never writes
+/// through a symlink leading outside, whatever the policy, but anything reading through it
+/// afterwards ends up outside the extraction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Create every symlink as stored in the archive
+    #[default]
+    AllowAny,
+
+    /// Create escaping symlinks but list them in `ExtractReport::escaping_symlinks`
+    WarnEscaping,
+
+    /// Abort the extraction at the first escaping symlink
+    RejectEscaping,
+}
 
-        if gzip {
-            if let Some(encoder) = encoder {
-                encoder.finish().map_err(|_|
-                    Error::GzEncoderError(String::from("failed when calling 'finish()' on encoder"))
-                )?;
-            }
-        } else {
-            let mut out_fp = File::create(archive_path).map_err(|_|
-                Error::FileSystemError(
-                    format!("Failed to create output file {}", archive_path.to_string_lossy())
-                )
-            )?;
-            out_fp.write(&out).map_err(|_|
-                Error::FileSystemError(String::from("failed to write data to archive file"))
-            )?;
-        }
+/// Handling of a path that is in both archives of `Cpio::merge`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// The entry from the second archive replaces the first one
+    #[default]
+    KeepLast,
 
-        Ok(())
-    }
+    /// Abort the merge, unless both entries are directories
+    Reject,
 }
 
+/// Handling of the archived uid/gid during extraction. Ownership is always set before the mode,
+/// as changing the owner clears setuid and setgid bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OwnershipPolicy {
+    /// Restore the archived owner when running as root, listing entries where that fails in
+    /// `ExtractReport::ownership_failures`, otherwise leave everything owned by the current user
+    #[default]
+    Auto,
 
+    /// Leave everything owned by the current user
+    Skip,
 
-pub struct Cpio<'a> {
-    mem: &'a [u8],
-    format: CpioFormat
+    /// Always try to restore the archived owner, listing entries where that isn't permitted in
+    /// `ExtractReport::ownership_failures` and extracting them as the current user
+    Warn,
+
+    /// Restore the archived owner and abort the extraction if that isn't permitted
+    Strict,
 }
 
-impl<'a> Cpio<'a> {
-    pub fn load(mem: &'a [u8]) -> Result<Self, Error> {
-        let format = identify_format(mem)?;
-        Ok(Cpio { mem, format })
-    }
+/// Summary of what an extraction wrote
+#[derive(Debug, Clone, Default)]
+pub struct ExtractReport {
+    /// Entries written to the output directory, in the order they were written
+    pub entries: Vec<ExtractedEntry>,
 
-    pub fn iter_files(&self) -> CpioEntryIter<'a> {
-        CpioEntryIter { index: 0, archive_mem: self.mem, format: self.format, trailer_seen: false }
-    }
+    /// Device entries left out under `DevicePolicy::Record`
+    pub devices: Vec<DeviceRecord>,
 
-    pub fn extract_one(&self, output_path: &Path, entry: &CpioEntry) -> Result<(), Error> {
-        let path = String::from_utf8(entry.name()?.to_vec()).map_err(|e|
-            Error::StringEncodingError(e.to_string())
-        )?;
-        let trimmed_path = path.trim_end_matches('\0');
+    /// Names of the entries left out under `UnknownTypePolicy::SkipWithWarning` because
+    /// extraction doesn't support their file type
+    pub skipped: Vec<String>,
 
-        let joined_path = output_path.join(trimmed_path).canonicalize().map_err(|e| {
-            Error::FileSystemError(e.to_string())
-        })?;
+    /// Names and targets of the symlinks created under `SymlinkPolicy::WarnEscaping` whose
+    /// relative target leads outside the output directory
+    pub escaping_symlinks: Vec<(String, String)>,
 
-        if !joined_path.starts_with(output_path) {
+    /// Why the archived owner couldn't be restored, for each entry where that failed under
+    /// `OwnershipPolicy::Auto` or `Warn`. Those entries stay owned by the current user.
+    pub ownership_failures: Vec<String>,
+}
 
-        }
+/// An entry written by an extraction
+#[derive(Debug, Clone)]
+pub struct ExtractedEntry {
+    /// Path relative to the output directory, `.` for the output directory itself
+    pub path: PathBuf,
 
-        Ok(())
+    /// Full file mode including the file type bits
+    pub mode: u64,
+}
 
-    }
+/// What extracting a single entry did, `unarchive_with_options` adds it to the `ExtractReport`
+#[derive(Debug, Default)]
+struct EntryOutcome {
+    /// Path that was written, `None` if the entry was left out
+    path: Option<PathBuf>,
 
-    pub fn push(&self, archive_path: &Path, fs_path: &Path, internal_path: &str) -> Result<(), Error> {
+    /// The entry was left out under `UnknownTypePolicy::SkipWithWarning`
+    skipped: bool,
 
-        // find trailer
-        let iter = self.iter_files();
-        if let Some(last) = iter.last()? {
-            let mut dat = self.mem[..last.index].to_vec();
-            dat.append(&mut entry_bytes(fs_path, internal_path, dat.len(), last.format, None)?);
-            dat.append(&mut trailer_bytes(last.format));
+    /// Target of a symlink created under `SymlinkPolicy::WarnEscaping` that leads outside the
+    /// output directory
+    escaping_target: Option<String>,
 
-            // pad to 0x100 alignment
-            let mut padding = vec![];
-            if dat.len() % 100 != 0 {
-                padding.resize(4 - (dat.len() % 4), 0)
-            }
-            dat.append(&mut padding);
+    /// Why the archived owner couldn't be restored, when that isn't an error
+    ownership_error: Option<String>,
+}
 
-            let mut out_fp = File::create(archive_path).map_err(|_|
-                Error::FileSystemError(
-                    format!("Failed to create output file {}", archive_path.to_string_lossy())
-                )
-            )?;
-            out_fp.write(&dat).map_err(|_|
-                Error::FileSystemError(String::from("failed to write data to archive file"))
-            )?;
+impl EntryOutcome {
+    fn written(path: PathBuf) -> Self {
+        EntryOutcome { path: Some(path), ..Default::default() }
+    }
+}
 
-            Ok(())
+impl ExtractOptions {
+    /// Map a normalized entry name to the path it extracts to relative to the output directory,
+    /// `None` if the entry is filtered out
+    fn output_name(&self, name: &str) -> Option<String> {
+        let Some(subtree) = &self.subtree else {
+            return self.strip(name)
+        };
+        // a subtree that can never match an entry name filters out every entry
+        let subtree = normalize_internal_path(subtree).ok()?;
+
+        let rest = if subtree == "." {
+            name
+        } else if name == subtree {
+            "."
         } else {
-            Err(Error::InvalidArchiveError("Input archive missing trailer?".to_string()))
-        }
+            name.strip_prefix(&subtree)?.strip_prefix('/')?
+        };
 
+        let name = if self.keep_subtree_path {
+            name
+        } else {
+            rest
+        };
+        self.strip(name)
     }
 
-    pub fn unarchive(&self, output_path: &Path) -> Result<(), Error> {
-        let output_path = output_path.canonicalize().map_err(|e| {
-            Error::FileSystemError(e.to_string())
-        })?;
-        if !output_path.exists() {
-            create_dir(&output_path).map_err(|_|
-                Error::FileSystemError(
-                    format!("Unable to create output directory: {}", output_path.display())
-                )
-            )?
+    /// Drop the first `strip_components` components of `name`, `None` if nothing would remain
+    fn strip(&self, name: &str) -> Option<String> {
+        if self.strip_components == 0 {
+            return Some(name.to_string())
         }
-        let mut iter = self.iter_files();
-        while let Some(file) = iter.next()? {
-            if !file.is_trailer()? {
-                self.extract_one(&output_path, &file)?
-            }
+        if name == "." {
+            return None
         }
-        Ok(())
+
+        let components: Vec<&str> = name.split('/').collect();
+        if components.len() <= self.strip_components {
+            return None
+        }
+        Some(components[self.strip_components..].join("/"))
+    }
+}
+
+/// Number of bytes available to unprivileged users on the filesystem holding `path`
+fn available_space(path: &Path) -> Result<u64, Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e|
+        Error::StringEncodingError(e.to_string())
+    )?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::FileSystemError(
+            format!(
+                "Failed to query free space for {}: {}",
+                path.display(), std::io::Error::last_os_error()
+            )
+        ))
     }
+
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct CpioEntryHeader<'a> {
     c_magic     : &'a[u8],
     c_ino       : &'a[u8],
@@ -477,7 +3843,92 @@ struct CpioEntryHeader<'a> {
     c_check     : &'a[u8],
 }
 
-#[derive(Debug)]
+impl<'a> CpioEntryHeader<'a> {
+    /// Split an odc header into its fields. The combined dev and rdev fields back both their
+    /// major and minor field, and there is no check field.
+    fn odc(header: &'a [u8]) -> Self {
+        let rdev = &header[42..48];
+        let dev = &header[6..12];
+        CpioEntryHeader {
+            c_magic     : &header[0..6],
+            c_ino       : &header[12..18],
+            c_mode      : &header[18..24],
+            c_uid       : &header[24..30],
+            c_gid       : &header[30..36],
+            c_nlink     : &header[36..42],
+            c_mtime     : &header[48..59],
+            c_filesize  : &header[65..76],
+            c_devmajor  : dev,
+            c_devminor  : dev,
+            c_rdevmajor : rdev,
+            c_rdevminor : rdev,
+            c_namesize  : &header[59..65],
+            c_check     : &[],
+        }
+    }
+}
+
+/// The numeric fields of a cpio entry header, in on-disk order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderField {
+    Ino,
+    Mode,
+    Uid,
+    Gid,
+    Nlink,
+    Mtime,
+    Filesize,
+    DevMajor,
+    DevMinor,
+    RDevMajor,
+    RDevMinor,
+    Namesize,
+    Check,
+}
+
+impl HeaderField {
+    /// Name of the field as used by the cpio header definition
+    pub fn name(&self) -> &'static str {
+        match self {
+            HeaderField::Ino       => "c_ino",
+            HeaderField::Mode      => "c_mode",
+            HeaderField::Uid       => "c_uid",
+            HeaderField::Gid       => "c_gid",
+            HeaderField::Nlink     => "c_nlink",
+            HeaderField::Mtime     => "c_mtime",
+            HeaderField::Filesize  => "c_filesize",
+            HeaderField::DevMajor  => "c_devmajor",
+            HeaderField::DevMinor  => "c_devminor",
+            HeaderField::RDevMajor => "c_rdevmajor",
+            HeaderField::RDevMinor => "c_rdevminor",
+            HeaderField::Namesize  => "c_namesize",
+            HeaderField::Check     => "c_check",
+        }
+    }
+}
+
+/// Owned summary of an entry's header, independent of the archive memory
+#[derive(Debug, Clone)]
+pub struct CpioEntryInfo {
+    /// Offset into the archive of the entry
+    pub index: usize,
+
+    /// Format of the entry header
+    pub format: CpioFormat,
+
+    /// Entry name without the trailing null terminator
+    pub name: String,
+
+    pub inode: u64,
+    pub mode: u64,
+    pub uid: u64,
+    pub gid: u64,
+    pub nlink: u64,
+    pub mtime: u64,
+    pub filesize: usize,
+}
+
+#[derive(Debug, Clone)]
 pub struct CpioEntry<'a> {
     /// Offset into the archive of this file entry
     pub index: usize,
@@ -495,10 +3946,14 @@ pub struct CpioEntry<'a> {
 impl<'a> CpioEntry<'a> {
     pub(crate) fn new(index: usize, format: CpioFormat, mem: &'a [u8])
     -> Result<Self, Error> {
-        if mem.len() - index < CPIO_HEADER_LEN {
+        if mem.len().saturating_sub(index) < format.header_len() {
             return Err(Error::EarlyEOFError);
         }
 
+        if format == CpioFormat::Odc {
+            return Ok(Self { index, format, mem, header: CpioEntryHeader::odc(&mem[index..]) })
+        }
+
         #[allow(clippy::identity_op)]
         #[allow(clippy::erasing_op)]
         let header = CpioEntryHeader {
@@ -525,129 +3980,223 @@ impl<'a> CpioEntry<'a> {
         self.header.c_magic
     }
 
-    pub fn inode(&self) -> Result<u64, Error> {
-        let str_inode = from_utf8(self.header.c_ino).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_ino' from utf8 failed"))
+    /// All numeric header fields, parsed
+    pub fn header_fields(&self) -> Result<CpioHeader, Error> {
+        Ok(CpioHeader {
+            c_ino       : self.inode()? as u32,
+            c_mode      : self.mode()? as u32,
+            c_uid       : self.uid()? as u32,
+            c_gid       : self.gid()? as u32,
+            c_nlink     : self.nlink()? as u32,
+            c_mtime     : self.mtime()? as u32,
+            c_filesize  : self.filesize()? as u32,
+            c_devmajor  : self.devmajor()? as u32,
+            c_devminor  : self.devminor()? as u32,
+            c_rdevmajor : self.rdevmajor()? as u32,
+            c_rdevminor : self.rdevminor()? as u32,
+            c_namesize  : self.namesize()? as u32,
+            c_check     : self.checksum()? as u32,
+        })
+    }
+
+    /// The unparsed bytes of a header field, useful for inspecting mis-encoded headers
+    pub fn raw_field(&self, field: HeaderField) -> &[u8] {
+        match field {
+            HeaderField::Ino       => self.header.c_ino,
+            HeaderField::Mode      => self.header.c_mode,
+            HeaderField::Uid       => self.header.c_uid,
+            HeaderField::Gid       => self.header.c_gid,
+            HeaderField::Nlink     => self.header.c_nlink,
+            HeaderField::Mtime     => self.header.c_mtime,
+            HeaderField::Filesize  => self.header.c_filesize,
+            HeaderField::DevMajor  => self.header.c_devmajor,
+            HeaderField::DevMinor  => self.header.c_devminor,
+            HeaderField::RDevMajor => self.header.c_rdevmajor,
+            HeaderField::RDevMinor => self.header.c_rdevminor,
+            HeaderField::Namesize  => self.header.c_namesize,
+            HeaderField::Check     => self.header.c_check,
+        }
+    }
+
+    /// Parse a hex-encoded (octal for odc) header field, errors include the escaped raw bytes of
+    /// the field
+    fn parse_field(&self, field: HeaderField) -> Result<u64, Error> {
+        let raw = self.raw_field(field);
+        if self.format == CpioFormat::Odc {
+            return self.parse_odc_field(field, raw)
+        }
+
+        let str_field = from_utf8(raw).map_err(|_|
+            Error::EntryConversionError(
+                format!("Converting '{}' from utf8 failed: \"{}\"", field.name(), raw.escape_ascii())
+            )
         )?;
 
-        u64::from_str_radix(str_inode, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_ino' to u64 failed"))
+        u64::from_str_radix(str_field, 16).map_err(|_| {
+            Error::EntryConversionError(
+                format!("Converting '{}' to u64 failed: \"{}\"", field.name(), raw.escape_ascii())
+            )
         })
     }
 
-    pub fn mode(&self) -> Result<u64, Error> {
-        let str_mode = from_utf8(self.header.c_mode).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_mode' from utf8 failed"))
-        )?;
+    /// Parse an octal odc field, splitting the combined device numbers and reading the missing
+    /// check field as zero
+    fn parse_odc_field(&self, field: HeaderField, raw: &[u8]) -> Result<u64, Error> {
+        if raw.is_empty() {
+            return Ok(0)
+        }
+
+        let value = from_utf8(raw).ok()
+            .and_then(|str_field| u64::from_str_radix(str_field, 8).ok())
+            .ok_or_else(|| Error::EntryConversionError(
+                format!("Converting '{}' from octal failed: \"{}\"", field.name(), raw.escape_ascii())
+            ))?;
 
-        u64::from_str_radix(str_mode, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_mode' to u64 failed"))
+        Ok(match field {
+            HeaderField::DevMajor | HeaderField::RDevMajor => major(value as u32) as u64,
+            HeaderField::DevMinor | HeaderField::RDevMinor => minor(value as u32) as u64,
+            _ => value,
         })
     }
 
+    pub fn inode(&self) -> Result<u64, Error> {
+        self.parse_field(HeaderField::Ino)
+    }
+
+    pub fn mode(&self) -> Result<u64, Error> {
+        self.parse_field(HeaderField::Mode)
+    }
+
+    /// The mode in `ls -l` notation, the first character gives the file type and the setuid,
+    /// setgid and sticky bits show up in the execute slots:
+    ///
+    /// ```
+    /// use rcpio::{Cpio, CpioBuilder, CpioFormat, CpioHeader};
+    ///
+    /// let types = [
+    ///     ("file", 0o100644, "-rw-r--r--"),
+    ///     ("dir", 0o040755, "drwxr-xr-x"),
+    ///     ("link", 0o120777, "lrwxrwxrwx"),
+    ///     ("chr", 0o020620, "crw--w----"),
+    ///     ("blk", 0o060660, "brw-rw----"),
+    ///     ("fifo", 0o010644, "prw-r--r--"),
+    ///     ("sock", 0o140755, "srwxr-xr-x"),
+    ///     ("sudo", 0o104755, "-rwsr-xr-x"),
+    ///     ("setgid", 0o102644, "-rw-r-Sr--"),
+    ///     ("tmp", 0o041777, "drwxrwxrwt"),
+    /// ];
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// for (ino, (name, mode, _)) in types.iter().enumerate() {
+    ///     builder.insert_header(name, CpioHeader::synthetic(ino as u32, *mode), vec![]).unwrap();
+    /// }
+    /// let bytes = builder.to_vec().unwrap();
+    /// let cpio = Cpio::load(&bytes).unwrap();
+    /// for (name, _, expected) in types {
+    ///     assert_eq!(cpio.find(name).unwrap().unwrap().mode_str().unwrap(), expected);
+    /// }
+    /// ```
     pub fn mode_str(&self) -> Result<String, Error> {
         mode_to_str(self.mode()?)
     }
 
+    /// Permission bits of the mode including setuid, setgid and sticky, without the file type
+    pub fn permissions(&self) -> Result<u32, Error> {
+        Ok((self.mode()? & defs::S_IPERM) as u32)
+    }
+
+    /// File type bits of the mode, without any permission bits
+    pub fn file_type_bits(&self) -> Result<u32, Error> {
+        Ok((self.mode()? & defs::S_IFMT) as u32)
+    }
+
+    /// A regular file with any of the user, group or other execute bits set
+    pub fn is_executable(&self) -> Result<bool, Error> {
+        Ok(self.is_reg()? && self.permissions()? & 0o111 != 0)
+    }
+
     pub fn is_link(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFLNK)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFLNK)
     }
 
     pub fn is_dir(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFDIR)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFDIR)
     }
 
     pub fn is_reg(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFREG)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFREG)
     }
 
     pub fn is_sock(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFSOCK)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFSOCK)
     }
 
     pub fn is_fifo(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFIFO)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFIFO)
     }
 
     pub fn is_blk(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFBLK)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFBLK)
     }
 
     pub fn is_chr(&self) -> Result<bool, Error> {
-        Ok((self.mode()? & defs::S_IFMT) == defs::S_IFCHR)
+        Ok(self.file_type_bits()? as u64 == defs::S_IFCHR)
     }
 
     pub fn uid(&self) -> Result<u64, Error> {
-        let str_uid = from_utf8(self.header.c_uid).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_uid' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_uid, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_uid' to u64 failed"))
-        })
+        self.parse_field(HeaderField::Uid)
     }
 
     pub fn gid(&self) -> Result<u64, Error> {
-        let str_gid = from_utf8(self.header.c_gid).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_gid' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_gid, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_gid' to u64 failed"))
-        })
+        self.parse_field(HeaderField::Gid)
     }
 
     pub fn nlink(&self) -> Result<u64, Error> {
-        let str_nlink = from_utf8(self.header.c_nlink).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_nlink' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_nlink, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_nlink' to u64 failed"))
-        })
+        self.parse_field(HeaderField::Nlink)
     }
 
+    /// The literal `c_mtime` value, a zero mtime is a valid timestamp (the epoch) rather than a
+    /// missing one
     pub fn mtime(&self) -> Result<u64, Error> {
-        let str_mtime = from_utf8(self.header.c_mtime).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_mtime' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_mtime, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_mtime' to u64 failed"))
-        })
+        self.parse_field(HeaderField::Mtime)
     }
 
     pub fn filesize(&self) -> Result<usize, Error> {
-        let str_filesize = from_utf8(self.header.c_filesize).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_filesize' from utf8 failed"))
-        )?;
-
-        usize::from_str_radix(str_filesize, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_filesize' to usize failed"))
-        })
+        Ok(self.parse_field(HeaderField::Filesize)? as usize)
     }
 
 
-    /// The file content begins after the name, the start is 4-byte aligned
+    /// The file content begins after the name, the start is 4-byte aligned (unaligned for odc)
     fn file_content_offset(&self) -> Result<usize, Error> {
         let nsize = self.namesize()?;
         let noff = self.name_offset();
 
-        let mut nend = noff + nsize;
-        if (self.index + nend) % 4 != 0 {
-            nend += 4 - ((self.index + nend) % 4);
-        }
-
-        Ok(nend)
+        let nend = self.index + noff + nsize;
+        Ok(nend.next_multiple_of(self.format.alignment()) - self.index)
     }
 
-    pub fn file_content(&self) -> Result<&[u8], Error> {
+    /// The stored content of the entry, which may end exactly at the last byte of the archive,
+    /// e.g. when an embedded archive is sliced tightly:
+    ///
+    /// ```
+    /// use rcpio::{Cpio, CpioBuilder, CpioFormat};
+    ///
+    /// let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    /// builder.insert_data("data", b"1234", 0o644).unwrap();
+    /// let bytes = builder.to_vec().unwrap();
+    ///
+    /// // cut the archive right after the content, before the trailer
+    /// let end = bytes.windows(4).position(|w| w == b"1234").unwrap() + 4;
+    /// let cpio = Cpio::load(&bytes[..end]).unwrap();
+    /// assert_eq!(cpio.find("data").unwrap().unwrap().file_content().unwrap(), b"1234");
+    /// ```
+    pub fn file_content(&self) -> Result<&'a [u8], Error> {
         let fc_start = self.file_content_offset()?;
         let fc_size = self.filesize()?;
 
         let slice = &self.mem[self.index..];
 
-        if fc_start + fc_size >= slice.len() {
+        if fc_start + fc_size > slice.len() {
             Err(Error::EarlyEOFError)
         } else {
             Ok(&slice[fc_start..fc_start+fc_size])
@@ -655,61 +4204,31 @@ impl<'a> CpioEntry<'a> {
     }
 
     pub fn devmajor(&self) -> Result<u64, Error> {
-        let str_devmajor = from_utf8(self.header.c_devmajor).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_devmajor' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_devmajor, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_devmajor' to u64 failed"))
-        })
+        self.parse_field(HeaderField::DevMajor)
     }
 
     pub fn devminor(&self) -> Result<u64, Error> {
-        let str_devminor = from_utf8(self.header.c_devminor).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_devminor' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_devminor, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_devminor' to u64 failed"))
-        })
+        self.parse_field(HeaderField::DevMinor)
     }
 
     pub fn rdevmajor(&self) -> Result<u64, Error> {
-        let str_rdevmajor = from_utf8(self.header.c_rdevmajor).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_rdevmajor' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_rdevmajor, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_rdevmajor' to u64 failed"))
-        })
+        self.parse_field(HeaderField::RDevMajor)
     }
 
     pub fn rdevminor(&self) -> Result<u64, Error> {
-        let str_rdevminor = from_utf8(self.header.c_rdevminor).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_rdevminor' from utf8 failed"))
-        )?;
-
-        u64::from_str_radix(str_rdevminor, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_rdevminor' to u64 failed"))
-        })
+        self.parse_field(HeaderField::RDevMinor)
     }
 
     pub fn namesize(&self) -> Result<usize, Error> {
-        let str_namesize = from_utf8(self.header.c_namesize).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_namesize' from utf8 failed"))
-        )?;
-
-        usize::from_str_radix(str_namesize, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_namesize' to usize failed"))
-        })
+        Ok(self.parse_field(HeaderField::Namesize)? as usize)
     }
 
     /// The name starts immediately after the header
     fn name_offset(&self) -> usize {
-        CPIO_HEADER_LEN
+        self.format.header_len()
     }
 
-    pub fn name(&self) -> Result<&[u8], Error> {
+    pub fn name(&self) -> Result<&'a [u8], Error> {
         let nsize = self.namesize()?;
         let noff = self.name_offset();
         let slice = &self.mem[self.index..];
@@ -720,27 +4239,105 @@ impl<'a> CpioEntry<'a> {
         }
     }
 
-    pub fn checksum(&self) -> Result<u64, Error> {
-        let str_check = from_utf8(self.header.c_check).map_err(|_|
-            Error::EntryConversionError(String::from("Converting 'c_check' from utf8 failed"))
+    /// Compare the entry name component by component against `normalized`, a path already
+    /// passed through `normalize_internal_path`, without allocating. Empty and `.` components of
+    /// the name are skipped the way normalizing would, names it rejects never match.
+    fn name_matches(&self, normalized: &str) -> Result<bool, Error> {
+        let name = self.name()?;
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        let name = &name[..end];
+        if name == normalized.as_bytes() {
+            return Ok(true)
+        }
+        if name.starts_with(b"/") {
+            return Ok(false)
+        }
+
+        // most entries differ in their last component, rule those out before splitting the name
+        let mut tail = name;
+        while let Some(rest) = tail.strip_suffix(b"/").or_else(|| tail.strip_suffix(b"/.")) {
+            tail = rest;
+        }
+        if normalized != "." && !tail.ends_with(normalized.rsplit('/').next().unwrap_or("").as_bytes()) {
+            return Ok(false)
+        }
+
+        let components = name.split(|b| *b == b'/').filter(|c| !c.is_empty() && *c != b".");
+        // the root normalizes to `.`, which has no components
+        let wanted = normalized.split('/').filter(|c| *c != ".").map(str::as_bytes);
+        Ok(components.eq(wanted))
+    }
+
+    /// The entry name as a string, without the trailing null terminator
+    pub fn name_string(&self) -> Result<String, Error> {
+        let name = String::from_utf8(self.name()?.to_vec()).map_err(|e|
+            Error::StringEncodingError(e.to_string())
         )?;
+        Ok(name.trim_end_matches('\0').to_string())
+    }
 
-        u64::from_str_radix(str_check, 16).map_err(|_| {
-            Error::EntryConversionError(String::from("Converting 'c_check' to u64 failed"))
-        })
+    /// The stored `c_check` field. It is 8 hex digits so it always fits in 32 bits, compare it
+    /// against `computed_checksum` as a `u32`.
+    pub fn checksum(&self) -> Result<u64, Error> {
+        self.parse_field(HeaderField::Check)
+    }
+
+    /// The crc checksum of this entry's stored content, computed the same way as when writing:
+    /// a 32-bit wrapping byte sum for regular files and zero for every other type
+    pub fn computed_checksum(&self) -> Result<u32, Error> {
+        if self.is_reg()? {
+            Ok(checksum(self.file_content()?))
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Whether the stored checksum matches the content, always true outside the crc format
+    /// where the check field carries no checksum
+    pub fn verify_checksum(&self) -> Result<bool, Error> {
+        if self.format != CpioFormat::Crc {
+            return Ok(true)
+        }
+        Ok(self.checksum()? as u32 == self.computed_checksum()?)
     }
 
     pub fn is_trailer(&self) -> Result<bool, Error> {
         Ok(self.namesize()? == 0xb && self.name()? == b"TRAILER!!!\0")
     }
 
+    /// Whether `other` refers to the same file as this entry, i.e. they are hardlinks of each
+    /// other. Inode numbers are only unique per device so the device numbers are compared too.
+    pub fn same_inode(&self, other: &CpioEntry) -> Result<bool, Error> {
+        Ok((self.inode()?, self.devmajor()?, self.devminor()?)
+            == (other.inode()?, other.devmajor()?, other.devminor()?))
+    }
+
+    /// Copy the header metadata out into an owned `CpioEntryInfo`
+    pub fn info(&self) -> Result<CpioEntryInfo, Error> {
+        Ok(CpioEntryInfo {
+            index: self.index,
+            format: self.format,
+            name: self.name_string()?,
+            inode: self.inode()?,
+            mode: self.mode()?,
+            uid: self.uid()?,
+            gid: self.gid()?,
+            nlink: self.nlink()?,
+            mtime: self.mtime()?,
+            filesize: self.filesize()?,
+        })
+    }
+
+    /// Number of bytes the entry occupies in the archive: header, name, content and the
+    /// alignment padding after each
+    pub fn entry_len(&self) -> Result<usize, Error> {
+        Ok(self.next()? - self.index)
+    }
+
     /// The next entry ends after the file content, the start is 4-byte aligned
     pub fn next(&self) -> Result<usize, Error> {
-        let mut next_offset = self.index + self.file_content_offset()? + self.filesize()?;
-        if next_offset % 4 != 0 {
-            next_offset += 4 - (next_offset % 4);
-        }
-        Ok(next_offset)
+        let next_offset = self.index + self.file_content_offset()? + self.filesize()?;
+        Ok(next_offset.next_multiple_of(self.format.alignment()))
     }
 
     pub fn valid_magic(&self) -> Result<bool, Error> {
@@ -748,21 +4345,11 @@ impl<'a> CpioEntry<'a> {
             return Err(Error::EarlyEOFError);
         }
 
-        let slice = &self.mem[self.index..];
-
-        let is_valid = match self.format {
-            CpioFormat::Newc => {
-                slice.starts_with(defs::NEWC_MAGIC)
-            },
-            CpioFormat::Crc => {
-                slice.starts_with(defs::CRC_MAGIC)
-            }
-        };
-
-        Ok(is_valid)
+        Ok(self.mem[self.index..].starts_with(self.format.magic()))
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct CpioEntryIter<'a> {
     /// Offset into the archive of the current entry
     index: usize,
@@ -775,6 +4362,25 @@ pub struct CpioEntryIter<'a> {
 
     /// Trailer was encountered
     trailer_seen: bool,
+
+    /// Validate that the entry following each entry starts with valid magic
+    strict: bool,
+
+    /// Continue into the next segment after a trailer
+    all_segments: bool,
+}
+
+impl CpioEntryIter<'_> {
+    /// Offset and format of the segment following the trailer that ended at `self.index`, if any.
+    /// Segments are separated by zero padding in 4-byte units, normally up to a 512-byte block.
+    fn next_segment(&self) -> Option<(usize, CpioFormat)> {
+        let rest = self.archive_mem.get(self.index..)?;
+        let padding = rest.chunks(4)
+            .take_while(|chunk| chunk.iter().all(|b| *b == 0))
+            .count() * 4;
+        let format = identify_format(rest.get(padding..)?).ok()?;
+        Some((self.index + padding, format))
+    }
 }
 
 impl<'a> FallibleIterator for CpioEntryIter<'a> {
@@ -808,6 +4414,27 @@ impl<'a> FallibleIterator for CpioEntryIter<'a> {
 
         self.index = file.next()?;
 
+        if self.trailer_seen && self.all_segments {
+            if let Some((index, format)) = self.next_segment() {
+                self.index = index;
+                self.format = format;
+                self.trailer_seen = false;
+            }
+        }
+
+        if self.strict && !self.trailer_seen {
+            let next_valid = self.archive_mem.get(self.index..)
+                .is_some_and(|slice| slice.starts_with(self.format.magic()));
+
+            if !next_valid {
+                return Err(Error::InvalidArchiveError(format!(
+                    "Content of entry '{}' at offset {:#x} overruns the following header",
+                    String::from_utf8_lossy(file.name()?).trim_end_matches('\0'),
+                    file.index,
+                )))
+            }
+        }
+
         Ok(Some(file))
     }
 }
@@ -0,0 +1,116 @@
+use std::io::{BufRead, Read, Write};
+
+/// Magic of the legacy lz4 format, little endian
+pub(crate) const LEGACY_MAGIC: u32 = 0x184c2102;
+
+/// Uncompressed size of each legacy block, all but the last block are exactly this long
+const BLOCK_SIZE: usize = 8 << 20;
+
+/// Writer producing the legacy lz4 format (`lz4 -l`), which is the only lz4 format the kernel
+/// unpacks an initramfs from: the magic followed by blocks of up to 8 MiB, each one a
+/// little endian compressed size and a raw lz4 block.
+pub(crate) struct LegacyLz4Encoder<W: Write> {
+    writer: W,
+    block: Vec<u8>,
+    magic_written: bool,
+}
+
+impl<W: Write> LegacyLz4Encoder<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        LegacyLz4Encoder { writer, block: Vec::new(), magic_written: false }
+    }
+
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if !self.magic_written {
+            self.writer.write_all(&LEGACY_MAGIC.to_le_bytes())?;
+            self.magic_written = true;
+        }
+        if self.block.is_empty() {
+            return Ok(())
+        }
+        let compressed = lz4_flex::block::compress(&self.block);
+        self.writer.write_all(&(compressed.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&compressed)?;
+        self.block.clear();
+        Ok(())
+    }
+
+    /// Compress the last block and hand back the inner writer
+    pub(crate) fn finish(mut self) -> std::io::Result<W> {
+        self.write_block()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for LegacyLz4Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let take = buf.len().min(BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..take]);
+        if self.block.len() == BLOCK_SIZE {
+            self.write_block()?;
+        }
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        // blocks other than the last must be full, so only whole blocks are ever written out
+        self.writer.flush()
+    }
+}
+
+/// Reader for the legacy lz4 format. Like the kernel, a repeated magic in place of a block size
+/// starts the next concatenated stream and the input may end after any block.
+pub(crate) struct LegacyLz4Decoder<R: BufRead> {
+    reader: R,
+    block: Vec<u8>,
+    position: usize,
+}
+
+impl<R: BufRead> LegacyLz4Decoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        LegacyLz4Decoder { reader, block: Vec::new(), position: 0 }
+    }
+
+    /// Decompress the next block, returning false at the end of the input
+    fn next_block(&mut self) -> std::io::Result<bool> {
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        loop {
+            if self.reader.fill_buf()?.is_empty() {
+                return Ok(false)
+            }
+            let mut size = [0u8; 4];
+            self.reader.read_exact(&mut size)?;
+            let size = u32::from_le_bytes(size);
+            if size == LEGACY_MAGIC {
+                continue;
+            }
+            if size as usize > lz4_flex::block::get_maximum_output_size(BLOCK_SIZE) {
+                return Err(invalid("lz4 block larger than the legacy format allows"))
+            }
+
+            let mut compressed = vec![0u8; size as usize];
+            self.reader.read_exact(&mut compressed)?;
+            self.block.resize(BLOCK_SIZE, 0);
+            let len = lz4_flex::block::decompress_into(&compressed, &mut self.block)
+                .map_err(|e| invalid(&format!("invalid lz4 block: {e}")))?;
+            self.block.truncate(len);
+            self.position = 0;
+            return Ok(true)
+        }
+    }
+}
+
+impl<R: BufRead> Read for LegacyLz4Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.block.len() {
+            if !self.next_block()? {
+                return Ok(0)
+            }
+        }
+        let len = buf.len().min(self.block.len() - self.position);
+        buf[..len].copy_from_slice(&self.block[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
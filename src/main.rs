@@ -1,13 +1,16 @@
 use fallible_iterator::FallibleIterator;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use memmap2::Mmap;
 
-use std::path::{PathBuf, Path};
+use std::path::{Path, PathBuf};
 use std::process::exit;
-use std::io::Write;
+use std::io::BufWriter;
 use std::fs::File;
 
-use rcpio::Cpio;
+use rcpio::{
+    Cpio, CpioAppender, DevicePolicy, DuplicatePolicy, ExtractOptions, OwnershipPolicy, Severity, SizePolicy,
+    SymlinkPolicy, UnknownTypePolicy,
+};
 
 type Result<T> = anyhow::Result<T>;
 
@@ -31,92 +34,494 @@ enum Commands {
         #[clap(short='c', long, action)]
         crc: bool,
 
-        /// Compress the archive in gzip format
-        #[clap(short='g', long, action)]
-        gzip: bool
+        /// Use the old portable (odc) format
+        #[clap(long, action, conflicts_with = "crc")]
+        odc: bool,
+
+        /// Compress the archive with the given algorithm
+        #[clap(long, value_enum, default_value = "none")]
+        compress: Compress,
+
+        /// Leave out the '.' entry for the archived directory itself
+        #[clap(long, action)]
+        no_root: bool,
+
+        /// Store names as './path' like 'find . | cpio -o' does
+        #[clap(long, action)]
+        dot_prefix: bool,
+
+        /// Sort entries by name and zero mtimes, owners and device numbers so the same tree
+        /// always gives the same archive
+        #[clap(long, action)]
+        reproducible: bool,
+    },
+    /// Create a cpio archive from a gen_init_cpio style manifest
+    Gen {
+        /// Path to the manifest listing the entries to archive
+        manifest_path: PathBuf,
+
+        /// Output path for created archive
+        output_path: PathBuf,
+
+        /// Use the SVR4 CRC format (default is no CRC)
+        #[clap(short='c', long, action)]
+        crc: bool,
+
+        /// Use the old portable (odc) format
+        #[clap(long, action, conflicts_with = "crc")]
+        odc: bool,
+
+        /// Compress the archive with the given algorithm
+        #[clap(long, value_enum, default_value = "none")]
+        compress: Compress,
+
+        /// Mtime for every entry, as epoch seconds or a UTC YYYY-MM-DD[THH:MM[:SS]] date. Taken
+        /// from KBUILD_BUILD_TIMESTAMP when that is set, otherwise files keep their own mtime
+        /// and everything else gets the current time
+        #[clap(short='t', long, value_parser = parse_timestamp)]
+        timestamp: Option<u32>,
+    },
+    /// Extract a cpio archive to a directory
+    Unar {
+        /// Path to the cpio archive to extract
+        archive_path: PathBuf,
+
+        /// Output path for extracted archive, '-' for stdout with --as-tar
+        output_path: PathBuf,
+
+        /// Write the contents as a tar archive to the output path instead of extracting them,
+        /// the other extraction options don't apply
+        #[clap(long, action)]
+        as_tar: bool,
+
+        /// Check that the output filesystem has room for the archive contents first
+        #[clap(long, action)]
+        check_space: bool,
+
+        /// Only create the layout: directories, symlinks and empty regular files
+        #[clap(long, action)]
+        metadata_only: bool,
+
+        /// Only extract entries below this archive path, rooted at the output directory
+        #[clap(long)]
+        subtree: Option<String>,
+
+        /// Keep the full archive path of subtree entries instead of rebasing them
+        #[clap(long, action, requires = "subtree")]
+        keep_subtree_path: bool,
+
+        /// Print each extracted path and the number of extracted entries to stderr
+        #[clap(short='v', long, action)]
+        verbose: bool,
+
+        /// Whether to restore the uid/gid stored in the archive: only when running as root,
+        /// never, always with a warning for each entry where that isn't permitted, or always,
+        /// failing if that isn't permitted
+        #[clap(long, value_enum, default_value_t = Owners::Auto)]
+        owners: Owners,
+
+        /// Restore the uid/gid stored in the archive, warning about entries where that isn't
+        /// permitted (same as --owners warn)
+        #[clap(long, action, conflicts_with = "owners")]
+        numeric_owner: bool,
+
+        /// Warn about and skip entries of unsupported types instead of failing
+        #[clap(long, action)]
+        skip_unknown: bool,
+
+        /// Drop this many leading components from each entry path, skipping shorter entries
+        #[clap(long, default_value_t = 0)]
+        strip_components: usize,
+
+        /// Leave extracted entries with the current time instead of restoring the mtime stored
+        /// in the archive
+        #[clap(long, action)]
+        no_preserve_mtime: bool,
+
+        /// How to handle device nodes: create them (needs root), skip them, or skip them and
+        /// print a makedevs table for creating them later
+        #[clap(long, value_enum, default_value_t = Devices::Create)]
+        devices: Devices,
+
+        /// How to handle symlinks with a relative target leading outside the output directory
+        #[clap(long, value_enum, default_value_t = Symlinks::Allow)]
+        symlinks: Symlinks,
+
+        /// Replace existing files, symlinks and other non-directories in the output directory
+        #[clap(long, action)]
+        overwrite: bool,
+
+        /// Write regular files and symlinks with this many threads
+        #[clap(short='j', long, default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Merge two cpio archives to a single archive
+    Merge {
+        /// Archive whose entries come first
+        archive_a: PathBuf,
+
+        /// Archive whose entries follow, replacing entries with the same path
+        archive_b: PathBuf,
+
+        /// Output path for the merged archive
+        output: PathBuf,
+
+        /// Fail if both archives contain the same path instead of keeping the second archive's
+        /// entry, directories in both are fine
+        #[clap(long, action)]
+        reject_duplicates: bool,
     },
-    // /// Extract a cpio archive to a directory
-    // Unar {
-    //     /// Path to the directory to archive
-    //     archive_path: PathBuf,
-
-    //     /// Output path for extracted archive
-    //     output_path: PathBuf,
-    // },
-    // /// Merge two cpio archives to a single archive
-    // Merge {
-
-    // },
     /// Extract a single file from a cpio archive
     Cat {
-        /// Path to the directory to archive
+        /// Path to the cpio archive to read
         archive_path: PathBuf,
 
         /// Path to the file to extract
         internal_path: String,
     },
-    // /// Insert a single file into an existing cpio archive
+    /// Insert files into an existing cpio archive
     Push {
-        /// Path to the directory to archive
+        /// Path to the cpio archive to insert into
         archive_path: PathBuf,
 
-        /// Path to the file to extract
-        insert_path: PathBuf,
+        /// Path to the file to insert
+        #[clap(required_unless_present = "add")]
+        insert_path: Option<PathBuf>,
 
-        /// Path to the file to extract
+        /// Path of the file within the archive
+        #[clap(required_unless_present = "add")]
+        internal_path: Option<String>,
+
+        /// Insert the whole directory tree at the insert path, rooted at the internal path
+        #[clap(short='r', long, action, conflicts_with = "add")]
+        recursive: bool,
+
+        /// Also insert the file at INSERT_PATH as INTERNAL_PATH, split at the last ':'. May be
+        /// given multiple times, the archive is only written once
+        #[clap(short='a', long, value_name = "INSERT_PATH:INTERNAL_PATH", value_parser = parse_push_pair)]
+        add: Vec<(PathBuf, String)>,
+    },
+    /// Remove a single entry from an existing cpio archive
+    Rm {
+        /// Path to the cpio archive to modify
+        archive_path: PathBuf,
+
+        /// Path of the entry to remove
         internal_path: String,
     },
-    /// List the files in a cpio archive
+    /// List the files in a cpio archive, including every segment of concatenated archives
     Ls {
         /// Path to the cpio archive to inspect
         archive_path: PathBuf,
+
+        /// Only list entries owned by this uid
+        #[clap(long)]
+        uid: Option<u32>,
+
+        /// Only list entries owned by this gid
+        #[clap(long)]
+        gid: Option<u32>,
+
+        /// Only list regular files with an execute bit set
+        #[clap(long, action)]
+        executables: bool,
+
+        /// Only list entries modified after this time, given as seconds since the epoch or as
+        /// a UTC date `YYYY-MM-DD[THH:MM[:SS]]`
+        #[clap(long, value_parser = parse_timestamp)]
+        newer_than: Option<u32>,
+
+        /// Print the total size of the listed entries
+        #[clap(long, action)]
+        total: bool,
+
+        /// How symlinks count towards --total
+        #[clap(long, value_enum, default_value_t = SymlinkSize::TargetLength)]
+        symlink_size: SymlinkSize,
+    },
+    /// Check a cpio archive for problems, exiting non-zero if any of them are errors
+    Check {
+        /// Path to the cpio archive to check
+        archive_path: PathBuf,
+    },
+    /// Summarize the entry types and sizes in a cpio archive
+    Stats {
+        /// Path to the cpio archive to inspect
+        archive_path: PathBuf,
+
+        /// Also list the N largest entries
+        #[clap(long)]
+        largest: Option<usize>,
+
+        /// How symlinks count towards the reported sizes
+        #[clap(long, value_enum, default_value_t = SymlinkSize::TargetLength)]
+        symlink_size: SymlinkSize,
     },
+    /// Show the content size under each top-level directory of a cpio archive
+    Du {
+        /// Path to the cpio archive to inspect
+        archive_path: PathBuf,
+    },
+}
+
+/// Command line spelling of `DevicePolicy`
+#[derive(Clone, Copy, ValueEnum)]
+enum Devices {
+    Create,
+    Skip,
+    Record,
+}
+
+impl From<Devices> for DevicePolicy {
+    fn from(devices: Devices) -> Self {
+        match devices {
+            Devices::Create => DevicePolicy::Create,
+            Devices::Skip => DevicePolicy::Skip,
+            Devices::Record => DevicePolicy::Record,
+        }
+    }
+}
+
+/// Command line spelling of `SymlinkPolicy`
+#[derive(Clone, Copy, ValueEnum)]
+enum Symlinks {
+    Allow,
+    Warn,
+    Reject,
+}
+
+impl From<Symlinks> for SymlinkPolicy {
+    fn from(symlinks: Symlinks) -> Self {
+        match symlinks {
+            Symlinks::Allow => SymlinkPolicy::AllowAny,
+            Symlinks::Warn => SymlinkPolicy::WarnEscaping,
+            Symlinks::Reject => SymlinkPolicy::RejectEscaping,
+        }
+    }
+}
+
+/// Command line spelling of `OwnershipPolicy`
+#[derive(Clone, Copy, ValueEnum)]
+enum Owners {
+    Auto,
+    Skip,
+    Warn,
+    Strict,
+}
+
+impl From<Owners> for OwnershipPolicy {
+    fn from(owners: Owners) -> Self {
+        match owners {
+            Owners::Auto => OwnershipPolicy::Auto,
+            Owners::Skip => OwnershipPolicy::Skip,
+            Owners::Warn => OwnershipPolicy::Warn,
+            Owners::Strict => OwnershipPolicy::Strict,
+        }
+    }
+}
+
+/// Command line spelling of `SizePolicy`
+#[derive(Clone, Copy, ValueEnum)]
+enum SymlinkSize {
+    /// Length of the target path, as stored in the archive
+    TargetLength,
+
+    /// Symlinks count as zero bytes
+    Zero,
+
+    /// Size of the file the symlink resolves to within the archive
+    Target,
 }
 
-fn collect_files(dir: &PathBuf) -> Vec<PathBuf> {
-    walkdir::WalkDir::new(dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .map(|e| e.into_path())
-        .collect()
+impl From<SymlinkSize> for SizePolicy {
+    fn from(size: SymlinkSize) -> Self {
+        match size {
+            SymlinkSize::TargetLength => SizePolicy::TargetLength,
+            SymlinkSize::Zero => SizePolicy::Zero,
+            SymlinkSize::Target => SizePolicy::Target,
+        }
+    }
+}
+
+/// Command line spelling of `Compression`
+#[derive(Clone, Copy, ValueEnum)]
+enum Compress {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+    Lz4,
+    Bzip2,
+}
+
+impl From<Compress> for rcpio::Compression {
+    fn from(compress: Compress) -> Self {
+        match compress {
+            Compress::None => rcpio::Compression::None,
+            Compress::Gzip => rcpio::Compression::Gzip,
+            Compress::Zstd => rcpio::Compression::Zstd,
+            Compress::Xz => rcpio::Compression::Xz,
+            Compress::Lz4 => rcpio::Compression::Lz4,
+            Compress::Bzip2 => rcpio::Compression::Bzip2,
+        }
+    }
+}
+
+/// Days from 1970-01-01 to the given proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parse a timestamp as epoch seconds or a UTC `YYYY-MM-DD[THH:MM[:SS]]` date into the 32-bit
+/// range cpio mtimes can hold
+fn parse_timestamp(arg: &str) -> std::result::Result<u32, String> {
+    let seconds = if arg.bytes().all(|b| b.is_ascii_digit()) {
+        arg.parse::<i64>().map_err(|e| e.to_string())?
+    } else {
+        let (date, time) = arg.split_once(['T', ' ']).unwrap_or((arg, "00:00"));
+        let field = |s: &str| s.parse::<i64>().map_err(|_| format!("invalid timestamp '{arg}'"));
+
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<&str> = time.split(':').collect();
+        if date.len() != 3 || !(2..=3).contains(&time.len()) {
+            return Err(format!("invalid timestamp '{arg}', expected YYYY-MM-DD[THH:MM[:SS]]"))
+        }
+
+        let (year, month, day) = (field(date[0])?, field(date[1])?, field(date[2])?);
+        let (hour, minute) = (field(time[0])?, field(time[1])?);
+        let second = time.get(2).map(|s| field(s)).transpose()?.unwrap_or(0);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day)
+            || hour > 23 || minute > 59 || second > 60 {
+            return Err(format!("invalid timestamp '{arg}'"))
+        }
+
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+    };
+
+    u32::try_from(seconds).map_err(|_|
+        format!("timestamp '{arg}' is outside the 32-bit range of cpio mtimes")
+    )
+}
+
+/// Parse an `INSERT_PATH:INTERNAL_PATH` pair, split at the last ':' so the insert path may
+/// contain colons
+fn parse_push_pair(arg: &str) -> std::result::Result<(PathBuf, String), String> {
+    match arg.rsplit_once(':') {
+        Some((insert_path, internal_path)) if !insert_path.is_empty() && !internal_path.is_empty() =>
+            Ok((PathBuf::from(insert_path), internal_path.to_string())),
+        _ => Err(format!("expected INSERT_PATH:INTERNAL_PATH, got '{arg}'")),
+    }
+}
+
+/// Map an archive file into memory, with a clear error when the path is a directory
+fn map_archive(archive_path: &Path) -> Result<Mmap> {
+    if archive_path.is_dir() {
+        anyhow::bail!("{} is a directory, not a cpio archive", archive_path.display());
+    }
+    let archive = File::open(archive_path)?;
+    Ok(unsafe { Mmap::map(&archive) }?)
 }
 
 fn main() -> Result<()> {
     let args = CmdArgs::parse();
     match args.commands {
-        Commands::Ar { directory_path, output_path, crc, gzip } => {
+        Commands::Ar {
+            directory_path,
+            output_path,
+            crc,
+            odc,
+            compress,
+            no_root,
+            dot_prefix,
+            reproducible,
+        } => {
             let format = if crc {
                 rcpio::CpioFormat::Crc
+            } else if odc {
+                rcpio::CpioFormat::Odc
             } else {
                 rcpio::CpioFormat::Newc
             };
+            let compression = rcpio::Compression::from(compress);
 
             let mut builder = rcpio::CpioBuilder::new(format);
-
-            let files = collect_files(&directory_path);
-            for file in files {
-                if let Some(file_str) = file.to_str() {
-                    if let Some(directory_path_str) = directory_path.to_str() {
-                        let internal_path = file_str
-                            .trim_start_matches(directory_path_str)
-                            .trim_start_matches('/');
-                        println!("{}", &internal_path);
-                        builder.insert(&file, internal_path)?;
-                    }
-                }
+            builder.include_root(!no_root);
+            builder.gnu_dot_prefix(dot_prefix);
+            builder.reproducible(reproducible);
+            builder.insert_tree(&directory_path, "")?;
+            if reproducible {
+                builder.sort_by(|a, b| a.cmp(b));
             }
-            builder.write(&output_path, gzip)?;
+            for internal_path in builder.archive_paths() {
+                println!("{internal_path}");
+            }
+            builder.write(&output_path, compression)?;
+        },
+        Commands::Gen { manifest_path, output_path, crc, odc, compress, timestamp } => {
+            let format = if crc {
+                rcpio::CpioFormat::Crc
+            } else if odc {
+                rcpio::CpioFormat::Odc
+            } else {
+                rcpio::CpioFormat::Newc
+            };
+
+            let timestamp = match (timestamp, std::env::var("KBUILD_BUILD_TIMESTAMP")) {
+                (Some(timestamp), _) => Some(timestamp),
+                (None, Ok(env)) if !env.is_empty() => match parse_timestamp(&env) {
+                    Ok(timestamp) => Some(timestamp),
+                    Err(e) => {
+                        eprintln!("KBUILD_BUILD_TIMESTAMP: {e}");
+                        exit(2);
+                    },
+                },
+                _ => None,
+            };
+            rcpio::from_manifest(&manifest_path, &output_path, format, compress.into(), timestamp)?;
         },
-        Commands::Ls { archive_path } => {
-            let archive = File::open(archive_path)?;
-            let mmap = &*unsafe { Mmap::map(&archive) }?;
+        Commands::Ls {
+            archive_path,
+            uid,
+            gid,
+            executables,
+            newer_than,
+            total,
+            symlink_size,
+        } => {
+            let archive = Cpio::open(&archive_path)?;
 
-            let cpio = Cpio::load(mmap)?;
+            let cpio = archive.as_cpio();
 
-            let mut iter = cpio.iter_files();
+            // list every segment of concatenated archives, e.g. microcode + initramfs
+            let mut total_size = 0;
+            let mut iter = cpio.iter_all_segments();
             while let Some(file) = iter.next()? {
                 if file.is_trailer()? {
-                    break;
+                    continue;
+                }
+
+                if let Some(uid) = uid {
+                    if file.uid()? != uid as u64 {
+                        continue;
+                    }
+                }
+                if let Some(gid) = gid {
+                    if file.gid()? != gid as u64 {
+                        continue;
+                    }
+                }
+                if executables && !file.is_executable()? {
+                    continue;
+                }
+                if let Some(newer_than) = newer_than {
+                    if file.mtime()? <= newer_than as u64 {
+                        continue;
+                    }
                 }
 
                 if file.is_link()? {
@@ -141,52 +546,206 @@ fn main() -> Result<()> {
                         std::str::from_utf8(file.name()?)?,
                     );
                 }
+                total_size += cpio.logical_size(&file, symlink_size.into())?;
+            }
+            if total {
+                println!("total {total_size}");
             }
         },
-        Commands::Cat { archive_path, internal_path } => {
-            let archive = File::open(archive_path)?;
-            let mmap = &*unsafe { Mmap::map(&archive) }?;
+        Commands::Check { archive_path } => {
+            let archive = Cpio::open(&archive_path)?;
 
-            let cpio = Cpio::load(mmap)?;
+            let cpio = archive.as_cpio();
 
-            let mut iter = cpio.iter_files();
-            while let Some(file) = iter.next()? {
-                if file.is_trailer()? {
-                    break;
-                }
+            let diagnostics = cpio.diagnose();
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
 
-                let name = String::from_utf8(file.name()?.to_vec())?;
-                let trimmed_name = name.trim_end_matches('\0');
+            let entries = cpio.iter_files().filter(|file| Ok(!file.is_trailer()?)).count().unwrap_or(0);
+            let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+            let warnings = diagnostics.len() - errors;
+            println!("{entries} entries, {errors} errors, {warnings} warnings");
+            if errors > 0 {
+                exit(1);
+            }
+        },
+        Commands::Stats { archive_path, largest, symlink_size } => {
+            let archive = Cpio::open(&archive_path)?;
 
-                if trimmed_name != internal_path {
-                    continue;
+            let cpio = archive.as_cpio();
+
+            let stats = cpio.stats(symlink_size.into())?;
+            println!("entries:       {}", stats.entries);
+            println!("regular files: {}", stats.regular_files);
+            println!("directories:   {}", stats.directories);
+            println!("symlinks:      {}", stats.symlinks);
+            println!("devices:       {}", stats.devices);
+            println!("other:         {}", stats.other);
+            println!("total size:    {}", stats.total_size);
+
+            let overhead = cpio.overhead()?;
+            println!();
+            println!("archive size:  {}", overhead.archive_size);
+            println!("  content:     {}", overhead.content_bytes);
+            println!("  headers:     {}", overhead.header_bytes);
+            println!("  names:       {}", overhead.name_bytes);
+            println!("  padding:     {}", overhead.padding_bytes);
+
+            if let Some(count) = largest {
+                println!();
+                for (name, size) in cpio.largest(count, symlink_size.into())? {
+                    println!("{size:>10} {name}");
                 }
+            }
+        },
+        Commands::Du { archive_path } => {
+            let archive = Cpio::open(&archive_path)?;
+
+            let cpio = archive.as_cpio();
+
+            let sizes = cpio.size_by_toplevel()?;
+            for (name, size) in &sizes {
+                println!("{size:>10} {name}");
+            }
+            println!("{:>10} total", sizes.iter().map(|(_, size)| size).sum::<u64>());
+        },
+        Commands::Merge { archive_a, archive_b, output, reject_duplicates } => {
+            let archive_a = Cpio::open(&archive_a)?;
+            let archive_b = Cpio::open(&archive_b)?;
+
+            let cpio_a = archive_a.as_cpio();
+            let cpio_b = archive_b.as_cpio();
+            let duplicates = if reject_duplicates {
+                DuplicatePolicy::Reject
+            } else {
+                DuplicatePolicy::KeepLast
+            };
+            cpio_a.merge(&cpio_b, &output, duplicates)?;
+        },
+        Commands::Cat { archive_path, internal_path } => {
+            let archive = Cpio::open(&archive_path)?;
 
-                if !file.mode_str()?.starts_with('-') {
-                    eprintln!("Cat is only supported for regular files!");
+            let cpio = archive.as_cpio();
+
+            // hardlink members without data print the content of their group
+            match cpio.extract_entry_to(&internal_path, &mut std::io::stdout().lock()) {
+                Err(rcpio::Error::NoSuchFile(_)) => {
+                    eprintln!("No file found in archive for path: '{internal_path}'");
                     exit(1);
-                }
+                },
+                Err(rcpio::Error::FileModeError(_)) => {
+                    eprintln!("Cat is only supported for regular files and symlinks!");
+                    exit(1);
+                },
+                result => result?,
+            }
+        },
+        Commands::Push { archive_path, insert_path, internal_path, recursive, add } => {
+            let pair = insert_path.zip(internal_path);
+            if recursive && pair.is_none() {
+                eprintln!("--recursive needs an insert path and an internal path");
+                exit(1);
+            }
 
-                std::io::stdout().write_all(file.file_content()?)?;
-                return Ok(())
+            // nothing is written unless every file was appended
+            let mut appender = CpioAppender::open(&archive_path)?;
+            let pushed = match pair {
+                Some((insert_path, internal_path)) if recursive =>
+                    appender.append_tree(&insert_path, &internal_path),
+                _ => pair.into_iter().chain(add).try_for_each(|(insert_path, internal_path)|
+                    appender.append(&insert_path, &internal_path)
+                ),
+            };
+
+            match pushed {
+                Ok(()) => appender.finish()?,
+                Err(rcpio::Error::DuplicatePath(path)) => {
+                    drop(appender);
+                    eprintln!("'{path}' is already in the archive");
+                    exit(1);
+                },
+                Err(e) => return Err(e.into()),
             }
-            eprintln!("No file found in archive for path: '{internal_path}'");
-            exit(1);
         },
-        Commands::Push { archive_path, insert_path, internal_path } => {
-            let archive = File::open(&archive_path)?;
-            let mmap = &*unsafe { Mmap::map(&archive) }?;
+        Commands::Rm { archive_path, internal_path } => {
+            let mmap = &*map_archive(&archive_path)?;
 
             let cpio = Cpio::load(mmap)?;
-            cpio.push(&archive_path, &insert_path, &internal_path)?;
+            cpio.remove(&archive_path, &internal_path)?;
         },
-        //Commands::Unar { archive_path, output_path } => {
-        //    let archive = File::open(archive_path)?;
-        //    let mmap = &*unsafe { Mmap::map(&archive) }?;
+        Commands::Unar {
+            archive_path,
+            output_path,
+            check_space,
+            metadata_only,
+            subtree,
+            keep_subtree_path,
+            verbose,
+            owners,
+            numeric_owner,
+            skip_unknown,
+            strip_components,
+            no_preserve_mtime,
+            devices,
+            symlinks,
+            overwrite,
+            jobs,
+            as_tar,
+        } => {
+            let archive = Cpio::open(&archive_path)?;
 
-        //    let cpio = Cpio::load(mmap)?;
-        //    cpio.unarchive(&output_path)?;
-        //},
+            let cpio = archive.as_cpio();
+            if as_tar {
+                if output_path.as_os_str() == "-" {
+                    cpio.write_tar(BufWriter::new(std::io::stdout().lock()))?;
+                } else {
+                    cpio.write_tar(BufWriter::new(File::create(&output_path)?))?;
+                }
+                return Ok(())
+            }
+
+            let options = ExtractOptions {
+                check_space,
+                metadata_only,
+                subtree,
+                keep_subtree_path,
+                ownership_policy: if numeric_owner { OwnershipPolicy::Warn } else { owners.into() },
+                unknown_type_policy: if skip_unknown {
+                    UnknownTypePolicy::SkipWithWarning
+                } else {
+                    UnknownTypePolicy::Error
+                },
+                strip_components,
+                skip_mtime: no_preserve_mtime,
+                device_policy: devices.into(),
+                symlink_policy: symlinks.into(),
+                overwrite,
+            };
+            let report = if jobs > 1 {
+                cpio.unarchive_parallel(&output_path, &options, jobs)?
+            } else {
+                cpio.unarchive_with_options(&output_path, &options)?
+            };
+            for message in &report.ownership_failures {
+                eprintln!("warning: {message}");
+            }
+            for (name, target) in &report.escaping_symlinks {
+                eprintln!("warning: symlink {name} -> {target} escapes the output directory");
+            }
+            for name in &report.skipped {
+                eprintln!("warning: skipped {name}, extraction doesn't support its file type");
+            }
+            if verbose {
+                for entry in &report.entries {
+                    eprintln!("{}", entry.path.display());
+                }
+                eprintln!("{} entries extracted", report.entries.len());
+            }
+            for device in &report.devices {
+                println!("{}", device.makedevs_line());
+            }
+        },
     }
 
     Ok(())
@@ -0,0 +1,131 @@
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{defs, normalize_internal_path, ArchiveSummary, Compression, CpioBuilder, CpioFormat, CpioHeader, Error};
+
+fn manifest_error(line_no: usize, message: &str) -> Error {
+    Error::ManifestError(format!("line {line_no}: {message}"))
+}
+
+/// Parse one numeric manifest field, modes are octal and everything else decimal
+fn number(fields: &[&str], index: usize, radix: u32, line_no: usize) -> Result<u32, Error> {
+    let field = fields.get(index).ok_or_else(||
+        manifest_error(line_no, &format!("'{}' expects at least {} fields", fields[0], index))
+    )?;
+    u32::from_str_radix(field, radix).map_err(|_|
+        manifest_error(line_no, &format!("invalid number '{field}'"))
+    )
+}
+
+/// Build a cpio archive at `output` from a `gen_init_cpio` style manifest. Each line is one of
+///
+/// ```text
+/// file <name> <location> <mode> <uid> <gid> [<hard links>]
+/// dir <name> <mode> <uid> <gid>
+/// nod <name> <mode> <uid> <gid> <dev_type> <maj> <min>
+/// slink <name> <target> <mode> <uid> <gid>
+/// pipe <name> <mode> <uid> <gid>
+/// sock <name> <mode> <uid> <gid>
+/// ```
+///
+/// Blank lines and lines starting with `#` are ignored. Names are stored without their leading
+/// `/`, modes are octal, `<location>` is a file read from disk and `<dev_type>` is `b` or `c`.
+/// Entries are written in manifest order. Like `gen_init_cpio`, every entry gets `mtime` when it
+/// is given, otherwise files keep the mtime of their `<location>` and everything else gets the
+/// current time.
+pub fn from_manifest(
+    manifest_path: &Path,
+    output: &Path,
+    format: CpioFormat,
+    compression: Compression,
+    mtime: Option<u32>
+) -> Result<ArchiveSummary, Error> {
+    let manifest = std::fs::read_to_string(manifest_path).map_err(|e|
+        Error::FileSystemError(format!("Failed to read manifest {}: {e}", manifest_path.display()))
+    )?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as u32);
+
+    let mut builder = CpioBuilder::new(format);
+    let mut ino = 0;
+
+    for (index, line) in manifest.lines().enumerate() {
+        let line_no = index + 1;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() || fields[0].starts_with('#') {
+            continue;
+        }
+
+        let name = fields.get(1).ok_or_else(|| manifest_error(line_no, "missing entry name"))?;
+        let name = normalize_internal_path(name.trim_start_matches('/'))
+            .map_err(|e| manifest_error(line_no, &e.to_string()))?;
+
+        // the position of the mode/uid/gid fields differs between directives
+        let ids_at = match fields[0] {
+            "file" | "slink" => 3,
+            "dir" | "nod" | "pipe" | "sock" => 2,
+            other => return Err(manifest_error(line_no, &format!("unknown directive '{other}'"))),
+        };
+        let perm = number(&fields, ids_at, 8, line_no)? & defs::S_IPERM as u32;
+        let uid = number(&fields, ids_at + 1, 10, line_no)?;
+        let gid = number(&fields, ids_at + 2, 10, line_no)?;
+
+        ino += 1;
+        let mut header = CpioHeader::synthetic(ino, perm);
+        header.c_uid = uid;
+        header.c_gid = gid;
+        header.c_mtime = mtime.unwrap_or(now);
+
+        match fields[0] {
+            "file" => {
+                let location = fields[2];
+                let read_error = |e: std::io::Error|
+                    manifest_error(line_no, &format!("failed to read {location}: {e}"));
+                let content = std::fs::read(location).map_err(read_error)?;
+                if mtime.is_none() {
+                    header.c_mtime = std::fs::metadata(location).map_err(read_error)?.mtime() as u32;
+                }
+
+                // extra names become hardlinks, the data goes with the last member
+                let mut names = vec![name];
+                for link in &fields[6..] {
+                    names.push(normalize_internal_path(link.trim_start_matches('/'))
+                        .map_err(|e| manifest_error(line_no, &e.to_string()))?);
+                }
+
+                header.c_mode |= defs::S_IFREG as u32;
+                header.c_nlink = names.len() as u32;
+                let last = names.len() - 1;
+                for (member, name) in names.iter().enumerate() {
+                    let data = if member == last { content.clone() } else { vec![] };
+                    builder.insert_header(name, header.clone(), data)?;
+                }
+                continue;
+            },
+            "dir" => {
+                header.c_mode |= defs::S_IFDIR as u32;
+                header.c_nlink = 2;
+            },
+            "nod" => {
+                header.c_mode |= match fields.get(5).copied() {
+                    Some("b") => defs::S_IFBLK as u32,
+                    Some("c") => defs::S_IFCHR as u32,
+                    _ => return Err(manifest_error(line_no, "device type must be 'b' or 'c'")),
+                };
+                header.c_rdevmajor = number(&fields, 6, 10, line_no)?;
+                header.c_rdevminor = number(&fields, 7, 10, line_no)?;
+            },
+            "slink" => {
+                header.c_mode |= defs::S_IFLNK as u32;
+                builder.insert_header(&name, header, fields[2].as_bytes().to_vec())?;
+                continue;
+            },
+            "pipe" => header.c_mode |= defs::S_IFIFO as u32,
+            "sock" => header.c_mode |= defs::S_IFSOCK as u32,
+            _ => unreachable!(),
+        }
+        builder.insert_header(&name, header, vec![])?;
+    }
+
+    builder.write(output, compression)
+}
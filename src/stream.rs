@@ -0,0 +1,131 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::defs::{CPIO_MAGIC_LEN, PATH_MAX};
+use crate::{identify_format, Compression, CpioEntry, CpioEntryInfo, Error};
+
+/// Initial buffer size for reads whose length comes from an entry header
+const READ_CHUNK: usize = 64 * 1024;
+
+fn read_error(e: std::io::Error) -> Error {
+    match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => Error::EarlyEOFError,
+        _ => Error::FileSystemError(format!("Failed to read archive stream: {e}")),
+    }
+}
+
+/// Sequential reader over an archive coming from any `Read`, e.g. a decompressor. Only the
+/// current entry's header is held in memory, content is read on request or skipped when moving
+/// on to the next entry.
+pub struct CpioStream<R: Read> {
+    reader: R,
+
+    /// Offset into the (uncompressed) archive stream
+    offset: usize,
+
+    /// Content of the current entry not yet read
+    content_left: usize,
+
+    /// Alignment padding following the current entry's content
+    padding_left: usize,
+
+    /// Trailer was encountered
+    trailer_seen: bool,
+}
+
+impl<R: Read> CpioStream<R> {
+    pub fn new(reader: R) -> Self {
+        CpioStream { reader, offset: 0, content_left: 0, padding_left: 0, trailer_seen: false }
+    }
+
+    /// Read `len` bytes, the buffer grows with the data actually read so a bogus length from a
+    /// header can't allocate more than the stream holds
+    fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::with_capacity(len.min(READ_CHUNK));
+        (&mut self.reader).take(len as u64).read_to_end(&mut buf).map_err(read_error)?;
+        if buf.len() != len {
+            return Err(Error::EarlyEOFError)
+        }
+        self.offset += len;
+        Ok(buf)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len as u64), &mut std::io::sink())
+            .map_err(read_error)?;
+        if skipped != len as u64 {
+            return Err(Error::EarlyEOFError)
+        }
+        self.offset += len;
+        Ok(())
+    }
+
+    /// Advance to the next entry, skipping whatever is left of the current one. Returns `None`
+    /// once the trailer is reached, the trailer itself is not returned.
+    pub fn next_entry(&mut self) -> Result<Option<CpioEntryInfo>, Error> {
+        if self.trailer_seen {
+            return Ok(None)
+        }
+        self.skip(self.content_left + self.padding_left)?;
+        self.content_left = 0;
+        self.padding_left = 0;
+
+        let start = self.offset;
+        let mut buf = self.read_exact(CPIO_MAGIC_LEN)?;
+        let format = identify_format(&buf).map_err(|_|
+            Error::InvalidArchiveError(format!("Invalid magic encountered at offset {start:#x}"))
+        )?;
+        buf.append(&mut self.read_exact(format.header_len() - CPIO_MAGIC_LEN)?);
+
+        // entries start aligned, so offsets relative to a buffer holding just this entry give
+        // the same alignment as the absolute ones
+        let namesize = CpioEntry::new(0, format, &buf)?.namesize()?;
+        if namesize > PATH_MAX {
+            return Err(Error::InvalidArchiveError(
+                format!("Name size {namesize} at offset {start:#x} exceeds PATH_MAX")
+            ))
+        }
+        buf.append(&mut self.read_exact(namesize)?);
+        let entry = CpioEntry::new(0, format, &buf)?;
+
+        // a symlink's content is its target, which callers read whole
+        if entry.is_link()? && entry.filesize()? > PATH_MAX {
+            return Err(Error::InvalidArchiveError(
+                format!("Symlink target size {} at offset {start:#x} exceeds PATH_MAX", entry.filesize()?)
+            ))
+        }
+
+        self.skip(entry.file_content_offset()? - buf.len())?;
+        if entry.is_trailer()? {
+            self.trailer_seen = true;
+            return Ok(None)
+        }
+
+        let mut info = entry.info()?;
+        info.index = start;
+        self.content_left = info.filesize;
+        self.padding_left = entry.next()? - entry.file_content_offset()? - info.filesize;
+        Ok(Some(info))
+    }
+
+    /// Read the remaining content of the entry last returned by `next_entry`
+    pub fn read_content(&mut self) -> Result<Vec<u8>, Error> {
+        let content = self.read_exact(self.content_left)?;
+        self.content_left = 0;
+        Ok(content)
+    }
+}
+
+/// Open an archive file for streaming, transparently decompressing it if it starts with a
+/// compression magic
+pub(crate) fn open_compressed(path: &Path) -> Result<CpioStream<Box<dyn Read>>, Error> {
+    let fp = File::open(path).map_err(|e|
+        Error::FileSystemError(format!("Failed to open archive {}: {e}", path.display()))
+    )?;
+
+    let mut fp = BufReader::new(fp);
+    let compression = Compression::detect(fp.fill_buf().map_err(read_error)?);
+    let reader = compression.decoder(fp)?;
+    Ok(CpioStream::new(reader))
+}
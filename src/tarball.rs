@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use fallible_iterator::FallibleIterator;
+use flate2::read::GzDecoder;
+use tar::EntryType;
+
+use crate::{defs, normalize_internal_path, Compression, Cpio, CpioBuilder, CpioHeader, CpioFormat, Error};
+
+fn tar_error(e: std::io::Error) -> Error {
+    Error::TarError(e.to_string())
+}
+
+/// Open a tarball, transparently decompressing it if it starts with the gzip magic
+fn open_tar(tar_path: &Path) -> Result<tar::Archive<Box<dyn Read>>, Error> {
+    let mut fp = File::open(tar_path).map_err(|_|
+        Error::FileSystemError(format!("Failed to open tar archive {}", tar_path.to_string_lossy()))
+    )?;
+
+    let mut magic = [0u8; 2];
+    let gzipped = fp.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+    fp.seek(SeekFrom::Start(0)).map_err(tar_error)?;
+
+    let reader: Box<dyn Read> = if gzipped {
+        Box::new(GzDecoder::new(BufReader::new(fp)))
+    } else {
+        Box::new(BufReader::new(fp))
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+/// Transcode the tarball at `tar_path` (optionally gzip compressed) into a cpio archive at
+/// `output`. Entry types, modes, owners, mtimes and symlink targets are carried over, tar
+/// hardlinks become cpio hardlink groups sharing an inode with the data in the last member.
+pub fn from_tar(
+    tar_path: &Path,
+    output: &Path,
+    format: CpioFormat,
+    compression: Compression
+) -> Result<(), Error> {
+    let mut archive = open_tar(tar_path)?;
+
+    let mut members: Vec<(String, CpioHeader, Vec<u8>)> = vec![];
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+
+    for entry in archive.entries().map_err(tar_error)? {
+        let mut entry = entry.map_err(tar_error)?;
+        let path = entry.path().map_err(tar_error)?;
+        let name = normalize_internal_path(&path.to_string_lossy())?;
+
+        let tar_header = entry.header().clone();
+        let perm = tar_header.mode().map_err(tar_error)? & defs::S_IPERM as u32;
+
+        let mut header = CpioHeader::synthetic(members.len() as u32 + 1, perm);
+        header.c_uid = tar_header.uid().map_err(tar_error)? as u32;
+        header.c_gid = tar_header.gid().map_err(tar_error)? as u32;
+        header.c_mtime = tar_header.mtime().map_err(tar_error)? as u32;
+
+        let mut content = vec![];
+        let file_type = match tar_header.entry_type() {
+            EntryType::Regular | EntryType::Continuous => {
+                entry.read_to_end(&mut content).map_err(tar_error)?;
+                defs::S_IFREG
+            },
+            EntryType::Directory => {
+                header.c_nlink = 2;
+                defs::S_IFDIR
+            },
+            EntryType::Symlink => {
+                let target = entry.link_name().map_err(tar_error)?.ok_or_else(||
+                    Error::TarError(format!("Symlink '{name}' has no target"))
+                )?;
+                content.extend_from_slice(target.to_string_lossy().as_bytes());
+                defs::S_IFLNK
+            },
+            EntryType::Link => {
+                let target = entry.link_name().map_err(tar_error)?.ok_or_else(||
+                    Error::TarError(format!("Hardlink '{name}' has no target"))
+                )?;
+                let target = normalize_internal_path(&target.to_string_lossy())?;
+                let target_index = *by_name.get(&target).ok_or_else(||
+                    Error::TarError(format!("Hardlink '{name}' targets missing entry '{target}'"))
+                )?;
+
+                // share the target's inode, the data is moved to the last member below
+                let target_header = &members[target_index].1;
+                header.c_ino = target_header.c_ino;
+                header.c_mode = target_header.c_mode;
+                by_name.insert(name.clone(), members.len());
+                members.push((name, header, content));
+                continue;
+            },
+            EntryType::Char | EntryType::Block => {
+                header.c_rdevmajor = tar_header.device_major().map_err(tar_error)?.unwrap_or(0);
+                header.c_rdevminor = tar_header.device_minor().map_err(tar_error)?.unwrap_or(0);
+                if tar_header.entry_type() == EntryType::Char {
+                    defs::S_IFCHR
+                } else {
+                    defs::S_IFBLK
+                }
+            },
+            EntryType::Fifo => defs::S_IFIFO,
+            EntryType::XGlobalHeader => continue,
+            other => {
+                return Err(Error::TarError(format!("Unsupported tar entry type {other:?} for '{name}'")))
+            },
+        };
+
+        header.c_mode |= file_type as u32;
+        by_name.insert(name.clone(), members.len());
+        members.push((name, header, content));
+    }
+
+    // hardlink groups share an inode, newc/crc store the data only in the last member
+    let mut groups: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, (_, header, _)) in members.iter().enumerate() {
+        if header.c_mode as u64 & defs::S_IFMT == defs::S_IFREG {
+            groups.entry(header.c_ino).or_default().push(index);
+        }
+    }
+    for group in groups.values().filter(|group| group.len() > 1) {
+        let mut content = vec![];
+        for index in group {
+            let member = &mut members[*index];
+            member.1.c_nlink = group.len() as u32;
+            if content.is_empty() {
+                content = std::mem::take(&mut member.2);
+            }
+        }
+        if let Some(last) = group.last() {
+            members[*last].2 = content;
+        }
+    }
+
+    let mut builder = CpioBuilder::new(format);
+    for (name, header, content) in members {
+        builder.insert_header(&name, header, content)?;
+    }
+    builder.write(output, compression)?;
+    Ok(())
+}
+
+impl Cpio<'_> {
+    /// Transcode the archive into a tar stream written to `writer`, e.g. stdout for piping into
+    /// `docker import -`. Directories, regular files, symlinks, devices and FIFOs are carried
+    /// over with their mode, owner and mtime. The first member of a hardlink group carries the
+    /// data and the others become tar hardlinks to it. Sockets can't be stored in tar and are
+    /// an error.
+    pub fn write_tar<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let mut builder = tar::Builder::new(writer);
+        let hardlinks = self.hardlinks()?;
+        let mut linked: HashMap<(u64, u64, u64), String> = HashMap::new();
+
+        let mut iter = self.iter_files();
+        while let Some(file) = iter.next()? {
+            if file.is_trailer()? {
+                break;
+            }
+            let name = normalize_internal_path(&file.name_string()?)?;
+
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(file.permissions()?);
+            header.set_uid(file.uid()?);
+            header.set_gid(file.gid()?);
+            header.set_mtime(file.mtime()?);
+            header.set_size(0);
+
+            if file.is_reg()? && file.nlink()? > 1 {
+                let inode = (file.inode()?, file.devmajor()?, file.devminor()?);
+                if let Some(target) = linked.get(&inode) {
+                    header.set_entry_type(EntryType::Link);
+                    builder.append_link(&mut header, &name, target).map_err(tar_error)?;
+                    continue;
+                }
+                linked.insert(inode, name.clone());
+            }
+
+            let mut content: &[u8] = &[];
+            if file.is_reg()? {
+                content = hardlinks.content(&file)?;
+                header.set_entry_type(EntryType::Regular);
+                header.set_size(content.len() as u64);
+            } else if file.is_dir()? {
+                header.set_entry_type(EntryType::Directory);
+            } else if file.is_link()? {
+                let target = String::from_utf8_lossy(file.file_content()?);
+                header.set_entry_type(EntryType::Symlink);
+                builder.append_link(&mut header, &name, target.as_ref()).map_err(tar_error)?;
+                continue;
+            } else if file.is_chr()? || file.is_blk()? {
+                header.set_entry_type(if file.is_chr()? { EntryType::Char } else { EntryType::Block });
+                header.set_device_major(file.rdevmajor()? as u32).map_err(tar_error)?;
+                header.set_device_minor(file.rdevminor()? as u32).map_err(tar_error)?;
+            } else if file.is_fifo()? {
+                header.set_entry_type(EntryType::Fifo);
+            } else {
+                return Err(Error::TarError(
+                    format!("{:o} can't be stored in a tar archive: {name}", file.mode()?)
+                ))
+            }
+            builder.append_data(&mut header, &name, content).map_err(tar_error)?;
+        }
+
+        builder.into_inner().map_err(tar_error)?.flush().map_err(tar_error)
+    }
+}
@@ -0,0 +1,488 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use fallible_iterator::FallibleIterator;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat};
+
+#[test]
+fn archiving_a_fifo_does_not_block() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(src.join("file"), b"data").unwrap();
+    let fifo = CString::new(src.join("fifo").as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+
+    // opening the fifo would wait for a writer forever, give up on the archive instead
+    let archive = dir.path().join("a.cpio");
+    let (sender, receiver) = mpsc::channel();
+    let output = archive.clone();
+    std::thread::spawn(move || {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.insert(&src.join("fifo"), "fifo").unwrap();
+        builder.insert(&src.join("file"), "file").unwrap();
+        builder.write(&output, rcpio::Compression::None).unwrap();
+        sender.send(()).unwrap();
+    });
+    receiver.recv_timeout(Duration::from_secs(10)).expect("archiving blocked on the fifo");
+
+    let mem = std::fs::read(&archive).unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+    let entries: Vec<_> = cpio.iter_files().collect().unwrap();
+    assert_eq!(entries[0].name().unwrap(), b"fifo\0");
+    assert!(entries[0].is_fifo().unwrap());
+    assert_eq!(entries[0].filesize().unwrap(), 0);
+    assert_eq!(entries[1].name().unwrap(), b"file\0");
+    assert_eq!(entries[1].file_content().unwrap(), b"data");
+}
+
+#[test]
+fn in_memory_archives_round_trip() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/hostname", b"rcpio\n", 0o644).unwrap();
+    builder.insert_symlink("hostname", "etc/hostname").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let cpio = Cpio::load(&mem).unwrap();
+    let entries: Vec<_> = cpio.iter_files().collect().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].name_string().unwrap(), "etc");
+    assert!(entries[0].is_dir().unwrap());
+    assert_eq!(entries[0].mode().unwrap() & 0o7777, 0o755);
+    assert_eq!(entries[1].name_string().unwrap(), "etc/hostname");
+    assert!(entries[1].is_reg().unwrap());
+    assert_eq!(entries[1].mode().unwrap() & 0o7777, 0o644);
+    assert_eq!(entries[1].file_content().unwrap(), b"rcpio\n");
+    assert_eq!(entries[2].name_string().unwrap(), "hostname");
+    assert!(entries[2].is_link().unwrap());
+    assert_eq!(entries[2].file_content().unwrap(), b"etc/hostname");
+    assert!(entries[3].is_trailer().unwrap());
+
+    // writing to a file gives the same bytes
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    builder.write(&path, rcpio::Compression::None).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), mem);
+}
+
+#[test]
+fn block_size_pads_after_the_trailer() {
+    let dir = tempfile::tempdir().unwrap();
+    for (block_size, multiple) in [(Some(512), 512), (Some(1024), 1024), (None, 1)] {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.insert_data("init", &[0x7f; 700], 0o755).unwrap();
+        builder.block_size(block_size);
+
+        let path = dir.path().join("a.cpio");
+        builder.write(&path, rcpio::Compression::None).unwrap();
+        let mem = std::fs::read(&path).unwrap();
+        assert_eq!(mem, builder.to_vec().unwrap());
+        assert_eq!(mem.len() % multiple, 0, "{block_size:?}");
+
+        // the trailer is the last entry and only zeros follow it
+        let cpio = Cpio::load(&mem).unwrap();
+        let trailer = cpio.iter_files().last().unwrap().unwrap();
+        assert!(trailer.is_trailer().unwrap());
+        let trailer_end = trailer.index + 110 + 11;
+        assert_eq!(&mem[trailer.index + 110..trailer_end], b"TRAILER!!!\0");
+        assert!(mem[trailer_end..].iter().all(|b| *b == 0));
+        assert!(mem.len() - trailer_end < multiple, "{block_size:?}");
+    }
+}
+
+#[test]
+fn writes_replace_the_archive_whole_or_not_at_all() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, b"previous archive").unwrap();
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    builder.write(&path, rcpio::Compression::None).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), builder.to_vec().unwrap());
+
+    // renaming over a non-empty directory fails after the data was written
+    let occupied = dir.path().join("occupied");
+    std::fs::create_dir(&occupied).unwrap();
+    std::fs::write(occupied.join("keep"), b"keep").unwrap();
+    assert!(builder.write(&occupied, rcpio::Compression::Gzip).is_err());
+    assert_eq!(std::fs::read(occupied.join("keep")).unwrap(), b"keep");
+
+    // no temporary file is left behind either way
+    let mut names: Vec<_> = std::fs::read_dir(dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.cpio", "occupied"]);
+}
+
+#[test]
+fn precomputed_checksums_are_written_as_given() {
+    let content = b"#!/bin/sh\n";
+    assert_eq!(rcpio::checksum(b"abc"), 97 + 98 + 99);
+
+    // newc archives always carry a zero check
+    for (format, expected) in [(CpioFormat::Crc, rcpio::checksum(content)), (CpioFormat::Newc, 0)] {
+        let mut computed = CpioBuilder::new(format);
+        computed.insert_data("init", content, 0o755).unwrap();
+        let mut precomputed = CpioBuilder::new(format);
+        precomputed.insert_data_with_checksum("init", content, 0o755, rcpio::checksum(content)).unwrap();
+
+        let mem = precomputed.to_vec().unwrap();
+        assert_eq!(mem, computed.to_vec().unwrap());
+        let check = Cpio::load(&mem).unwrap().iter_files().next().unwrap().unwrap().checksum().unwrap();
+        assert_eq!(check, expected as u64);
+    }
+}
+
+#[test]
+fn aligned_archives_get_no_extra_block() {
+    for format in [CpioFormat::Newc, CpioFormat::Crc] {
+        for size in 0..1100 {
+            let mut builder = CpioBuilder::new(format);
+            builder.insert_data("data", &vec![0xaa; size], 0o644).unwrap();
+            let padded = builder.to_vec().unwrap();
+            let unpadded = builder.block_size(None).to_vec().unwrap();
+            assert_eq!(padded.len(), unpadded.len().next_multiple_of(512), "{size}");
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src/data"), [0xaa; 700]).unwrap();
+    let output = dir.path().join("a.cpio");
+    let result = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("ar").arg(dir.path().join("src")).arg(&output)
+        .output().unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(std::fs::metadata(&output).unwrap().len() % 512, 0);
+}
+
+#[test]
+fn overlapping_trees_are_merged_last_wins() {
+    let dir = tempfile::tempdir().unwrap();
+    let (base, overlay) = (dir.path().join("base"), dir.path().join("overlay"));
+    for (root, files) in [
+        (&base, &[("etc/hostname", "base"), ("etc/passwd", "root"), ("bin/sh", "sh")][..]),
+        (&overlay, &[("etc/hostname", "overlay"), ("etc/motd", "hello")][..]),
+    ] {
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+    }
+    let config = dir.path().join("config");
+    std::fs::create_dir(&config).unwrap();
+    std::fs::write(config.join("app.conf"), "key=value").unwrap();
+
+    let output = dir.path().join("out.cpio");
+    let summary = rcpio::archive_trees(
+        &[(base, String::new()), (overlay, String::new()), (config, String::from("etc/app"))],
+        &output,
+        CpioFormat::Newc,
+        rcpio::Compression::None,
+    ).unwrap();
+    // the directories both trees have are no conflict
+    assert_eq!(summary.overridden, ["etc/hostname"]);
+
+    let mem = std::fs::read(&output).unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+    let contents: std::collections::HashMap<String, Vec<u8>> = cpio.iter_files()
+        .map(|entry| Ok((entry.name_string()?, entry.file_content()?.to_vec())))
+        .collect().unwrap();
+    assert_eq!(contents["etc/hostname"], b"overlay");
+    assert_eq!(contents["etc/passwd"], b"root");
+    assert_eq!(contents["etc/motd"], b"hello");
+    assert_eq!(contents["etc/app/app.conf"], b"key=value");
+
+    // every path once, each after its parent directory
+    let names: Vec<String> = cpio.iter_files().map(|entry| entry.name_string()).collect().unwrap();
+    let mut seen = std::collections::HashSet::new();
+    for name in names.iter().filter(|name| *name != "TRAILER!!!") {
+        assert!(seen.insert(name.as_str()), "{name} archived twice");
+        if let Some((parent, _)) = name.rsplit_once('/') {
+            assert!(seen.contains(parent), "{name} comes before {parent}");
+        }
+    }
+}
+
+#[test]
+fn sort_by_keeps_directories_ahead_of_their_contents() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/passwd", b"root", 0o644).unwrap();
+    builder.insert_dir("etc/init.d", 0o755).unwrap();
+    builder.insert_data("etc/init.d/rcS", b"#!/bin/sh", 0o755).unwrap();
+    builder.insert_data("init", b"#!/bin/sh", 0o755).unwrap();
+
+    // reverse order by name would put every directory after its contents
+    builder.sort_by(|a, b| b.cmp(a));
+    let mem = builder.to_vec().unwrap();
+
+    let names: Vec<String> = Cpio::load(&mem).unwrap().iter_files()
+        .map(|entry| entry.name_string())
+        .filter(|name| Ok(name != "TRAILER!!!"))
+        .collect()
+        .unwrap();
+    assert_eq!(names, ["init", "etc", "etc/passwd", "etc/init.d", "etc/init.d/rcS"]);
+}
+
+#[test]
+fn root_entry_is_optional() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir_all(src.join("etc/init.d")).unwrap();
+    std::fs::write(src.join("etc/init.d/rcS"), b"#!/bin/sh\n").unwrap();
+
+    for include_root in [true, false] {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.include_root(include_root);
+        builder.insert_tree(&src, "").unwrap();
+        let mem = builder.to_vec().unwrap();
+
+        let cpio = Cpio::load(&mem).unwrap();
+        let first = cpio.iter_files().next().unwrap().unwrap().name_string().unwrap();
+        assert_eq!(first == ".", include_root);
+        assert_eq!(cpio.iter_files().any(|entry| Ok(entry.name_string()? == ".")).unwrap(), include_root);
+
+        let output = dir.path().join(format!("out-{include_root}"));
+        cpio.unarchive(&output).unwrap();
+        assert_eq!(std::fs::read(output.join("etc/init.d/rcS")).unwrap(), b"#!/bin/sh\n");
+    }
+}
+
+#[test]
+fn archive_summary_counts_what_was_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(root.join("etc/hostname"), b"rcpio\n").unwrap();
+    std::os::unix::fs::symlink("etc/hostname", root.join("hostname")).unwrap();
+
+    let output = dir.path().join("out.cpio");
+    let summary = rcpio::archive_directory(&root, &output, CpioFormat::Newc, rcpio::Compression::None).unwrap();
+    assert_eq!(summary.archive_size, std::fs::metadata(&output).unwrap().len());
+    assert_eq!(summary.stats.regular_files, 1);
+    assert_eq!(summary.stats.symlinks, 1);
+    assert_eq!(summary.stats.total_size, "rcpio\n".len() as u64 + "etc/hostname".len() as u64);
+    assert!(summary.overridden.is_empty());
+
+    let mem = std::fs::read(&output).unwrap();
+    let entries = Cpio::load(&mem).unwrap().iter_files().filter(|entry| Ok(!entry.is_trailer()?)).count().unwrap();
+    assert_eq!(summary.stats.entries, entries);
+    assert_eq!(summary.stats.directories, entries - 2);
+}
+
+#[test]
+fn buffer_size_does_not_change_the_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&src, &content).unwrap();
+
+    let write = |size: Option<usize>, compression: rcpio::Compression| -> Vec<u8> {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        if let Some(size) = size {
+            builder.read_buffer_size(size);
+        }
+        builder.insert(&src, "src").unwrap();
+        let path = dir.path().join("a.cpio");
+        builder.write(&path, compression).unwrap();
+        std::fs::read(path).unwrap()
+    };
+
+    let expected = write(None, rcpio::Compression::None);
+    let cpio = Cpio::load(&expected).unwrap();
+    assert_eq!(cpio.iter_files().next().unwrap().unwrap().file_content().unwrap(), content);
+
+    for size in [0, 1, 7, 4096, 1 << 20] {
+        assert_eq!(write(Some(size), rcpio::Compression::None), expected, "{size}");
+
+        let gzipped = write(Some(size), rcpio::Compression::Gzip);
+        let mut inflated = vec![];
+        std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&gzipped[..]), &mut inflated).unwrap();
+        assert_eq!(inflated, expected, "{size}");
+    }
+}
+
+#[test]
+fn sequential_inodes_are_deterministic() {
+    let inodes = || {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path();
+        std::fs::create_dir(src.join("d")).unwrap();
+        std::fs::write(src.join("d/a"), b"linked").unwrap();
+        std::fs::hard_link(src.join("d/a"), src.join("d/b")).unwrap();
+        std::fs::write(src.join("d/c"), b"plain").unwrap();
+
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.sequential_inodes(true);
+        for name in ["d", "d/a", "d/b", "d/c"] {
+            builder.insert(&src.join(name), name).unwrap();
+        }
+        let mem = builder.to_vec().unwrap();
+        Cpio::load(&mem).unwrap().iter_files()
+            .filter(|entry| Ok(!entry.is_trailer()?))
+            .map(|entry| entry.inode())
+            .collect::<Vec<_>>().unwrap()
+    };
+
+    // hardlinked files keep sharing one number
+    assert_eq!(inodes(), [1, 2, 2, 3]);
+    assert_eq!(inodes(), inodes());
+}
+
+#[test]
+fn reproducible_archives_ignore_mtimes_and_inodes() {
+    let archive = |mtime: u32| {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path();
+        std::fs::write(src.join("b"), b"linked").unwrap();
+        std::fs::hard_link(src.join("b"), src.join("a")).unwrap();
+        std::fs::write(src.join("c"), b"plain").unwrap();
+        for name in ["a", "c"] {
+            let time = std::time::UNIX_EPOCH + Duration::from_secs(mtime.into());
+            std::fs::File::options().write(true).open(src.join(name)).unwrap()
+                .set_modified(time).unwrap();
+        }
+
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.reproducible(true);
+        for name in ["a", "b", "c"] {
+            builder.insert(&src.join(name), name).unwrap();
+        }
+        let mem = builder.to_vec().unwrap();
+
+        let output = dir.path().join("cli.cpio");
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+            .arg("ar").arg("--reproducible").arg(src).arg(&output)
+            .output().unwrap();
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+        (mem, std::fs::read(output).unwrap())
+    };
+
+    let (first, first_cli) = archive(1_000_000_000);
+    let (second, second_cli) = archive(2_000_000_000);
+    assert_eq!(first, second);
+    assert_eq!(first_cli, second_cli);
+
+    let cpio = Cpio::load(&first).unwrap();
+    let entries = cpio.iter_files()
+        .filter(|entry| Ok(!entry.is_trailer()?))
+        .map(|entry| Ok((entry.inode()?, entry.mtime()?)))
+        .collect::<Vec<_>>().unwrap();
+    assert_eq!(entries, [(1, 0), (1, 0), (2, 0)]);
+}
+
+#[test]
+fn dot_prefix_matches_find_piped_to_cpio() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    std::fs::create_dir_all(root.join("etc/init.d")).unwrap();
+    std::fs::write(root.join("etc/init.d/rcS"), b"#!/bin/sh\n").unwrap();
+    std::fs::write(root.join("init"), b"#!/bin/sh\n").unwrap();
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.gnu_dot_prefix(true);
+    builder.insert_tree(&root, "").unwrap();
+    let mem = builder.to_vec().unwrap();
+    let mut names: Vec<String> = Cpio::load(&mem).unwrap().iter_files()
+        .filter(|entry| Ok(!entry.is_trailer()?))
+        .map(|entry| entry.name_string())
+        .collect().unwrap();
+    names.sort();
+    assert_eq!(names, [".", "./etc", "./etc/init.d", "./etc/init.d/rcS", "./init"]);
+
+    // compare against the GNU invocation itself where cpio is installed
+    let gnu = std::process::Command::new("sh")
+        .arg("-c").arg("find . | cpio -o -H newc 2>/dev/null")
+        .current_dir(&root)
+        .output().unwrap();
+    if !gnu.status.success() || gnu.stdout.is_empty() {
+        return
+    }
+    let mut gnu_names: Vec<String> = Cpio::load(&gnu.stdout).unwrap().iter_files()
+        .filter(|entry| Ok(!entry.is_trailer()?))
+        .map(|entry| entry.name_string())
+        .collect().unwrap();
+    gnu_names.sort();
+    assert_eq!(names, gnu_names);
+}
+
+#[test]
+fn directory_output_paths_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    std::fs::create_dir(&out).unwrap();
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("file", b"data", 0o644).unwrap();
+    let result = builder.write(&out, rcpio::Compression::None);
+    assert!(matches!(result, Err(rcpio::Error::OutputIsDirectoryError(_))), "{result:?}");
+    assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1, "temporary file left behind");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("ar").arg(&out).arg(&out)
+        .output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("is a directory, specify a file"), "{stderr}");
+
+    // and as the archive to read
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("ls").arg(&out)
+        .output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("is a directory, not a cpio archive"), "{stderr}");
+}
+
+#[test]
+fn estimated_compressed_size_matches_the_written_archive() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for i in 0..200 {
+        let content: Vec<u8> = (0..i * 37).map(|b| (b % 13) as u8).collect();
+        builder.insert_data(&format!("dir/file{i}"), &content, 0o644).unwrap();
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    for compression in [rcpio::Compression::None, rcpio::Compression::Gzip] {
+        let estimate = builder.estimated_compressed_size(compression).unwrap();
+        builder.write(&path, compression).unwrap();
+        assert_eq!(estimate, std::fs::metadata(&path).unwrap().len(), "{compression:?}");
+    }
+}
+
+#[test]
+fn write_to_streams_files_in_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large");
+    let content: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &content).unwrap();
+
+    for compression in [rcpio::Compression::None, rcpio::Compression::Gzip] {
+        let mut builder = CpioBuilder::new(CpioFormat::Crc);
+        // smaller than the file and not dividing it, so the content is read in many chunks
+        builder.read_buffer_size(4093);
+        builder.insert(&path, "large").unwrap();
+        builder.insert_data("small", b"small", 0o644).unwrap();
+
+        let mut written = vec![];
+        let stats = builder.write_to(&mut written, compression).unwrap();
+        assert_eq!((stats.entries, stats.regular_files), (2, 2));
+        assert_eq!(stats.total_size, content.len() as u64 + 5);
+
+        let mut buf = vec![];
+        let cpio = Cpio::load_compressed(written.as_slice(), &mut buf).unwrap();
+        assert_eq!(cpio.read_file("large").unwrap(), content);
+        assert!(cpio.verify().unwrap().is_empty());
+        assert_eq!(buf, builder.to_vec().unwrap());
+    }
+}
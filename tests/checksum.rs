@@ -0,0 +1,54 @@
+use rcpio::{checksum, Cpio, CpioBuilder, CpioFormat};
+
+#[test]
+fn checksum_wraps_past_u32() {
+    // 17 MiB of 0xff sums to more than u32::MAX
+    let content = vec![0xffu8; 17 << 20];
+    let sum = content.len() as u64 * 0xff;
+    assert!(sum > u32::MAX as u64);
+    assert_eq!(checksum(&content) as u64, sum % (1 << 32));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("big");
+    std::fs::write(&path, &content).unwrap();
+
+    // content held in memory and content streamed from a file take different paths
+    let mut builder = CpioBuilder::new(CpioFormat::Crc);
+    builder.insert_data("memory", &content, 0o644).unwrap();
+    builder.insert(&path, "file").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let cpio = Cpio::load(&mem).unwrap();
+    for name in ["memory", "file"] {
+        let entry = cpio.find(name).unwrap().unwrap();
+        assert_eq!(entry.checksum().unwrap(), sum % (1 << 32), "{name}");
+        assert!(entry.verify_checksum().unwrap(), "{name}");
+    }
+    assert!(cpio.verify().unwrap().is_empty());
+}
+
+#[test]
+fn checksum_mismatch_is_detected() {
+    for format in [CpioFormat::Crc, CpioFormat::Newc] {
+        let mut builder = CpioBuilder::new(format);
+        builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+        builder.insert_data("intact", b"intact", 0o644).unwrap();
+        let mut mem = builder.to_vec().unwrap();
+        assert!(Cpio::load(&mem).unwrap().verify().unwrap().is_empty());
+        // flip a content byte, it follows the 110 byte header and "init\0" padded to 4 bytes
+        assert_eq!(&mem[116..118], b"#!");
+        mem[116] ^= 1;
+
+        let cpio = Cpio::load(&mem).unwrap();
+        let entry = cpio.find("init").unwrap().unwrap();
+        if format == CpioFormat::Crc {
+            assert_ne!(entry.computed_checksum().unwrap() as u64, entry.checksum().unwrap());
+            assert!(!entry.verify_checksum().unwrap());
+            assert_eq!(cpio.verify().unwrap(), ["init"]);
+        } else {
+            // nothing to compare against without a checksum
+            assert!(entry.verify_checksum().unwrap());
+            assert!(cpio.verify().unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use fallible_iterator::FallibleIterator;
+use rcpio::{Cpio, CpioBuilder, CpioFormat, EquivalenceMask};
+
+/// Overwrite a field of the newc header at `offset`, `field` counts the fields after the magic
+fn set_field(mem: &mut [u8], offset: usize, field: usize, value: u32) {
+    let start = offset + 6 + field * 8;
+    mem[start..start + 8].copy_from_slice(format!("{value:08x}").as_bytes());
+}
+
+/// Small rootfs whose metadata varies with the arguments
+fn archive(mtime: u32, uid: u32, init: &[u8], target: &str) -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", init, 0o755).unwrap();
+    builder.insert_data("etc/hostname", b"rcpio\n", 0o644).unwrap();
+    builder.insert_symlink("linuxrc", target).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+
+    let files: Vec<usize> = Cpio::load(&mem).unwrap().iter_files()
+        .map(|entry| Ok(entry.index))
+        .take(2)
+        .collect().unwrap();
+    for offset in files {
+        // uid, gid and mtime
+        for (field, value) in [(2, uid), (3, uid), (5, mtime)] {
+            set_field(&mut mem, offset, field, value);
+        }
+    }
+    mem
+}
+
+fn equivalent(a: &[u8], b: &[u8], ignore: EquivalenceMask) -> bool {
+    Cpio::load(a).unwrap().equivalent(&Cpio::load(b).unwrap(), ignore).unwrap()
+}
+
+#[test]
+fn same_except_mtime() {
+    let a = archive(1, 0, b"#!/bin/sh\n", "init");
+    let b = archive(2, 0, b"#!/bin/sh\n", "init");
+    let ignore_mtime = EquivalenceMask { mtime: true, ..EquivalenceMask::default() };
+
+    assert!(!equivalent(&a, &b, EquivalenceMask::default()));
+    assert!(equivalent(&a, &b, ignore_mtime));
+    assert_ne!(
+        Cpio::load(&a).unwrap().content_digest(EquivalenceMask::default()).unwrap(),
+        Cpio::load(&b).unwrap().content_digest(EquivalenceMask::default()).unwrap()
+    );
+    assert_eq!(
+        Cpio::load(&a).unwrap().content_digest(ignore_mtime).unwrap(),
+        Cpio::load(&b).unwrap().content_digest(ignore_mtime).unwrap()
+    );
+}
+
+#[test]
+fn masks_only_cover_their_metadata() {
+    let base = archive(1, 0, b"#!/bin/sh\n", "init");
+    let everything = EquivalenceMask { mtime: true, owner: true, inode: true };
+
+    let other_owner = archive(1, 1000, b"#!/bin/sh\n", "init");
+    assert!(!equivalent(&base, &other_owner, EquivalenceMask { mtime: true, ..EquivalenceMask::default() }));
+    assert!(equivalent(&base, &other_owner, EquivalenceMask { owner: true, ..EquivalenceMask::default() }));
+
+    // content and symlink targets are always compared
+    assert!(!equivalent(&base, &archive(1, 0, b"#!/bin/ash\n", "init"), everything));
+    assert!(!equivalent(&base, &archive(1, 0, b"#!/bin/sh\n", "/init"), everything));
+    assert!(equivalent(&base, &base.clone(), EquivalenceMask::default()));
+}
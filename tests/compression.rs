@@ -0,0 +1,119 @@
+use std::process::Command;
+
+use rcpio::{Compression, Cpio, CpioBuilder, CpioFormat, OwnedCpio};
+
+const ALL: [Compression; 6] = [
+    Compression::None,
+    Compression::Gzip,
+    Compression::Zstd,
+    Compression::Xz,
+    Compression::Lz4,
+    Compression::Bzip2,
+];
+
+fn builder(content_size: u32) -> CpioBuilder {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/motd", b"hello\n", 0o644).unwrap();
+    let content: Vec<u8> = (0..content_size).map(|i| (i % 251) as u8).collect();
+    builder.insert_data("content", &content, 0o644).unwrap();
+    builder
+}
+
+#[test]
+fn every_compression_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let builder = builder(100_000);
+    let plain = builder.to_vec().unwrap();
+
+    for compression in ALL {
+        let path = dir.path().join(format!("{compression:?}.cpio"));
+        builder.write(&path, compression).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        assert_eq!(builder.estimated_compressed_size(compression).unwrap(), size, "{compression:?}");
+
+        let archive = Cpio::open(&path).unwrap();
+        assert_eq!(archive.into_inner(), plain, "{compression:?}");
+    }
+}
+
+#[test]
+fn zstd_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio.zst");
+    let builder = builder(100_000);
+    builder.write(&path, Compression::Zstd).unwrap();
+
+    let decoded = zstd::decode_all(&std::fs::read(&path).unwrap()[..]).unwrap();
+    assert_eq!(decoded, builder.to_vec().unwrap());
+}
+
+#[test]
+fn lz4_uses_the_legacy_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio.lz4");
+    // more than one 8 MiB block of the legacy format
+    let builder = builder(9 << 20);
+    builder.write(&path, Compression::Lz4).unwrap();
+    let plain = builder.to_vec().unwrap();
+
+    // magic, then blocks of a little endian compressed size and a raw lz4 block
+    let mem = std::fs::read(&path).unwrap();
+    assert_eq!(&mem[..4], &[0x02, 0x21, 0x4c, 0x18]);
+    let (mut offset, mut decoded) = (4, vec![]);
+    while offset < mem.len() {
+        let size = u32::from_le_bytes(mem[offset..offset + 4].try_into().unwrap()) as usize;
+        let block = lz4_flex::block::decompress(&mem[offset + 4..offset + 4 + size], 8 << 20).unwrap();
+        assert!(block.len() == 8 << 20 || offset + 4 + size == mem.len());
+        decoded.extend(block);
+        offset += 4 + size;
+    }
+    assert_eq!(decoded, plain);
+    assert_eq!(Cpio::open(&path).unwrap().into_inner(), plain);
+
+    // the reference implementation must agree, when it is installed
+    if let Ok(output) = Command::new("lz4").arg("-d").arg("-c").arg(&path).output() {
+        assert!(output.status.success());
+        assert_eq!(output.stdout, plain);
+    }
+}
+
+#[test]
+fn xz_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio.xz");
+    let builder = builder(100_000);
+    builder.write(&path, Compression::Xz).unwrap();
+    let plain = builder.to_vec().unwrap();
+
+    let mem = std::fs::read(&path).unwrap();
+    assert_eq!(&mem[..6], b"\xfd7zXZ\x00");
+    let archive = OwnedCpio::load_compressed(&mem[..]).unwrap();
+    assert_eq!(archive.as_cpio().to_map().unwrap(), Cpio::load(&plain).unwrap().to_map().unwrap());
+    assert_eq!(archive.into_inner(), plain);
+
+    // concatenated streams decode one after another, like the kernel reads them
+    let twice = [&mem[..], &mem[..]].concat();
+    let mut streamed = vec![];
+    let mut stream = Cpio::load_compressed(&twice[..], &mut streamed).unwrap().iter_all_segments();
+    let mut names = vec![];
+    while let Some(entry) = fallible_iterator::FallibleIterator::next(&mut stream).unwrap() {
+        names.push(entry.name_string().unwrap());
+    }
+    assert_eq!(names.iter().filter(|name| *name == "etc/motd").count(), 2);
+}
+
+#[test]
+fn lz4_frames_are_read() {
+    use std::io::Write;
+
+    // the frame format the lz4 tool writes by default
+    let plain = builder(100_000).to_vec().unwrap();
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+    encoder.write_all(&plain).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio.lz4");
+    std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    assert_eq!(Cpio::open(&path).unwrap().into_inner(), plain);
+}
@@ -0,0 +1,191 @@
+use fallible_iterator::FallibleIterator;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat};
+
+type Fields = (u64, u64, u64, u64, u64, u64, u64, u64, u64, u64);
+
+/// Header fields other than the magic and the check field, name and content of every entry
+fn entries(mem: &[u8]) -> Vec<(Fields, Vec<u8>, Vec<u8>)> {
+    let cpio = Cpio::load(mem).unwrap();
+    cpio.iter_files().map(|entry| Ok((
+        (
+            entry.inode()?, entry.mode()?, entry.uid()?, entry.gid()?, entry.nlink()?,
+            entry.mtime()?, entry.devmajor()?, entry.devminor()?, entry.rdevmajor()?,
+            entry.rdevminor()?,
+        ),
+        entry.name()?.to_vec(),
+        entry.file_content()?.to_vec(),
+    ))).collect().unwrap()
+}
+
+#[test]
+fn newc_to_crc_and_back() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.insert_data("bin/busybox", b"\x7fELF busybox", 0o755).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    builder.insert_data("empty", b"", 0o600).unwrap();
+    let original = builder.to_vec().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let (crc_path, newc_path) = (dir.path().join("crc.cpio"), dir.path().join("newc.cpio"));
+    Cpio::load(&original).unwrap().convert_format(&crc_path, CpioFormat::Crc).unwrap();
+    let crc = std::fs::read(&crc_path).unwrap();
+
+    let converted = Cpio::load(&crc).unwrap();
+    assert_eq!(&crc[..6], b"070702");
+    let mut iter = converted.iter_files();
+    while let Some(entry) = iter.next().unwrap() {
+        let expected = if entry.is_reg().unwrap() {
+            entry.file_content().unwrap().iter().fold(0u32, |sum, b| sum.wrapping_add(*b as u32))
+        } else {
+            0
+        };
+        assert_eq!(entry.checksum().unwrap(), expected as u64, "{}", entry.name_string().unwrap());
+    }
+
+    // everything but the magic and the check field survives the round trip
+    assert_eq!(entries(&crc), entries(&original));
+
+    Cpio::load(&crc).unwrap().convert_format(&newc_path, CpioFormat::Newc).unwrap();
+    let newc = std::fs::read(&newc_path).unwrap();
+    assert_eq!(&newc[..6], b"070701");
+    assert_eq!(entries(&newc), entries(&original));
+    assert_eq!(newc, original);
+}
+
+#[test]
+fn every_segment_is_converted() {
+    let mut microcode = CpioBuilder::new(CpioFormat::Newc);
+    microcode.insert_data("kernel/x86/microcode/GenuineIntel.bin", b"ucode", 0o644).unwrap();
+    let mut main = CpioBuilder::new(CpioFormat::Newc);
+    main.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    let mut mem = microcode.to_vec().unwrap();
+    mem.extend(main.to_vec().unwrap());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("crc.cpio");
+    Cpio::load(&mem).unwrap().convert_format(&path, CpioFormat::Crc).unwrap();
+    let crc = std::fs::read(&path).unwrap();
+
+    // each segment is padded to a 512 byte block and ends with its own trailer
+    let names = |mem: &[u8]| -> Vec<(String, Vec<u8>)> {
+        Cpio::load(mem).unwrap().iter_files()
+            .map(|entry| Ok((entry.name_string()?, entry.file_content()?.to_vec())))
+            .collect().unwrap()
+    };
+    assert_eq!(crc.len(), 1024);
+    assert_eq!(names(&crc[..512]), [
+        (String::from("kernel/x86/microcode/GenuineIntel.bin"), b"ucode".to_vec()),
+        (String::from("TRAILER!!!"), vec![]),
+    ]);
+    assert_eq!(names(&crc[512..]), [
+        (String::from("init"), b"#!/bin/sh\n".to_vec()),
+        (String::from("TRAILER!!!"), vec![]),
+    ]);
+    assert_eq!(crc.windows(6).filter(|w| w == b"070702").count(), 4);
+
+    // a compressed segment can't be converted and isn't silently left out
+    let mut mem = microcode.to_vec().unwrap();
+    mem.extend(b"\x1f\x8b\x08\x00");
+    assert!(Cpio::load(&mem).unwrap().convert_format(&path, CpioFormat::Crc).is_err());
+}
+
+#[test]
+fn tarballs_are_transcoded() {
+    use std::io::Write;
+
+    fn header(entry_type: tar::EntryType, mode: u32, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_uid(1000);
+        header.set_gid(100);
+        header.set_mtime(1_000_000_000);
+        header.set_size(size);
+        header
+    }
+
+    let mut tar = tar::Builder::new(vec![]);
+    tar.append_data(&mut header(tar::EntryType::Directory, 0o755, 0), "bin", &b""[..]).unwrap();
+    let mut busybox = header(tar::EntryType::Regular, 0o755, 7);
+    tar.append_data(&mut busybox, "bin/busybox", &b"busybox"[..]).unwrap();
+    tar.append_link(&mut header(tar::EntryType::Symlink, 0o777, 0), "bin/sh", "busybox").unwrap();
+    tar.append_link(&mut header(tar::EntryType::Link, 0o755, 0), "bin/ash", "bin/busybox").unwrap();
+    tar.append_data(&mut header(tar::EntryType::Fifo, 0o600, 0), "fifo", &b""[..]).unwrap();
+    let tarball = tar.into_inner().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("a.tar");
+    std::fs::write(&plain, &tarball).unwrap();
+    let gzipped = dir.path().join("a.tar.gz");
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&tarball).unwrap();
+    std::fs::write(&gzipped, encoder.finish().unwrap()).unwrap();
+
+    for tar_path in [plain, gzipped] {
+        let output = dir.path().join("a.cpio");
+        rcpio::from_tar(&tar_path, &output, CpioFormat::Newc, rcpio::Compression::None).unwrap();
+        let mem = std::fs::read(&output).unwrap();
+
+        let cpio = Cpio::load(&mem).unwrap();
+        let found: Vec<_> = cpio.iter_files()
+            .filter(|entry| Ok(!entry.is_trailer()?))
+            .map(|entry| Ok((
+                entry.name_string()?, entry.mode()?, entry.uid()?, entry.gid()?, entry.mtime()?
+            )))
+            .collect().unwrap();
+        let names: Vec<_> = found.iter().map(|(name, mode, ..)| (name.as_str(), *mode)).collect();
+        assert_eq!(names, [
+            ("bin", 0o040755),
+            ("bin/busybox", 0o100755),
+            ("bin/sh", 0o120777),
+            ("bin/ash", 0o100755),
+            ("fifo", 0o010600),
+        ]);
+        for (name, _, uid, gid, mtime) in &found {
+            assert_eq!((*uid, *gid, *mtime), (1000, 100, 1_000_000_000), "{name}");
+        }
+
+        let entry = |name: &str| cpio.iter_files()
+            .find(|entry| Ok(entry.name_string()? == name))
+            .unwrap().unwrap();
+        assert_eq!(entry("bin/sh").file_content().unwrap(), b"busybox");
+        // the hardlink group shares an inode and only its last member carries the data
+        let (busybox, ash) = (entry("bin/busybox"), entry("bin/ash"));
+        assert_eq!(busybox.inode().unwrap(), ash.inode().unwrap());
+        assert_eq!((busybox.nlink().unwrap(), ash.nlink().unwrap()), (2, 2));
+        assert_eq!(busybox.file_content().unwrap(), b"");
+        assert_eq!(ash.file_content().unwrap(), b"busybox");
+    }
+}
+
+#[test]
+fn odc_round_trips() {
+    let mut builder = CpioBuilder::new(CpioFormat::Odc);
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.insert_data("bin/busybox", b"\x7fELF busybox", 0o755).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    builder.insert_data("odd", b"abc", 0o600).unwrap();
+    let odc = builder.to_vec().unwrap();
+    assert_eq!(&odc[..6], b"070707");
+    // 76 byte header, then the name with no alignment padding
+    assert_eq!(&odc[76..80], b"bin\0");
+
+    let cpio = Cpio::load(&odc).unwrap();
+    let names: Vec<String> = cpio.iter_files().map(|entry| entry.name_string()).collect().unwrap();
+    assert_eq!(names, ["bin", "bin/busybox", "bin/sh", "odd", "TRAILER!!!"]);
+    assert_eq!(cpio.read_file("bin/busybox").unwrap(), b"\x7fELF busybox");
+    assert_eq!(cpio.read_file("odd").unwrap(), b"abc");
+
+    let dir = tempfile::tempdir().unwrap();
+    let newc_path = dir.path().join("newc.cpio");
+    cpio.convert_format(&newc_path, CpioFormat::Newc).unwrap();
+    let newc = std::fs::read(&newc_path).unwrap();
+    assert_eq!(&newc[..6], b"070701");
+    assert_eq!(entries(&newc), entries(&odc));
+
+    let odc_path = dir.path().join("odc.cpio");
+    Cpio::load(&newc).unwrap().convert_format(&odc_path, CpioFormat::Odc).unwrap();
+    assert_eq!(std::fs::read(&odc_path).unwrap(), odc);
+}
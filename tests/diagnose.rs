@@ -0,0 +1,59 @@
+use rcpio::{Cpio, CpioBuilder, CpioFormat, Severity};
+
+fn messages(mem: &[u8]) -> Vec<(Severity, String)> {
+    Cpio::load(mem).unwrap().diagnose().into_iter()
+        .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+        .collect()
+}
+
+#[test]
+fn clean_archive_has_no_diagnostics() {
+    let mut builder = CpioBuilder::new(CpioFormat::Crc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/hostname", b"box\n", 0o644).unwrap();
+    assert!(messages(&builder.to_vec().unwrap()).is_empty());
+}
+
+#[test]
+fn every_problem_is_reported() {
+    let mut builder = CpioBuilder::new(CpioFormat::Crc);
+    builder.insert_data("etc/hostname", b"hello", 0o644).unwrap();
+    builder.insert_data("etc/hostname", b"again", 0o644).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    let at = mem.windows(5).position(|w| w == b"hello").unwrap();
+    mem[at] = b'j';
+
+    let found = messages(&mem);
+    let warnings: Vec<_> = found.iter()
+        .filter(|(severity, _)| *severity == Severity::Warning)
+        .map(|(_, message)| message.as_str())
+        .collect();
+    assert!(warnings.contains(&"'etc' has no directory entry before 'etc/hostname'"), "{found:?}");
+    assert!(warnings.contains(&"duplicate name 'etc/hostname'"), "{found:?}");
+    assert_eq!(found.iter().filter(|(severity, _)| *severity == Severity::Error).count(), 1, "{found:?}");
+}
+
+#[test]
+fn check_fails_on_errors_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    let check = |mem: &[u8]| {
+        std::fs::write(&path, mem).unwrap();
+        std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+            .arg("check").arg(&path)
+            .output().unwrap()
+    };
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("late/file", b"file", 0o644).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let output = check(&mem);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 entries, 0 errors, 1 warnings"));
+
+    // cut off before the trailer
+    let end = mem.windows(10).position(|w| w == b"TRAILER!!!").unwrap() - 110;
+    let output = check(&mem[..end]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("error: archive ends without a trailer"));
+}
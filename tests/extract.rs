@@ -0,0 +1,502 @@
+use rcpio::{Cpio, CpioBuilder, CpioFormat, Error, ExtractOptions};
+
+fn extract(mem: &[u8]) -> (tempfile::TempDir, Result<rcpio::ExtractReport, Error>) {
+    let dir = tempfile::tempdir().unwrap();
+    let result = Cpio::load(mem).unwrap()
+        .unarchive_with_options(&dir.path().join("out"), &ExtractOptions::default());
+    (dir, result)
+}
+
+/// Archive with one file stored under `name` as is, even where the builder would refuse it
+fn with_raw_name(name: &str) -> Vec<u8> {
+    let placeholder = "p".repeat(name.len());
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data(&placeholder, b"data", 0o644).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    mem[110..110 + name.len()].copy_from_slice(name.as_bytes());
+    mem
+}
+
+#[test]
+fn overlong_names_are_descriptive_errors() {
+    let long = "x".repeat(300);
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data(&format!("dir/{long}"), b"data", 0o644).unwrap();
+    let (_dir, result) = extract(&builder.to_vec().unwrap());
+    match result {
+        Err(Error::NameTooLongError(message)) => assert!(message.contains(&long), "{message}"),
+        other => panic!("expected NameTooLongError, got {other:?}"),
+    }
+
+    // every component fits, the whole path doesn't
+    let deep = vec!["y".repeat(200); 25].join("/");
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data(&deep, b"data", 0o644).unwrap();
+    let (_dir, result) = extract(&builder.to_vec().unwrap());
+    assert!(matches!(result, Err(Error::NameTooLongError(_))), "{result:?}");
+}
+
+#[test]
+fn normalize_internal_path_forms() {
+    use rcpio::normalize_internal_path;
+
+    for (name, expected) in [
+        ("etc/passwd", "etc/passwd"),
+        ("etc//passwd", "etc/passwd"),
+        ("etc/./passwd", "etc/passwd"),
+        ("./etc/passwd", "etc/passwd"),
+        (".//etc///passwd", "etc/passwd"),
+        ("etc/", "etc"),
+        ("etc/.", "etc"),
+        ("etc/./", "etc"),
+        (".", "."),
+        ("./", "."),
+        ("", "."),
+    ] {
+        assert_eq!(normalize_internal_path(name).unwrap(), expected, "{name}");
+    }
+    for name in ["..", "../etc", "etc/../passwd", "etc/..", "/etc/passwd", "//etc"] {
+        assert!(matches!(normalize_internal_path(name), Err(Error::InvalidPathError(_))), "{name}");
+    }
+}
+
+#[test]
+fn degenerate_names_extract_to_normalized_paths() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("./etc/.", 0o755).unwrap();
+    builder.insert_data("etc//passwd", b"passwd", 0o644).unwrap();
+    builder.insert_data("./etc/./group", b"group", 0o644).unwrap();
+    builder.insert_dir("usr//lib/", 0o755).unwrap();
+    let (dir, result) = extract(&builder.to_vec().unwrap());
+    result.unwrap();
+
+    let out = dir.path().join("out");
+    assert_eq!(std::fs::read(out.join("etc/passwd")).unwrap(), b"passwd");
+    assert_eq!(std::fs::read(out.join("etc/group")).unwrap(), b"group");
+    assert!(out.join("usr/lib").is_dir());
+}
+
+#[test]
+fn parent_references_are_rejected() {
+    for name in ["../escaped", "etc/../../escaped", "./a/../../escaped"] {
+        let (dir, result) = extract(&with_raw_name(name));
+        assert!(matches!(result, Err(Error::InvalidPathError(_))), "{name}: {result:?}");
+        assert!(!dir.path().join("escaped").exists(), "{name}");
+    }
+}
+
+#[test]
+fn metadata_only_extraction_writes_no_content() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.insert_data("bin/busybox", b"busybox", 0o755).unwrap();
+    builder.insert_data("etc", b"etc", 0o600).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let library = dir.path().join("library");
+    let options = ExtractOptions { metadata_only: true, ..ExtractOptions::default() };
+    Cpio::load(&mem).unwrap().unarchive_with_options(&library, &options).unwrap();
+    let cli = dir.path().join("cli");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg("--metadata-only").arg(&path).arg(&cli)
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    for out in [library, cli] {
+        assert!(out.join("bin").is_dir());
+        for (name, mode) in [("bin/busybox", 0o755), ("etc", 0o600)] {
+            let metadata = std::fs::metadata(out.join(name)).unwrap();
+            assert_eq!(metadata.len(), 0, "{name}");
+            assert_eq!(metadata.permissions().mode() & 0o777, mode, "{name}");
+        }
+        assert_eq!(std::fs::read_link(out.join("bin/sh")).unwrap().to_str().unwrap(), "busybox");
+    }
+}
+
+#[test]
+fn subtree_extraction_rebases_or_keeps_paths() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_dir("etc/init.d", 0o755).unwrap();
+    builder.insert_data("etc/init.d/rcS", b"rcS", 0o755).unwrap();
+    builder.insert_data("etc/passwd", b"passwd", 0o644).unwrap();
+    // shares a prefix with etc but isn't below it
+    builder.insert_data("etc-old", b"old", 0o644).unwrap();
+    builder.insert_data("init", b"init", 0o755).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = |root: &std::path::Path| -> Vec<String> {
+        let mut names = vec![];
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(path) = pending.pop() {
+            for entry in std::fs::read_dir(path).unwrap() {
+                let path = entry.unwrap().path();
+                names.push(path.strip_prefix(root).unwrap().to_string_lossy().into_owned());
+                if path.is_dir() {
+                    pending.push(path);
+                }
+            }
+        }
+        names.sort();
+        names
+    };
+
+    let rebased = dir.path().join("rebased");
+    let options = ExtractOptions { subtree: Some(String::from("./etc/")), ..ExtractOptions::default() };
+    cpio.unarchive_with_options(&rebased, &options).unwrap();
+    assert_eq!(files(&rebased), ["init.d", "init.d/rcS", "passwd"]);
+
+    let kept = dir.path().join("kept");
+    let options = ExtractOptions {
+        subtree: Some(String::from("etc/init.d")),
+        keep_subtree_path: true,
+        ..ExtractOptions::default()
+    };
+    cpio.unarchive_with_options(&kept, &options).unwrap();
+    assert_eq!(files(&kept), ["etc", "etc/init.d", "etc/init.d/rcS"]);
+
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let cli = dir.path().join("cli");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg(&path).arg(&cli).arg("--subtree").arg("etc/init.d")
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(files(&cli), ["rcS"]);
+}
+
+#[test]
+fn report_lists_extracted_entries() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir(".", 0o755).unwrap();
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.insert_data("bin/busybox", b"busybox", 0o755).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let (dir, report) = extract(&mem);
+    let entries: Vec<_> = report.unwrap().entries.iter()
+        .map(|entry| (entry.path.to_str().unwrap().to_string(), entry.mode))
+        .collect();
+    assert_eq!(entries, [
+        (String::from("."), 0o040755),
+        (String::from("bin"), 0o040755),
+        (String::from("bin/busybox"), 0o100755),
+        (String::from("bin/sh"), 0o120777),
+    ]);
+
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg("-v").arg(&path).arg(dir.path().join("verbose"))
+        .output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), ".\nbin\nbin/busybox\nbin/sh\n4 entries extracted\n");
+}
+
+/// Archive holding a socket, an entry of a file type that doesn't exist and a regular file
+fn unknown_type_archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_header("run/sock", rcpio::CpioHeader::synthetic(1, 0o140755), vec![]).unwrap();
+    builder.insert_header("run/initctl", rcpio::CpioHeader::synthetic(3, 0o010600), vec![]).unwrap();
+    builder.insert_header("weird", rcpio::CpioHeader::synthetic(2, 0o030644), vec![]).unwrap();
+    builder.insert_data("after", b"data", 0o644).unwrap();
+    builder.to_vec().unwrap()
+}
+
+#[test]
+fn unknown_type_policies() {
+    use std::os::unix::fs::FileTypeExt;
+    use rcpio::UnknownTypePolicy;
+
+    let mem = unknown_type_archive();
+    let cpio = Cpio::load(&mem).unwrap();
+    for policy in [UnknownTypePolicy::Error, UnknownTypePolicy::Skip, UnknownTypePolicy::SkipWithWarning] {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let options = ExtractOptions { unknown_type_policy: policy, ..ExtractOptions::default() };
+        let result = cpio.unarchive_with_options(&out, &options);
+
+        // sockets and FIFOs are known types, only the mode 03 entry is unknown
+        let socket = std::fs::symlink_metadata(out.join("run/sock")).unwrap();
+        assert!(socket.file_type().is_socket());
+        let fifo = std::fs::symlink_metadata(out.join("run/initctl")).unwrap();
+        assert!(fifo.file_type().is_fifo());
+        assert!(!out.join("weird").exists());
+        match policy {
+            UnknownTypePolicy::Error => {
+                assert!(matches!(result, Err(Error::FileModeError(_))), "{result:?}");
+                assert!(!out.join("after").exists());
+            },
+            UnknownTypePolicy::Skip => {
+                assert!(result.unwrap().skipped.is_empty());
+                assert_eq!(std::fs::read(out.join("after")).unwrap(), b"data");
+            },
+            UnknownTypePolicy::SkipWithWarning => {
+                assert_eq!(result.unwrap().skipped, ["weird"]);
+                assert_eq!(std::fs::read(out.join("after")).unwrap(), b"data");
+            },
+        }
+    }
+}
+
+#[test]
+fn strip_components_skips_short_names() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir(".", 0o755).unwrap();
+    builder.insert_data("README", b"readme", 0o644).unwrap();
+    builder.insert_dir("./usr", 0o755).unwrap();
+    builder.insert_dir("./usr/local", 0o755).unwrap();
+    builder.insert_dir("./usr/local/bin", 0o755).unwrap();
+    builder.insert_data("./usr/local/bin/foo", b"foo", 0o755).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    // entries with two components or fewer have nothing left and are skipped
+    let dir = tempfile::tempdir().unwrap();
+    let options = ExtractOptions { strip_components: 2, ..Default::default() };
+    let report = Cpio::load(&mem).unwrap()
+        .unarchive_with_options(&dir.path().join("out"), &options)
+        .unwrap();
+    let entries: Vec<_> = report.entries.iter().map(|entry| entry.path.to_str().unwrap()).collect();
+    assert_eq!(entries, ["bin", "bin/foo"]);
+    assert_eq!(std::fs::read(dir.path().join("out/bin/foo")).unwrap(), b"foo");
+    assert!(!dir.path().join("out/README").exists());
+    assert!(!dir.path().join("out/local").exists());
+
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg(&path).arg(dir.path().join("cli")).arg("--strip-components").arg("3")
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut names: Vec<_> = std::fs::read_dir(dir.path().join("cli")).unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["foo"]);
+}
+
+#[test]
+fn zero_mtime_is_restored_as_the_epoch() {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("zero", b"zero", 0o644).unwrap();
+    let mut header = rcpio::CpioHeader::synthetic(1, 0o100644);
+    header.c_mtime = 1_000_000_000;
+    builder.insert_header("dated", header, b"dated".to_vec()).unwrap();
+    builder.insert_symlink("link", "zero").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    Cpio::load(&mem).unwrap().unarchive(&dir.path().join("out")).unwrap();
+    let mtime = |name: &str| std::fs::symlink_metadata(dir.path().join("out").join(name)).unwrap().mtime();
+    assert_eq!(mtime("zero"), 0);
+    assert_eq!(mtime("dated"), 1_000_000_000);
+    assert_eq!(mtime("link"), 0);
+
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg(&path).arg(dir.path().join("cli"))
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::metadata(dir.path().join("cli/zero")).unwrap().mtime(), 0);
+}
+
+#[test]
+fn device_policies() {
+    use rcpio::DevicePolicy;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("dev", 0o755).unwrap();
+    let mut null = rcpio::CpioHeader::synthetic(1, 0o020666);
+    (null.c_rdevmajor, null.c_rdevminor) = (1, 3);
+    builder.insert_header("dev/null", null, vec![]).unwrap();
+    let mut loop0 = rcpio::CpioHeader::synthetic(2, 0o060660);
+    (loop0.c_rdevmajor, loop0.c_rdevminor, loop0.c_gid) = (7, 0, 6);
+    builder.insert_header("dev/loop0", loop0, vec![]).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let extract = |name: &str, device_policy| {
+        let options = ExtractOptions { device_policy, ..Default::default() };
+        cpio.unarchive_with_options(&dir.path().join(name), &options).unwrap()
+    };
+
+    let report = extract("skip", DevicePolicy::Skip);
+    assert!(report.devices.is_empty());
+    assert!(dir.path().join("skip/dev").is_dir());
+    assert!(!dir.path().join("skip/dev/null").exists());
+
+    let report = extract("record", DevicePolicy::Record);
+    assert!(!dir.path().join("record/dev/null").exists());
+    let lines: Vec<_> = report.devices.iter().map(|device| device.makedevs_line()).collect();
+    assert_eq!(lines, ["/dev/null c 666 0 0 1 3 0 0 -", "/dev/loop0 b 660 0 6 7 0 0 0 -"]);
+
+    // creating device nodes needs root
+    if unsafe { libc::geteuid() } != 0 {
+        return
+    }
+    let report = extract("create", DevicePolicy::Create);
+    assert!(report.devices.is_empty());
+    let null = std::fs::symlink_metadata(dir.path().join("create/dev/null")).unwrap();
+    assert!(null.file_type().is_char_device());
+    assert_eq!(null.rdev(), libc::makedev(1, 3));
+    assert_eq!(null.mode() & 0o7777, 0o666);
+    let loop0 = std::fs::symlink_metadata(dir.path().join("create/dev/loop0")).unwrap();
+    assert!(loop0.file_type().is_block_device());
+    assert_eq!(loop0.rdev(), libc::makedev(7, 0));
+}
+
+#[test]
+fn directory_mtimes_survive_their_children() {
+    use std::os::unix::fs::MetadataExt;
+
+    // 2001-09-09, far from when the test runs
+    const MTIME: u32 = 1_000_000_000;
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for (ino, (name, mode)) in [("a", 0o040755), ("a/b", 0o040755), ("a/b/c", 0o100644)].into_iter().enumerate() {
+        let header = rcpio::CpioHeader { c_mtime: MTIME, ..rcpio::CpioHeader::synthetic(ino as u32 + 1, mode) };
+        builder.insert_header(name, header, vec![]).unwrap();
+    }
+    let mem = builder.to_vec().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    Cpio::load(&mem).unwrap().unarchive_with_options(&out, &ExtractOptions::default()).unwrap();
+    for name in ["a", "a/b", "a/b/c"] {
+        assert_eq!(std::fs::metadata(out.join(name)).unwrap().mtime(), MTIME as i64, "{name}");
+    }
+
+    let skipped = dir.path().join("skipped");
+    let options = ExtractOptions { skip_mtime: true, ..ExtractOptions::default() };
+    Cpio::load(&mem).unwrap().unarchive_with_options(&skipped, &options).unwrap();
+    for name in ["a", "a/b", "a/b/c"] {
+        assert!(std::fs::metadata(skipped.join(name)).unwrap().mtime() > MTIME as i64, "{name}");
+    }
+}
+
+#[test]
+fn parallel_extraction_matches_serial() {
+    use std::os::unix::fs::MetadataExt;
+
+    const MTIME: u32 = 1_000_000_000;
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_header("bin", rcpio::CpioHeader { c_mtime: MTIME, ..rcpio::CpioHeader::synthetic(1, 0o040755) }, vec![]).unwrap();
+    for i in 0..50 {
+        builder.insert_data(&format!("bin/{i}"), format!("{i}").repeat(i * 100).as_bytes(), 0o644).unwrap();
+    }
+    builder.insert_symlink("bin/sh", "0").unwrap();
+    builder.insert_dir("etc", 0o700).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let options = ExtractOptions::default();
+    let listed = |report: rcpio::ExtractReport| -> Vec<_> {
+        report.entries.into_iter().map(|entry| (entry.path, entry.mode)).collect()
+    };
+    let serial = listed(cpio.unarchive_with_options(&dir.path().join("serial"), &options).unwrap());
+    for threads in [1, 4, 100] {
+        let out = dir.path().join(format!("parallel{threads}"));
+        assert_eq!(listed(cpio.unarchive_parallel(&out, &options, threads).unwrap()), serial);
+
+        for i in 0..50 {
+            assert_eq!(std::fs::read(out.join(format!("bin/{i}"))).unwrap(), format!("{i}").repeat(i * 100).as_bytes());
+        }
+        assert_eq!(std::fs::read_link(out.join("bin/sh")).unwrap().to_str().unwrap(), "0");
+        assert_eq!(std::fs::metadata(out.join("bin")).unwrap().mtime(), MTIME as i64);
+        assert!(out.join("etc").is_dir());
+    }
+
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let out = dir.path().join("cli");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg("--jobs").arg("4").arg(&path).arg(&out)
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(out.join("bin/49")).unwrap().len(), 49 * 100 * 2);
+}
+
+#[test]
+fn directory_modes_are_restored_after_their_children() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("ro", 0o500).unwrap();
+    builder.insert_dir("ro/shared", 0o555).unwrap();
+    builder.insert_data("ro/shared/file", b"file", 0o444).unwrap();
+    builder.insert_dir("private", 0o700).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let serial = dir.path().join("serial");
+    cpio.unarchive_with_options(&serial, &ExtractOptions::default()).unwrap();
+    let parallel = dir.path().join("parallel");
+    cpio.unarchive_parallel(&parallel, &ExtractOptions::default(), 4).unwrap();
+
+    for out in [serial, parallel] {
+        for (name, mode) in [("ro", 0o500), ("ro/shared", 0o555), ("private", 0o700)] {
+            let permissions = std::fs::metadata(out.join(name)).unwrap().permissions();
+            assert_eq!(permissions.mode() & 0o7777, mode, "{name}");
+        }
+        assert_eq!(std::fs::read(out.join("ro/shared/file")).unwrap(), b"file");
+        // let the temporary directory be cleaned up
+        for name in ["ro", "ro/shared"] {
+            std::fs::set_permissions(out.join(name), std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn overwrite_replaces_files_without_touching_their_other_links() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/motd", b"archived", 0o644).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let cpio = Cpio::load(&mem).unwrap();
+    cpio.unarchive(&out).unwrap();
+    std::fs::write(out.join("etc/motd"), b"modified").unwrap();
+    std::fs::hard_link(out.join("etc/motd"), dir.path().join("link")).unwrap();
+    std::fs::set_permissions(out.join("etc"), std::fs::Permissions::from_mode(0o700)).unwrap();
+
+    assert!(cpio.unarchive(&out).is_err());
+
+    let options = ExtractOptions { overwrite: true, ..Default::default() };
+    cpio.unarchive_with_options(&out, &options).unwrap();
+    assert_eq!(std::fs::read(out.join("etc/motd")).unwrap(), b"archived");
+    assert_eq!(std::fs::read(dir.path().join("link")).unwrap(), b"modified");
+    assert_eq!(std::fs::metadata(out.join("etc")).unwrap().permissions().mode() & 0o777, 0o755);
+
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let unar = |overwrite: bool| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"));
+        command.arg("unar").arg(&path).arg(&out);
+        if overwrite {
+            command.arg("--overwrite");
+        }
+        command.output().unwrap().status.success()
+    };
+    assert!(!unar(false));
+    assert!(unar(true));
+}
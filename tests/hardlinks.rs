@@ -0,0 +1,261 @@
+use std::os::unix::fs::MetadataExt;
+
+use fallible_iterator::FallibleIterator;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat, EquivalenceMask, ExtractOptions};
+
+/// newc record the way GNU cpio writes it, `nlink` members sharing `ino`
+fn newc_entry(ino: u32, nlink: u32, name: &str, content: &[u8]) -> Vec<u8> {
+    let fields = [ino, 0o100644, 0, 0, nlink, 0, content.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+    let mut out = b"070701".to_vec();
+    for field in fields {
+        out.extend(format!("{field:08x}").as_bytes());
+    }
+    out.extend(name.as_bytes());
+    out.push(0);
+    out.resize(out.len().next_multiple_of(4), 0);
+    out.extend(content);
+    out.resize(out.len().next_multiple_of(4), 0);
+    out
+}
+
+/// A hardlink set of three members where only the last carries the data, like `cpio -o` stores
+/// them, plus a file that isn't hardlinked
+fn gnu_archive() -> Vec<u8> {
+    let mut mem = vec![];
+    mem.extend(newc_entry(7, 3, "sh", b""));
+    mem.extend(newc_entry(8, 1, "motd", b""));
+    mem.extend(newc_entry(7, 3, "ash", b""));
+    mem.extend(newc_entry(7, 3, "busybox", b"\x7fELF busybox"));
+    mem.extend(newc_entry(0, 1, "TRAILER!!!", b""));
+    mem.resize(mem.len().next_multiple_of(512), 0);
+    mem
+}
+
+/// Archive with `groups` hardlink groups of three members each, plus one plain file per group
+fn archive(groups: usize) -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for group in 0..groups {
+        let content = format!("group {group}\n");
+        let names: Vec<String> = (0..3).map(|member| format!("g{group}-{member}")).collect();
+        for name in &names {
+            builder.insert_data(name, content.as_bytes(), 0o644).unwrap();
+        }
+        builder.insert_data(&format!("plain{group}"), b"plain\n", 0o644).unwrap();
+        builder.hardlink_group(&names.iter().map(String::as_str).collect::<Vec<_>>()).unwrap();
+    }
+    builder.to_vec().unwrap()
+}
+
+#[test]
+fn to_map_resolves_placeholders() {
+    let mem = archive(50);
+    let map = Cpio::load(&mem).unwrap().to_map().unwrap();
+    assert_eq!(map.len(), 200);
+    for group in 0..50 {
+        for member in 0..3 {
+            assert_eq!(map[&format!("g{group}-{member}")], format!("group {group}\n").as_bytes());
+        }
+    }
+}
+
+#[test]
+fn placeholders_resolve_to_the_group_data() {
+    let mem = gnu_archive();
+    let cpio = Cpio::load(&mem).unwrap();
+    let hardlinks = cpio.hardlinks().unwrap();
+
+    let mut iter = cpio.iter_files();
+    while let Some(entry) = iter.next().unwrap() {
+        let expected: &[u8] = match entry.name_string().unwrap().as_str() {
+            "sh" | "ash" | "busybox" => b"\x7fELF busybox",
+            _ => b"",
+        };
+        assert_eq!(hardlinks.content(&entry).unwrap(), expected, "{}", entry.name_string().unwrap());
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    cpio.unarchive_with_options(&out, &ExtractOptions::default()).unwrap();
+    for name in ["sh", "ash", "busybox"] {
+        assert_eq!(std::fs::read(out.join(name)).unwrap(), b"\x7fELF busybox", "{name}");
+    }
+    assert_eq!(std::fs::read(out.join("motd")).unwrap(), b"");
+}
+
+#[test]
+fn digest_ignores_which_member_carries_data() {
+    let linked = archive(5);
+    // the same files without any hardlinks
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for group in 0..5 {
+        for member in 0..3 {
+            builder.insert_data(&format!("g{group}-{member}"), format!("group {group}\n").as_bytes(), 0o644).unwrap();
+        }
+        builder.insert_data(&format!("plain{group}"), b"plain\n", 0o644).unwrap();
+    }
+    let unlinked = builder.to_vec().unwrap();
+
+    let ignore = EquivalenceMask { inode: true, ..EquivalenceMask::default() };
+    let a = Cpio::load(&linked).unwrap().content_digest(ignore).unwrap();
+    let b = Cpio::load(&unlinked).unwrap().content_digest(ignore).unwrap();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn same_inode_compares_devices() {
+    let header = |ino: u32, devmajor: u32, devminor: u32| {
+        let mut header = rcpio::CpioHeader::synthetic(ino, 0o100644);
+        header.c_devmajor = devmajor;
+        header.c_devminor = devminor;
+        header
+    };
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_header("a", header(7, 8, 1), vec![]).unwrap();
+    builder.insert_header("b", header(7, 8, 1), vec![]).unwrap();
+    builder.insert_header("other-major", header(7, 9, 1), vec![]).unwrap();
+    builder.insert_header("other-minor", header(7, 8, 2), vec![]).unwrap();
+    builder.insert_header("other-inode", header(8, 8, 1), vec![]).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let cpio = Cpio::load(&mem).unwrap();
+    let entry = |name: &str| cpio.find(name).unwrap().unwrap();
+    assert!(entry("a").same_inode(&entry("b")).unwrap());
+    assert!(entry("a").same_inode(&entry("a")).unwrap());
+    for other in ["other-major", "other-minor", "other-inode"] {
+        assert!(!entry("a").same_inode(&entry(other)).unwrap(), "{other}");
+    }
+}
+
+#[test]
+fn read_file_resolves_placeholders() {
+    let mem = gnu_archive();
+    let cpio = Cpio::load(&mem).unwrap();
+    for name in ["sh", "ash", "busybox"] {
+        assert_eq!(cpio.read_file_borrowed(name).unwrap(), b"\x7fELF busybox", "{name}");
+    }
+    assert!(cpio.read_file("motd").unwrap().is_empty());
+}
+
+#[test]
+fn hardlink_group_shares_inode_and_data() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("bin/sh", b"sh", 0o755).unwrap();
+    builder.insert_data("bin/busybox", b"busybox", 0o755).unwrap();
+    builder.insert_data("bin/ls", b"ls", 0o755).unwrap();
+    builder.insert_data("init", b"init", 0o755).unwrap();
+    builder.hardlink_group(&["bin/sh", "bin/busybox", "./bin/ls"]).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let members: Vec<_> = ["bin/sh", "bin/busybox", "bin/ls"].iter()
+        .map(|name| cpio.find(name).unwrap().unwrap())
+        .collect();
+    for member in &members {
+        assert_eq!(member.nlink().unwrap(), 3);
+        assert_eq!(member.inode().unwrap(), members[0].inode().unwrap());
+    }
+    assert_eq!(members[0].filesize().unwrap(), 0);
+    assert_eq!(members[1].filesize().unwrap(), 0);
+    assert_eq!(members[2].file_content().unwrap(), b"ls");
+
+    let init = cpio.find("init").unwrap().unwrap();
+    assert_eq!(init.nlink().unwrap(), 1);
+    assert_ne!(init.inode().unwrap(), members[0].inode().unwrap());
+
+    assert!(builder.hardlink_group(&["init", "bin/sh"]).is_err());
+    assert!(builder.hardlink_group(&["missing"]).is_err());
+}
+
+#[test]
+fn hardlinks_resolve_every_member() {
+    let mem = archive(50);
+    let cpio = Cpio::load(&mem).unwrap();
+    let hardlinks = cpio.hardlinks().unwrap();
+    for group in 0..50 {
+        let first = cpio.find(&format!("g{group}-0")).unwrap().unwrap();
+        assert!(hardlinks.first(&first).unwrap().is_none());
+        for member in 0..3 {
+            let entry = cpio.find(&format!("g{group}-{member}")).unwrap().unwrap();
+            assert_eq!(hardlinks.content(&entry).unwrap(), format!("group {group}\n").as_bytes());
+            if member > 0 {
+                let first = hardlinks.first(&entry).unwrap().unwrap();
+                assert_eq!(first.name_string().unwrap(), format!("g{group}-0"));
+            }
+        }
+        let plain = cpio.find(&format!("plain{group}")).unwrap().unwrap();
+        assert_eq!(hardlinks.content(&plain).unwrap(), b"plain\n");
+        assert!(hardlinks.first(&plain).unwrap().is_none());
+    }
+}
+
+#[test]
+fn extraction_links_group_members() {
+    let mem = archive(50);
+    let cpio = Cpio::load(&mem).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let serial = dir.path().join("serial");
+    cpio.unarchive_with_options(&serial, &ExtractOptions::default()).unwrap();
+    let parallel = dir.path().join("parallel");
+    cpio.unarchive_parallel(&parallel, &ExtractOptions::default(), 4).unwrap();
+
+    for output in [serial, parallel] {
+        for group in 0..50 {
+            let first = std::fs::metadata(output.join(format!("g{group}-0"))).unwrap();
+            assert_eq!(first.nlink(), 3);
+            for member in 1..3 {
+                let path = output.join(format!("g{group}-{member}"));
+                assert_eq!(std::fs::metadata(&path).unwrap().ino(), first.ino());
+                assert_eq!(std::fs::read(&path).unwrap(), format!("group {group}\n").as_bytes());
+            }
+        }
+    }
+}
+
+#[test]
+fn filesystem_hardlinks_are_stored_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    std::fs::create_dir(&root).unwrap();
+    std::fs::write(root.join("a"), b"shared").unwrap();
+    std::fs::hard_link(root.join("a"), root.join("b")).unwrap();
+    std::fs::hard_link(root.join("a"), root.join("c")).unwrap();
+    std::fs::write(root.join("single"), b"single").unwrap();
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_tree(&root, "").unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let members: Vec<_> = ["a", "b", "c"].iter().map(|name| cpio.find(name).unwrap().unwrap()).collect();
+    let with_data = members.iter().filter(|entry| entry.filesize().unwrap() != 0).count();
+    assert_eq!(with_data, 1);
+    assert!(members.iter().all(|entry| entry.same_inode(&members[0]).unwrap()));
+    assert_eq!(cpio.find("single").unwrap().unwrap().nlink().unwrap(), 1);
+
+    let output = dir.path().join("out");
+    cpio.unarchive_with_options(&output, &ExtractOptions::default()).unwrap();
+    let first = std::fs::metadata(output.join("a")).unwrap();
+    assert_eq!(first.nlink(), 3);
+    for name in ["b", "c"] {
+        assert_eq!(std::fs::metadata(output.join(name)).unwrap().ino(), first.ino());
+        assert_eq!(std::fs::read(output.join(name)).unwrap(), b"shared");
+    }
+}
+
+#[test]
+fn remove_keeps_group_data() {
+    let mem = archive(3);
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.cpio");
+
+    // the last member carries the data, removing it moves the data to the one before
+    Cpio::load(&mem).unwrap().remove(&output, "g1-2").unwrap();
+    let removed = std::fs::read(&output).unwrap();
+    let cpio = Cpio::load(&removed).unwrap();
+    assert!(cpio.find("g1-2").unwrap().is_none());
+    assert_eq!(cpio.read_file("g1-0").unwrap(), b"group 1\n");
+    assert_eq!(cpio.find("g1-1").unwrap().unwrap().nlink().unwrap(), 2);
+    assert_eq!(cpio.to_map().unwrap()["g1-1"], b"group 1\n");
+}
@@ -0,0 +1,61 @@
+use rcpio::{CpioBuilder, CpioFormat, CpioIndex, Error};
+
+fn archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("etc/hostname", b"rcpio\n", 0o644).unwrap();
+    builder.insert_symlink("hostname", "etc/hostname").unwrap();
+    builder.to_vec().unwrap()
+}
+
+/// Overwrite the namesize field of the newc header at `offset`
+fn set_namesize(mem: &mut [u8], offset: usize, namesize: u32) {
+    mem[offset + 94..offset + 102].copy_from_slice(format!("{namesize:08x}").as_bytes());
+}
+
+/// Overwrite the filesize field of the newc header at `offset`
+fn set_filesize(mem: &mut [u8], offset: usize, filesize: u32) {
+    mem[offset + 54..offset + 62].copy_from_slice(format!("{filesize:08x}").as_bytes());
+}
+
+#[test]
+fn index_reads_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, archive()).unwrap();
+
+    let index = CpioIndex::build_from_file(&path).unwrap();
+    assert_eq!(index.entries().len(), 2);
+    assert_eq!(index.read_content("./etc/hostname").unwrap(), b"rcpio\n");
+}
+
+#[test]
+fn huge_namesize_is_rejected_before_allocating() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+
+    let mut mem = archive();
+    set_namesize(&mut mem, 0, u32::MAX);
+    std::fs::write(&path, &mem).unwrap();
+    assert!(matches!(CpioIndex::build_from_file(&path), Err(Error::InvalidArchiveError(_))));
+
+    // within PATH_MAX, but running past the end of the file
+    let mut mem = archive();
+    mem.truncate(200);
+    set_namesize(&mut mem, 0, 4000);
+    std::fs::write(&path, &mem).unwrap();
+    assert!(matches!(CpioIndex::build_from_file(&path), Err(Error::EarlyEOFError)));
+}
+
+#[test]
+fn huge_filesize_is_rejected_before_allocating() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+
+    let mut mem = archive();
+    set_filesize(&mut mem, 0, u32::MAX);
+    std::fs::write(&path, &mem).unwrap();
+    match CpioIndex::build_from_file(&path) {
+        Err(Error::InvalidArchiveError(message)) => assert!(message.contains("4294967295"), "{message}"),
+        other => panic!("expected InvalidArchiveError, got {other:?}"),
+    }
+}
@@ -0,0 +1,77 @@
+use rcpio::{check_initramfs, CpioBuilder, CpioFormat};
+
+fn built(format: CpioFormat) -> Vec<u8> {
+    let mut builder = CpioBuilder::new(format);
+    builder.insert_dir(".", 0o755).unwrap();
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.insert_data("bin/busybox", &[0x7f; 1001], 0o755).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    builder.insert_data("init", b"#!/bin/sh\nexec /bin/sh\n", 0o755).unwrap();
+    builder.to_vec().unwrap()
+}
+
+#[test]
+fn builder_output_is_kernel_parseable() {
+    for format in [CpioFormat::Newc, CpioFormat::Crc] {
+        let mem = built(format);
+        check_initramfs(&mem).unwrap();
+
+        // the kernel unpacks concatenated segments, e.g. early microcode in front of the image
+        let mut concatenated = built(format);
+        concatenated.extend(built(format));
+        check_initramfs(&concatenated).unwrap();
+    }
+}
+
+#[test]
+fn odc_is_rejected() {
+    assert!(check_initramfs(&built(CpioFormat::Odc)).is_err());
+}
+
+#[test]
+fn truncated_archives_are_errors() {
+    let mem = built(CpioFormat::Newc);
+    // every cut, including those ending right after unaligned content, must fail cleanly
+    for len in 0..mem.len() {
+        let trailer_end = mem.len() - mem.iter().rev().take_while(|b| **b == 0).count();
+        if len >= trailer_end {
+            break;
+        }
+        assert!(check_initramfs(&mem[..len]).is_err(), "accepted archive cut at {len}");
+    }
+}
+
+#[test]
+fn content_ending_unaligned_at_eof() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("a", b"x", 0o644).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    // 110 byte header, "a\0" padded to 112, then one byte of content
+    let end = 113;
+    assert_eq!(&mem[end - 1..end], b"x");
+    assert!(check_initramfs(&mem[..end]).is_err());
+}
+
+#[test]
+fn malformed_headers_are_errors() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("etc/passwd", b"root", 0o644).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let mut embedded_null = mem.clone();
+    embedded_null[110 + 3] = 0;
+    assert!(check_initramfs(&embedded_null).is_err());
+
+    let mut unterminated = mem.clone();
+    unterminated[110 + 10] = b'x';
+    assert!(check_initramfs(&unterminated).is_err());
+
+    let mut mem = built(CpioFormat::Newc);
+    // misaligned start of the segment
+    mem.insert(0, 0);
+    assert!(check_initramfs(&mem).is_err());
+
+    assert!(check_initramfs(&[0; 512]).is_err());
+    assert!(check_initramfs(b"070701garbage").is_err());
+}
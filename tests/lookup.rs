@@ -0,0 +1,283 @@
+use std::path::Path;
+use std::process::Command;
+
+use fallible_iterator::FallibleIterator;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat, Error};
+
+fn archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir(".", 0o755).unwrap();
+    builder.insert_data("./etc//passwd", b"root:x:0:0::/root:/bin/sh\n", 0o644).unwrap();
+    builder.insert_data("usr/bin/env", b"env", 0o755).unwrap();
+    builder.to_vec().unwrap()
+}
+
+#[test]
+fn lookups_normalize_names() {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    for query in ["etc/passwd", "./etc/passwd", "etc//passwd", "etc/./passwd", "etc/passwd/", ".//etc/passwd"] {
+        let entry = cpio.find(query).unwrap().unwrap_or_else(|| panic!("{query} not found"));
+        assert_eq!(entry.name_string().unwrap(), "./etc//passwd");
+    }
+    for query in ["usr/bin/env", "./usr/bin/env", "usr//bin///env"] {
+        assert!(cpio.contains(query).unwrap(), "{query}");
+    }
+    for query in [".", "./", ""] {
+        assert_eq!(cpio.find(query).unwrap().unwrap().name_string().unwrap(), ".", "{query}");
+    }
+
+    assert!(cpio.find("etc/pass").unwrap().is_none());
+    assert!(cpio.find("etc/passwd/x").unwrap().is_none());
+    assert!(cpio.find("usr/bin").unwrap().is_none());
+    assert!(matches!(cpio.find("/etc/passwd"), Err(Error::InvalidPathError(_))));
+    assert!(matches!(cpio.find("usr/../etc/passwd"), Err(Error::InvalidPathError(_))));
+}
+
+#[test]
+fn extract_entry_to_and_cat_normalize_names() {
+    let mem = archive();
+    let mut out = vec![];
+    Cpio::load(&mem).unwrap().extract_entry_to("etc//passwd", &mut out).unwrap();
+    assert_eq!(out, b"root:x:0:0::/root:/bin/sh\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("cat").arg(&path).arg("etc//passwd")
+        .output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"root:x:0:0::/root:/bin/sh\n");
+}
+
+/// Archive of empty files owned by the given uids and gids, patched into the newc headers
+fn owned_archive(dir: &Path, owners: &[(&str, u32, u32)]) -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for (name, _, _) in owners {
+        std::fs::write(dir.join(name), b"").unwrap();
+        builder.insert(&dir.join(name), name).unwrap();
+    }
+    let path = dir.join("a.cpio");
+    builder.write(&path, rcpio::Compression::None).unwrap();
+    let mut mem = std::fs::read(path).unwrap();
+
+    let indices: Vec<usize> = Cpio::load(&mem).unwrap().iter_files()
+        .map(|entry| Ok(entry.index))
+        .collect().unwrap();
+    for (index, (_, uid, gid)) in indices.into_iter().zip(owners) {
+        // uid and gid are the third and fourth fields after the magic
+        mem[index + 22..index + 30].copy_from_slice(format!("{uid:08x}").as_bytes());
+        mem[index + 30..index + 38].copy_from_slice(format!("{gid:08x}").as_bytes());
+    }
+    mem
+}
+
+#[test]
+fn entries_are_filtered_by_owner() {
+    let dir = tempfile::tempdir().unwrap();
+    let mem = owned_archive(dir.path(), &[("root", 0, 0), ("user", 1000, 1000), ("shared", 1000, 0)]);
+    let cpio = Cpio::load(&mem).unwrap();
+
+    assert_eq!(cpio.entries_by_owner(Some(1000), None).unwrap(), ["user", "shared"]);
+    assert_eq!(cpio.entries_by_owner(None, Some(0)).unwrap(), ["root", "shared"]);
+    assert_eq!(cpio.entries_by_owner(Some(1000), Some(0)).unwrap(), ["shared"]);
+    assert_eq!(cpio.entries_by_owner(None, None).unwrap(), ["root", "user", "shared"]);
+    assert!(cpio.entries_by_owner(Some(1), None).unwrap().is_empty());
+
+    let path = dir.path().join("owned.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let ls = |args: &[&str]| -> Vec<String> {
+        let output = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+            .arg("ls").arg(&path).args(args)
+            .output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap().lines()
+            .map(|line| line.rsplit(' ').next().unwrap().trim_end_matches('\0').to_string())
+            .collect()
+    };
+    assert_eq!(ls(&["--uid", "1000"]), ["user", "shared"]);
+    assert_eq!(ls(&["--uid", "1000", "--gid", "0"]), ["shared"]);
+}
+
+#[test]
+fn executables_are_regular_files_with_an_execute_bit() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.insert_data("bin/sh", b"sh", 0o755).unwrap();
+    builder.insert_data("bin/group", b"group", 0o610).unwrap();
+    builder.insert_data("bin/other", b"other", 0o601).unwrap();
+    builder.insert_data("etc", b"etc", 0o644).unwrap();
+    builder.insert_symlink("linuxrc", "bin/sh").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let cpio = Cpio::load(&mem).unwrap();
+    let executables: Vec<String> = cpio.iter_files()
+        .filter(|entry| Ok(!entry.is_trailer()? && entry.is_executable()?))
+        .map(|entry| entry.name_string())
+        .collect().unwrap();
+    assert_eq!(executables, ["bin/sh", "bin/group", "bin/other"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("ls").arg(&path).arg("--executables")
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let names: Vec<String> = String::from_utf8(output.stdout).unwrap().lines()
+        .map(|line| line.rsplit(' ').next().unwrap().trim_end_matches('\0').to_string())
+        .collect();
+    assert_eq!(names, executables);
+}
+
+#[test]
+fn mtime_queries_straddle_the_boundary() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for (name, mtime) in [("zero", 0), ("before", 999), ("at", 1000), ("after", 1001), ("last", u32::MAX)] {
+        let mut header = rcpio::CpioHeader::synthetic(mtime, 0o100644);
+        header.c_mtime = mtime;
+        builder.insert_header(name, header, vec![]).unwrap();
+    }
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    assert_eq!(cpio.entries_modified_after(1000).unwrap(), ["after", "last"]);
+    assert_eq!(cpio.entries_modified_after(0).unwrap(), ["before", "at", "after", "last"]);
+    assert!(cpio.entries_modified_after(u32::MAX).unwrap().is_empty());
+    assert_eq!(cpio.entries_modified_between(999, 1001).unwrap(), ["before", "at"]);
+    assert_eq!(cpio.entries_modified_between(1001, u32::MAX).unwrap(), ["after"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let ls = |newer_than: &str| Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("ls").arg(&path).arg("--newer-than").arg(newer_than)
+        .output().unwrap();
+    for newer_than in ["1000", "1970-01-01T00:16:40"] {
+        let output = ls(newer_than);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let names: Vec<_> = stdout.lines()
+            .map(|line| line.rsplit(' ').next().unwrap().trim_end_matches('\0'))
+            .collect();
+        assert_eq!(names, ["after", "last"], "{newer_than}");
+    }
+    // past the end of the 32-bit mtime range
+    assert!(!ls("4294967296").status.success());
+    assert!(!ls("2106-02-08").status.success());
+}
+
+#[test]
+fn symlink_sizes_follow_the_policy() {
+    use rcpio::SizePolicy;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("bin/busybox", &[0; 100], 0o755).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    builder.insert_symlink("lib/sh", "/bin/sh").unwrap();
+    builder.insert_symlink("usr/up", "../../bin/busybox").unwrap();
+    builder.insert_symlink("dangling", "missing").unwrap();
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    // relative, absolute, chained and root-clamped targets all end at busybox
+    for name in ["bin/sh", "lib/sh", "usr/up"] {
+        let link = cpio.find(name).unwrap().unwrap();
+        let target = cpio.follow_symlink(&link).unwrap().unwrap();
+        assert_eq!(target.name_string().unwrap(), "bin/busybox", "{name}");
+    }
+    let dangling = cpio.find("dangling").unwrap().unwrap();
+    assert!(cpio.follow_symlink(&dangling).unwrap().is_none());
+
+    assert_eq!(cpio.stats(SizePolicy::TargetLength).unwrap().total_size, 100 + 7 + 7 + 17 + 7);
+    assert_eq!(cpio.stats(SizePolicy::Zero).unwrap().total_size, 100);
+    let stats = cpio.stats(SizePolicy::Target).unwrap();
+    assert_eq!((stats.entries, stats.regular_files, stats.symlinks), (5, 1, 4));
+    assert_eq!(stats.total_size, 4 * 100);
+    assert_eq!(cpio.largest(1, SizePolicy::Zero).unwrap(), [(String::from("bin/busybox"), 100)]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let run = |args: &[&str]| -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+            .arg(args[0]).arg(&path).args(&args[1..])
+            .output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(run(&["ls", "--total", "--symlink-size", "zero"]).ends_with("total 100\n"));
+    let stats = run(&["stats", "--symlink-size", "target"]);
+    assert!(stats.contains("symlinks:      4\n"), "{stats}");
+    assert!(stats.contains("total size:    400\n"), "{stats}");
+}
+
+#[test]
+fn read_file_returns_regular_file_content() {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let passwd = b"root:x:0:0::/root:/bin/sh\n";
+    assert_eq!(cpio.read_file_borrowed("etc/passwd").unwrap(), passwd);
+    assert_eq!(cpio.read_file("./etc/passwd").unwrap(), passwd);
+
+    assert!(matches!(cpio.read_file("etc/shadow"), Err(Error::NoSuchFile(_))));
+    assert!(matches!(cpio.read_file("."), Err(Error::FileModeError(_))));
+}
+
+#[test]
+fn du_groups_sizes_by_top_level_component() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir(".", 0o755).unwrap();
+    builder.insert_data("etc/passwd", &[b'p'; 10], 0o644).unwrap();
+    builder.insert_data("./etc/group", &[b'g'; 5], 0o644).unwrap();
+    builder.insert_data("usr/lib/libc.so.6", &[b'l'; 100], 0o755).unwrap();
+    builder.insert_data("init", &[b'i'; 15], 0o755).unwrap();
+    builder.insert_dir("tmp", 0o1777).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    // equal sizes are ordered by name, empty directories still show up
+    let sizes = Cpio::load(&mem).unwrap().size_by_toplevel().unwrap();
+    assert_eq!(sizes, [
+        (String::from("usr"), 100),
+        (String::from("etc"), 15),
+        (String::from("init"), 15),
+        (String::from("tmp"), 0),
+    ]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcpio")).arg("du").arg(&path).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<Vec<&str>> = stdout.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(lines, [["100", "usr"], ["15", "etc"], ["15", "init"], ["0", "tmp"], ["130", "total"]]);
+}
+
+#[test]
+fn cat_prints_hardlinked_content() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("bin/sh", b"busybox", 0o755).unwrap();
+    builder.insert_data("bin/ls", b"busybox", 0o755).unwrap();
+    builder.insert_dir("bin", 0o755).unwrap();
+    builder.hardlink_group(&["bin/sh", "bin/ls"]).unwrap();
+    let mem = builder.to_vec().unwrap();
+    assert_eq!(Cpio::load(&mem).unwrap().find("bin/sh").unwrap().unwrap().filesize().unwrap(), 0);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let cat = |name: &str| Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("cat").arg(&path).arg(name)
+        .output().unwrap();
+
+    // the empty member prints the data of its group
+    let output = cat("./bin/sh");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"busybox");
+    assert!(!cat("bin").status.success());
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rcpio::{from_manifest, Compression, Cpio, CpioFormat};
+
+fn write_manifest(dir: &Path) -> std::path::PathBuf {
+    let init = dir.join("init");
+    std::fs::write(&init, b"#!/bin/sh\n").unwrap();
+    let file = std::fs::File::options().write(true).open(&init).unwrap();
+    file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(1_000_000)).unwrap();
+
+    let manifest = dir.join("manifest");
+    std::fs::write(&manifest, format!(
+        "# comment\n\
+         dir /dev 755 0 0\n\
+         nod /dev/console 600 0 0 c 5 1\n\
+         file /init {} 755 0 0\n\
+         slink /bin/sh busybox 777 0 0\n",
+        init.display()
+    )).unwrap();
+    manifest
+}
+
+fn mtimes(path: &Path) -> Vec<(String, u64)> {
+    let mem = std::fs::read(path).unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+    let mut entries = vec![];
+    let mut iter = cpio.iter_files();
+    while let Some(entry) = fallible_iterator::FallibleIterator::next(&mut iter).unwrap() {
+        let name = entry.name_string().unwrap();
+        if name != "TRAILER!!!" {
+            entries.push((name, entry.mtime().unwrap()));
+        }
+    }
+    entries
+}
+
+#[test]
+fn explicit_mtime_applies_to_every_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(dir.path());
+    let output = dir.path().join("out.cpio");
+
+    from_manifest(&manifest, &output, CpioFormat::Newc, Compression::None, Some(42)).unwrap();
+    let entries = mtimes(&output);
+    let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["dev", "dev/console", "init", "bin/sh"]);
+    assert!(entries.iter().all(|(_, mtime)| *mtime == 42));
+}
+
+#[test]
+fn default_mtime_matches_gen_init_cpio() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(dir.path());
+    let output = dir.path().join("out.cpio");
+
+    let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    from_manifest(&manifest, &output, CpioFormat::Newc, Compression::None, None).unwrap();
+    for (name, mtime) in mtimes(&output) {
+        if name == "init" {
+            assert_eq!(mtime, 1_000_000);
+        } else {
+            assert!(mtime >= before, "{name}");
+        }
+    }
+}
+
+#[test]
+fn gen_takes_kbuild_build_timestamp() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(dir.path());
+    let output = dir.path().join("out.cpio");
+
+    let result = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("gen").arg(&manifest).arg(&output)
+        .env("KBUILD_BUILD_TIMESTAMP", "2000-01-01")
+        .output().unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(mtimes(&output).iter().all(|(_, mtime)| *mtime == 946684800));
+
+    // an explicit --timestamp wins over the environment
+    let result = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("gen").arg(&manifest).arg(&output).args(["--timestamp", "7"])
+        .env("KBUILD_BUILD_TIMESTAMP", "2000-01-01")
+        .output().unwrap();
+    assert!(result.status.success());
+    assert!(mtimes(&output).iter().all(|(_, mtime)| *mtime == 7));
+}
+
+#[test]
+fn malformed_manifests_are_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.cpio");
+    for manifest in [
+        "dir /etc 75x 0 0\n",
+        "nod /dev/null 666 0 0 x 1 3\n",
+        "file /init /does/not/exist 755 0 0\n",
+        "blob /x 644 0 0\n",
+        "dir\n",
+    ] {
+        let path = dir.path().join("manifest");
+        std::fs::write(&path, manifest).unwrap();
+        assert!(
+            from_manifest(&path, &output, CpioFormat::Newc, Compression::None, Some(0)).is_err(),
+            "{manifest}"
+        );
+    }
+}
+
+#[test]
+fn gen_writes_odc() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(dir.path());
+    let output = dir.path().join("out.cpio");
+    let result = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("gen").arg(&manifest).arg(&output).arg("--odc")
+        .output().unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+
+    let mem = std::fs::read(&output).unwrap();
+    assert_eq!(&mem[..6], b"070707");
+    let names: Vec<String> = mtimes(&output).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["dev", "dev/console", "init", "bin/sh"]);
+    assert_eq!(Cpio::load(&mem).unwrap().read_file("init").unwrap(), b"#!/bin/sh\n");
+}
+
+#[test]
+fn gen_compresses_with_zstd() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(dir.path());
+    let gen = |output: &std::path::Path, compress: &str| {
+        let result = Command::new(env!("CARGO_BIN_EXE_rcpio"))
+            .arg("gen").arg(&manifest).arg(output).arg("-t").arg("42").arg("--compress").arg(compress)
+            .output().unwrap();
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+        std::fs::read(output).unwrap()
+    };
+
+    let plain = gen(&dir.path().join("out.cpio"), "none");
+    let compressed = gen(&dir.path().join("out.cpio.zst"), "zstd");
+    assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), plain);
+}
@@ -0,0 +1,43 @@
+use std::collections::BTreeMap;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat};
+
+fn small_archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/hostname", b"box\n", 0o644).unwrap();
+    builder.insert_data("init", b"#!/bin/sh\nexec /bin/sh\n", 0o755).unwrap();
+    builder.insert_data("empty", b"", 0o644).unwrap();
+    builder.insert_symlink("sbin/init", "/init").unwrap();
+    builder.to_vec().unwrap()
+}
+
+#[test]
+fn map_round_trips_regular_files() {
+    let mem = small_archive();
+    let map = Cpio::load(&mem).unwrap().to_map().unwrap();
+    let expected: BTreeMap<String, Vec<u8>> = [
+        ("empty", &b""[..]),
+        ("etc/hostname", b"box\n"),
+        ("init", b"#!/bin/sh\nexec /bin/sh\n"),
+    ]
+    .into_iter()
+    .map(|(name, content)| (name.to_string(), content.to_vec()))
+    .collect();
+    assert_eq!(map, expected);
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for (name, content) in &map {
+        builder.insert_data(name, content, 0o644).unwrap();
+    }
+    let rebuilt = builder.to_vec().unwrap();
+    assert_eq!(Cpio::load(&rebuilt).unwrap().to_map().unwrap(), map);
+}
+
+#[test]
+fn symlinks_map_to_their_target() {
+    let mem = small_archive();
+    let map = Cpio::load(&mem).unwrap().to_map_with_symlinks().unwrap();
+    assert_eq!(map.len(), 4);
+    assert_eq!(map["sbin/init"], b"/init");
+}
@@ -0,0 +1,36 @@
+use std::process::Command;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat, CpioHeader};
+
+const TYPES: [(&str, u32, char); 7] = [
+    ("sock", 0o140755, 's'),
+    ("link", 0o120777, 'l'),
+    ("file", 0o100644, '-'),
+    ("blk", 0o060660, 'b'),
+    ("dir", 0o040755, 'd'),
+    ("chr", 0o020620, 'c'),
+    ("fifo", 0o010644, 'p'),
+];
+
+#[test]
+fn every_file_type_has_its_own_character() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for (ino, (name, mode, _)) in TYPES.iter().enumerate() {
+        builder.insert_header(name, CpioHeader::synthetic(ino as u32, *mode), vec![]).unwrap();
+    }
+    let mem = builder.to_vec().unwrap();
+    let cpio = Cpio::load(&mem).unwrap();
+    for (name, _, kind) in TYPES {
+        let mode = cpio.find(name).unwrap().unwrap().mode_str().unwrap();
+        assert_eq!(mode.chars().next(), Some(kind), "{name}: {mode}");
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcpio")).arg("ls").arg(&path).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let kinds: String = stdout.lines().map(|line| line.chars().next().unwrap()).collect();
+    assert_eq!(kinds, "sl-bdcp");
+}
@@ -0,0 +1,133 @@
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::process::{Command, Output};
+
+use rcpio::{CpioBuilder, CpioFormat, CpioHeader};
+
+const UID: u32 = 100;
+const GID: u32 = 101;
+
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Regular file header owned by UID:GID
+fn owned(ino: u32, mode: u32) -> CpioHeader {
+    CpioHeader { c_uid: UID, c_gid: GID, ..CpioHeader::synthetic(ino, 0o100000 | mode) }
+}
+
+/// Archive with a file and a directory owned by UID:GID
+fn write_archive(path: &Path) {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_header("file", owned(1, 0o644), b"hello\n".to_vec()).unwrap();
+    builder.insert_dir("dir", 0o755).unwrap();
+    builder.insert_header("dir/empty", owned(3, 0o600), vec![]).unwrap();
+    std::fs::write(path, builder.to_vec().unwrap()).unwrap();
+}
+
+/// Run `rcpio unar`, dropping to an unprivileged user first when `unprivileged` is set
+fn unar(archive: &Path, output: &Path, flags: &[&str], unprivileged: bool) -> Output {
+    let mut command = if unprivileged && is_root() {
+        let mut command = Command::new("setpriv");
+        command.args(["--reuid", "65534", "--regid", "65534", "--clear-groups"]);
+        command.arg(env!("CARGO_BIN_EXE_rcpio"));
+        command
+    } else {
+        Command::new(env!("CARGO_BIN_EXE_rcpio"))
+    };
+    command.arg("unar").arg(archive).arg(output).args(flags).output().unwrap()
+}
+
+#[test]
+fn numeric_owner_as_root_restores_ownership() {
+    if !is_root() {
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let (archive, output) = (dir.path().join("a.cpio"), dir.path().join("out"));
+    write_archive(&archive);
+
+    let result = unar(&archive, &output, &["--numeric-owner"], false);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(result.stderr.is_empty());
+
+    for name in ["file", "dir/empty"] {
+        let meta = std::fs::symlink_metadata(output.join(name)).unwrap();
+        assert_eq!((meta.uid(), meta.gid()), (UID, GID), "{name}");
+    }
+    // setting the owner must not drop the mode applied afterwards
+    let meta = std::fs::metadata(output.join("dir/empty")).unwrap();
+    assert_eq!(meta.permissions().mode() & 0o7777, 0o600);
+}
+
+#[test]
+fn numeric_owner_unprivileged_warns_and_continues() {
+    if is_root() && Command::new("setpriv").arg("--help").output().is_err() {
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    // the unprivileged user needs to be able to read the archive and create the output
+    std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o777)).unwrap();
+    let (archive, output) = (dir.path().join("a.cpio"), dir.path().join("out"));
+    write_archive(&archive);
+
+    let result = unar(&archive, &output, &["--numeric-owner"], true);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(result.status.success(), "{stderr}");
+    // one warning per entry, the directory is archived as owned by root
+    assert_eq!(stderr.matches("warning: unable to set owner").count(), 3, "{stderr}");
+    assert_eq!(std::fs::read(output.join("file")).unwrap(), b"hello\n");
+    assert!(output.join("dir/empty").exists());
+}
+
+#[test]
+fn default_unprivileged_extracts_as_current_user() {
+    if is_root() && Command::new("setpriv").arg("--help").output().is_err() {
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o777)).unwrap();
+    let (archive, output) = (dir.path().join("a.cpio"), dir.path().join("out"));
+    write_archive(&archive);
+
+    let result = unar(&archive, &output, &[], true);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(result.stderr.is_empty());
+
+    let meta = std::fs::symlink_metadata(output.join("file")).unwrap();
+    assert_ne!(meta.uid(), UID);
+}
+
+#[test]
+fn default_as_root_restores_ownership() {
+    if !is_root() {
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let (archive, output) = (dir.path().join("a.cpio"), dir.path().join("out"));
+    write_archive(&archive);
+
+    let result = unar(&archive, &output, &[], false);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let meta = std::fs::symlink_metadata(output.join("file")).unwrap();
+    assert_eq!((meta.uid(), meta.gid()), (UID, GID));
+
+    let skipped = dir.path().join("skipped");
+    let result = unar(&archive, &skipped, &["--owners", "skip"], false);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(std::fs::symlink_metadata(skipped.join("file")).unwrap().uid(), 0);
+}
+
+#[test]
+fn strict_unprivileged_fails() {
+    if is_root() && Command::new("setpriv").arg("--help").output().is_err() {
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o777)).unwrap();
+    let (archive, output) = (dir.path().join("a.cpio"), dir.path().join("out"));
+    write_archive(&archive);
+
+    let result = unar(&archive, &output, &["--owners", "strict"], true);
+    assert!(!result.status.success());
+}
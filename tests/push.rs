@@ -0,0 +1,255 @@
+use std::fs;
+
+use rcpio::{Cpio, CpioAppender, CpioBuilder, CpioFormat, Error};
+use tempfile::TempDir;
+
+fn archive(names: &[&str]) -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    for name in names {
+        builder.insert_data(name, name.as_bytes(), 0o644).unwrap();
+    }
+    builder.to_vec().unwrap()
+}
+
+fn names(mem: &[u8]) -> Vec<String> {
+    let cpio = Cpio::load(mem).unwrap();
+    let mut names = vec![];
+    let mut iter = cpio.iter_all_segments();
+    while let Some(entry) = fallible_iterator::FallibleIterator::next(&mut iter).unwrap() {
+        if !entry.is_trailer().unwrap() {
+            names.push(entry.name_string().unwrap());
+        }
+    }
+    names
+}
+
+#[test]
+fn push_tree_inserts_every_entry() {
+    let dir = TempDir::new().unwrap();
+    let tree = dir.path().join("modules");
+    fs::create_dir_all(tree.join("kernel/fs")).unwrap();
+    fs::write(tree.join("modules.dep"), b"dep").unwrap();
+    fs::write(tree.join("kernel/fs/ext4.ko"), b"ext4").unwrap();
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", b"init", 0o755).unwrap();
+    builder.insert_dir("lib", 0o755).unwrap();
+    let path = dir.path().join("a.cpio");
+    fs::write(&path, builder.to_vec().unwrap()).unwrap();
+
+    let mem = fs::read(&path).unwrap();
+    Cpio::load(&mem).unwrap().push_tree(&path, &tree, "lib").unwrap();
+
+    // the existing lib directory is kept, everything below it is added
+    let mem = fs::read(&path).unwrap();
+    let mut pushed = names(&mem);
+    assert_eq!(pushed.drain(..2).collect::<Vec<_>>(), ["init", "lib"]);
+    pushed.sort();
+    assert_eq!(pushed, ["lib/kernel", "lib/kernel/fs", "lib/kernel/fs/ext4.ko", "lib/modules.dep"]);
+    let cpio = Cpio::load(&mem).unwrap();
+    let map = cpio.to_map().unwrap();
+    assert_eq!(map["lib/kernel/fs/ext4.ko"], b"ext4");
+    assert_eq!(map["lib/modules.dep"], b"dep");
+    assert_eq!(map["init"], b"init");
+
+    // pushing the same tree again collides with the files and writes nothing
+    assert!(cpio.push_tree(&path, &tree, "lib").is_err());
+    assert_eq!(fs::read(&path).unwrap(), mem);
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("push").arg("-r").arg(&path).arg(&tree).arg("boot/modules")
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mem = fs::read(&path).unwrap();
+    assert_eq!(Cpio::load(&mem).unwrap().to_map().unwrap()["boot/modules/kernel/fs/ext4.ko"], b"ext4");
+    assert!(names(&mem).contains(&String::from("boot/modules/kernel")));
+}
+
+#[test]
+fn pushed_archives_stay_aligned() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("a.cpio");
+    fs::write(&path, archive(&["init"])).unwrap();
+
+    // every content length modulo 4, with names of every length modulo 4
+    let mut pushed = vec![];
+    for size in 0..8 {
+        let name = format!("{}{size}", "f".repeat(size % 4 + 1));
+        let content = vec![b'0' + size as u8; size];
+        fs::write(dir.path().join("src"), &content).unwrap();
+
+        let mem = fs::read(&path).unwrap();
+        Cpio::load(&mem).unwrap().push(&path, &dir.path().join("src"), &name).unwrap();
+        pushed.push((name, content));
+
+        let mem = fs::read(&path).unwrap();
+        assert_eq!(mem.len() % 512, 0, "after pushing {size} bytes");
+        let cpio = Cpio::load(&mem).unwrap();
+        let mut iter = cpio.iter_files();
+        while let Some(entry) = fallible_iterator::FallibleIterator::next(&mut iter).unwrap() {
+            assert_eq!(entry.index % 4, 0);
+        }
+        assert_eq!(names(&mem).len(), pushed.len() + 1);
+        assert_eq!(cpio.read_file("init").unwrap(), b"init");
+        for (name, content) in &pushed {
+            assert_eq!(&cpio.read_file(name).unwrap(), content);
+        }
+    }
+}
+
+#[test]
+fn merge_keeps_every_segment() {
+    // both segments number their inodes from the same start, the groups must stay apart
+    let linked = |names: [&str; 2], content: &[u8]| {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        for name in names {
+            builder.insert_data(name, content, 0o644).unwrap();
+        }
+        builder.hardlink_group(&names).unwrap();
+        builder.to_vec().unwrap()
+    };
+    let mut first = linked(["kernel/a", "kernel/b"], b"ucode");
+    first.extend(linked(["lib/a", "lib/b"], b"lib"));
+    first.extend(archive(&["init"]));
+    first.extend(archive(&["init"]));
+    let second = archive(&["init", "etc"]);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("merged.cpio");
+    Cpio::load(&first).unwrap()
+        .merge(&Cpio::load(&second).unwrap(), &path, rcpio::DuplicatePolicy::KeepLast)
+        .unwrap();
+
+    let mem = fs::read(&path).unwrap();
+    assert_eq!(names(&mem), ["kernel/a", "kernel/b", "lib/a", "lib/b", "init", "etc"]);
+    let map = Cpio::load(&mem).unwrap().to_map().unwrap();
+    for (name, content) in [("kernel/a", "ucode"), ("kernel/b", "ucode"), ("lib/a", "lib"), ("lib/b", "lib")] {
+        assert_eq!(map[name], content.as_bytes(), "{name}");
+    }
+
+    // paths repeated across segments of one archive are layered, not duplicates
+    let result = Cpio::load(&first).unwrap()
+        .merge(&Cpio::load(&archive(&["etc"])).unwrap(), &path, rcpio::DuplicatePolicy::Reject);
+    result.unwrap();
+    let result = Cpio::load(&first).unwrap()
+        .merge(&Cpio::load(&second).unwrap(), &path, rcpio::DuplicatePolicy::Reject);
+    assert!(matches!(result, Err(Error::InvalidArchiveError(_))), "{result:?}");
+}
+
+#[test]
+fn remove_keeps_following_segments() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("initramfs.cpio");
+    let mut mem = archive(&["kernel/x86/microcode/GenuineIntel.bin", "kernel/ucode"]);
+    mem.extend(archive(&["init", "bin/sh"]));
+    fs::write(&path, &mem).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("rm").arg(&path).arg("kernel/ucode")
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mem = fs::read(&path).unwrap();
+    assert_eq!(names(&mem), ["kernel/x86/microcode/GenuineIntel.bin", "init", "bin/sh"]);
+    let cpio = Cpio::load(&mem).unwrap();
+    assert_eq!(cpio.read_file("kernel/x86/microcode/GenuineIntel.bin").unwrap().len(), 37);
+
+    // entries of later segments aren't removed
+    assert!(matches!(cpio.remove(&path, "init"), Err(Error::NoSuchFile(_))));
+}
+
+#[test]
+fn push_adds_every_pair() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("a.cpio");
+    fs::write(&path, archive(&["init"])).unwrap();
+
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"));
+    command.arg("push").arg(&path);
+    for i in 0..50 {
+        let src = dir.path().join(format!("src{i}"));
+        fs::write(&src, format!("file {i}")).unwrap();
+        command.arg("-a").arg(format!("{}:bin/f{i}", src.display()));
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mem = fs::read(&path).unwrap();
+    assert_eq!(names(&mem).len(), 51);
+    let cpio = Cpio::load(&mem).unwrap();
+    for i in 0..50 {
+        assert_eq!(cpio.read_file(&format!("bin/f{i}")).unwrap(), format!("file {i}").as_bytes());
+    }
+}
+
+#[test]
+fn append_keeps_following_segments() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("initramfs.cpio");
+    let mut mem = archive(&["kernel/x86/microcode/GenuineIntel.bin"]);
+    mem.extend(archive(&["init"]));
+    fs::write(&path, &mem).unwrap();
+    fs::write(dir.path().join("extra"), b"extra").unwrap();
+
+    let mut appender = CpioAppender::open(&path).unwrap();
+    appender.append(&dir.path().join("extra"), "extra").unwrap();
+    appender.finish().unwrap();
+
+    let mem = fs::read(&path).unwrap();
+    assert_eq!(names(&mem), ["kernel/x86/microcode/GenuineIntel.bin", "extra", "init"]);
+}
+
+#[test]
+fn failed_push_leaves_archive_untouched() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("a.cpio");
+    let before = archive(&["init", "etc"]);
+    fs::write(&path, &before).unwrap();
+    fs::write(dir.path().join("extra"), b"extra").unwrap();
+
+    let mut appender = CpioAppender::open(&path).unwrap();
+    appender.append(&dir.path().join("extra"), "extra").unwrap();
+    assert!(matches!(appender.append(&dir.path().join("extra"), "./init"), Err(Error::DuplicatePath(_))));
+    assert!(appender.append(&dir.path().join("missing"), "missing").is_err());
+    drop(appender);
+
+    assert_eq!(fs::read(&path).unwrap(), before);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2, "temporary file left behind");
+}
+
+#[test]
+fn push_to_mapped_archive() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("a.cpio");
+    fs::write(&path, archive(&["init"])).unwrap();
+    fs::write(dir.path().join("extra"), b"extra").unwrap();
+
+    // the loaded archive still reads the old contents after the push replaced the file
+    let file = fs::File::open(&path).unwrap();
+    let mmap = unsafe { memmap2::Mmap::map(&file) }.unwrap();
+    let cpio = Cpio::load(&mmap).unwrap();
+    cpio.push(&path, &dir.path().join("extra"), "extra").unwrap();
+    assert_eq!(names(&mmap), ["init"]);
+
+    assert_eq!(names(&fs::read(&path).unwrap()), ["init", "extra"]);
+}
+
+#[test]
+fn push_to_existing_path_fails_cleanly() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("a.cpio");
+    let before = archive(&["init"]);
+    fs::write(&path, &before).unwrap();
+    fs::write(dir.path().join("extra"), b"extra").unwrap();
+
+    let mem = fs::read(&path).unwrap();
+    let result = Cpio::load(&mem).unwrap().push(&path, &dir.path().join("extra"), "./init");
+    assert!(matches!(result, Err(Error::DuplicatePath(ref name)) if name == "init"), "{result:?}");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("push").arg(&path).arg(dir.path().join("extra")).arg("init")
+        .output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "'init' is already in the archive");
+    assert_eq!(fs::read(&path).unwrap(), before);
+}
@@ -0,0 +1,505 @@
+use fallible_iterator::FallibleIterator;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat, Error};
+
+/// Overwrite a field of the newc header at `offset`, `field` counts the fields after the magic
+fn set_field(mem: &mut [u8], offset: usize, field: usize, value: u32) {
+    let start = offset + 6 + field * 8;
+    mem[start..start + 8].copy_from_slice(format!("{value:08x}").as_bytes());
+}
+
+const FILESIZE: usize = 6;
+
+fn archive() -> Vec<u8> {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("a", b"aaaa", 0o644).unwrap();
+    builder.insert_data("b", &[b'b'; 100], 0o644).unwrap();
+    builder.insert_data("c", b"cccc", 0o644).unwrap();
+    builder.to_vec().unwrap()
+}
+
+#[test]
+fn strict_iteration_catches_content_overrun() {
+    let mut mem = archive();
+    // swallow the header of "b" and part of its content, landing inside that content
+    set_field(&mut mem, 0, FILESIZE, 4 + 112 + 20);
+
+    // the plain iterator hands out content that is really the next entry
+    let cpio = Cpio::load(&mem).unwrap();
+    let first = cpio.iter_files().next().unwrap().unwrap();
+    assert!(first.file_content().unwrap().starts_with(b"aaaa070701"));
+
+    let result = cpio.iter_files_strict().collect::<Vec<_>>();
+    match result {
+        Err(Error::InvalidArchiveError(message)) => assert!(message.contains("'a'"), "{message}"),
+        other => panic!("overrun not detected: {:?}", other.map(|entries| entries.len())),
+    }
+
+    // an intact archive passes strict iteration
+    let mem = archive();
+    assert_eq!(Cpio::load(&mem).unwrap().iter_files_strict().count().unwrap(), 4);
+}
+
+#[test]
+fn permissions_and_file_type_bits_split_the_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    const MODE: usize = 1;
+    let mut mem = archive();
+    // a setuid "a" and a sticky, world writable directory "b"
+    set_field(&mut mem, 0, MODE, 0o104755);
+    set_field(&mut mem, 116, MODE, 0o041777);
+
+    let cpio = Cpio::load(&mem).unwrap();
+    let entries: Vec<_> = cpio.iter_files().collect().unwrap();
+    let split = |entry: &rcpio::CpioEntry| (entry.permissions().unwrap(), entry.file_type_bits().unwrap());
+    assert_eq!(split(&entries[0]), (0o4755, 0o100000));
+    assert_eq!(split(&entries[1]), (0o1777, 0o040000));
+    assert!(entries[0].is_reg().unwrap());
+    assert!(entries[1].is_dir().unwrap());
+
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    std::fs::create_dir(&out).unwrap();
+    cpio.extract_one(&out, &entries[0]).unwrap();
+    assert_eq!(std::fs::metadata(out.join("a")).unwrap().permissions().mode() & 0o7777, 0o4755);
+}
+
+#[test]
+fn conversion_errors_show_the_raw_field() {
+    use rcpio::HeaderField;
+
+    const UID: usize = 2;
+    let mut mem = archive();
+    let uid = 6 + UID * 8;
+    mem[uid..uid + 8].copy_from_slice(b"0000zz00");
+    // the mtime of "b", which starts after the 116 bytes of "a"
+    let mtime = 116 + 6 + 5 * 8;
+    mem[mtime..mtime + 8].copy_from_slice(b"\xff0000000");
+
+    let cpio = Cpio::load(&mem).unwrap();
+    let entries: Vec<_> = cpio.iter_files().collect().unwrap();
+    assert_eq!(entries[0].raw_field(HeaderField::Uid), b"0000zz00");
+    assert_eq!(entries[0].raw_field(HeaderField::Gid), b"00000000");
+    match entries[0].uid() {
+        Err(Error::EntryConversionError(message)) => {
+            assert_eq!(message, "Converting 'c_uid' to u64 failed: \"0000zz00\"")
+        },
+        other => panic!("expected a conversion error, got {other:?}"),
+    }
+    match entries[1].mtime() {
+        Err(Error::EntryConversionError(message)) => {
+            assert_eq!(message, "Converting 'c_mtime' from utf8 failed: \"\\xff0000000\"")
+        },
+        other => panic!("expected a conversion error, got {other:?}"),
+    }
+    assert_eq!(entries[1].file_content().unwrap(), [b'b'; 100]);
+}
+
+#[test]
+fn iteration_can_be_repeated() {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+    let names = |cpio: &Cpio| -> Vec<String> {
+        cpio.iter_files().map(|entry| entry.name_string()).collect().unwrap()
+    };
+
+    let first = names(&cpio);
+    assert_eq!(first, ["a", "b", "c", "TRAILER!!!"]);
+    assert_eq!(names(&cpio), first);
+
+    // stopping early leaves nothing behind for the next iteration
+    let mut partial = cpio.iter_files();
+    partial.next().unwrap();
+    assert_eq!(names(&cpio), first);
+
+    // neither does an iteration that fails partway through
+    let mut broken = mem.clone();
+    set_field(&mut broken, 0, FILESIZE, 4 + 112 + 20);
+    let broken = Cpio::load(&broken).unwrap();
+    assert!(broken.iter_files_strict().count().is_err());
+    assert!(broken.iter_files_strict().count().is_err());
+    assert_eq!(broken.iter_files().next().unwrap().unwrap().name_string().unwrap(), "a");
+}
+
+#[test]
+fn headers_are_lowercase_hex_and_parse_in_either_case() {
+    use rcpio::{encode_header, CpioHeader};
+
+    let header = CpioHeader {
+        c_mtime: 0xdeadbeef,
+        c_filesize: 4,
+        c_namesize: 2,
+        ..CpioHeader::synthetic(0xab, 0o100644)
+    };
+    let encoded = encode_header(&header, CpioFormat::Newc);
+    assert_eq!(encoded.len(), 110);
+    assert!(encoded.starts_with(b"070701000000ab000081a4"));
+    assert!(!encoded.iter().any(u8::is_ascii_uppercase));
+    assert!(encode_header(&header, CpioFormat::Crc).starts_with(b"070702"));
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_header("a", header.clone(), b"aaaa".to_vec()).unwrap();
+    let mem = builder.to_vec().unwrap();
+    assert!(mem.starts_with(&encoded));
+    // the trailer is lowercase too
+    let trailer = Cpio::load(&mem).unwrap().iter_files().last().unwrap().unwrap().index;
+    assert!(!mem[trailer..trailer + 110].iter().any(u8::is_ascii_uppercase));
+
+    let mut upper = mem.clone();
+    upper[..110].make_ascii_uppercase();
+    for mem in [&mem, &upper] {
+        let cpio = Cpio::load(mem).unwrap();
+        let entry = cpio.iter_files().next().unwrap().unwrap();
+        assert_eq!(entry.header_fields().unwrap(), header);
+    }
+}
+
+#[test]
+fn entry_after_walks_the_archive_without_an_iterator() {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let mut entry = cpio.iter_files().next().unwrap().unwrap();
+    let mut names = vec![entry.name_string().unwrap()];
+    while let Some(next) = cpio.entry_after(&entry).unwrap() {
+        names.push(next.name_string().unwrap());
+        entry = next;
+    }
+    assert_eq!(names, ["a", "b", "c", "TRAILER!!!"]);
+
+    // looking ahead does not disturb a running iteration
+    let mut iter = cpio.iter_files();
+    let a = iter.next().unwrap().unwrap();
+    assert_eq!(cpio.entry_after(&a).unwrap().unwrap().name_string().unwrap(), "b");
+    assert_eq!(iter.next().unwrap().unwrap().name_string().unwrap(), "b");
+}
+
+#[test]
+fn peek_first_copies_out_the_first_header() {
+    let info = {
+        let mem = archive();
+        Cpio::load(&mem).unwrap().peek_first().unwrap().unwrap()
+    };
+    assert_eq!(info.index, 0);
+    assert_eq!(info.name, "a");
+    assert_eq!(info.mode, 0o100644);
+    assert_eq!(info.filesize, 4);
+
+    let empty = CpioBuilder::new(CpioFormat::Newc).to_vec().unwrap();
+    assert!(Cpio::load(&empty).unwrap().peek_first().unwrap().is_none());
+}
+
+#[test]
+fn overhead_accounts_for_every_byte() {
+    let mem = archive();
+    let cpio = Cpio::load(&mem).unwrap();
+
+    let report = cpio.overhead().unwrap();
+    assert_eq!(report.archive_size, mem.len());
+    assert_eq!(report.header_bytes, 4 * 110);
+    assert_eq!(report.name_bytes, 2 + 2 + 2 + 11);
+    assert_eq!(report.content_bytes, 4 + 100 + 4);
+    assert_eq!(
+        report.header_bytes + report.name_bytes + report.content_bytes + report.padding_bytes,
+        mem.len()
+    );
+
+    let lens: Vec<usize> = cpio.iter_files().map(|entry| entry.entry_len()).collect().unwrap();
+    // 110 byte header, 2 byte name and 4 bytes of content land on a 4 byte boundary as is
+    assert_eq!(lens, [116, 212, 116, 124]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("stats").arg(&path)
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("archive size:  {}\n", mem.len())), "{stdout}");
+    assert!(stdout.contains("  headers:     440\n"), "{stdout}");
+}
+
+#[test]
+fn tiny_archives_are_invalid() {
+    for mem in [&b"070701"[..], b"070701000000", b"070702abcdef0123", b"070707"] {
+        match Cpio::load(mem) {
+            Err(Error::InvalidArchiveError(message)) => assert!(message.contains("truncated header"), "{message}"),
+            other => panic!("{:?} loaded: {:?}", String::from_utf8_lossy(mem), other.is_ok()),
+        }
+    }
+}
+
+#[test]
+fn all_segments_are_visited() {
+    let mut microcode = CpioBuilder::new(CpioFormat::Newc);
+    microcode.insert_data("kernel/x86/microcode/GenuineIntel.bin", b"ucode", 0o644).unwrap();
+    let mut main = CpioBuilder::new(CpioFormat::Crc);
+    main.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    main.insert_symlink("linuxrc", "init").unwrap();
+
+    for block_size in [Some(512), None] {
+        microcode.block_size(block_size);
+        let mut mem = microcode.to_vec().unwrap();
+        // segments start 4-byte aligned
+        mem.resize(mem.len().next_multiple_of(4), 0);
+        mem.extend(main.to_vec().unwrap());
+
+        let cpio = Cpio::load(&mem).unwrap();
+        let names = |iter: rcpio::CpioEntryIter| -> Vec<String> {
+            iter.map(|entry| entry.name_string()).collect().unwrap()
+        };
+        assert_eq!(names(cpio.iter_all_segments()), [
+            "kernel/x86/microcode/GenuineIntel.bin", "TRAILER!!!", "init", "linuxrc", "TRAILER!!!",
+        ]);
+        assert_eq!(names(cpio.iter_files()), ["kernel/x86/microcode/GenuineIntel.bin", "TRAILER!!!"]);
+
+        // data that isn't a cpio header ends the walk
+        mem.extend(b"not a header");
+        let cpio = Cpio::load(&mem).unwrap();
+        assert_eq!(names(cpio.iter_all_segments()).len(), 5);
+    }
+}
+
+#[test]
+fn ls_lists_every_segment() {
+    let mut microcode = CpioBuilder::new(CpioFormat::Newc);
+    microcode.insert_data("kernel/x86/microcode/GenuineIntel.bin", b"ucode", 0o644).unwrap();
+    let mut main = CpioBuilder::new(CpioFormat::Newc);
+    main.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    let ls = |mem: &[u8]| -> Vec<String> {
+        std::fs::write(&path, mem).unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+            .arg("ls").arg(&path)
+            .output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap().lines()
+            .map(|line| line.split_whitespace().nth(5).unwrap().trim_end_matches('\0').to_string())
+            .collect()
+    };
+
+    let microcode = microcode.to_vec().unwrap();
+    assert_eq!(ls(&microcode), ["kernel/x86/microcode/GenuineIntel.bin"]);
+    let concatenated = [microcode, main.to_vec().unwrap()].concat();
+    assert_eq!(ls(&concatenated), ["kernel/x86/microcode/GenuineIntel.bin", "init"]);
+}
+
+#[test]
+fn foreign_formats_are_named() {
+    // a tar header carries its magic at offset 257
+    let mut tar = vec![0u8; 512];
+    tar[..9].copy_from_slice(b"etc/motd\0");
+    tar[257..263].copy_from_slice(b"ustar\0");
+    let zip = b"PK\x03\x04\x14\x00\x00\x00\x08\x00".to_vec();
+    let gzip = b"\x1f\x8b\x08\x00\x00\x00\x00\x00".to_vec();
+
+    for (mem, expected) in [(tar, "a tar archive"), (zip, "a zip archive"), (gzip, "a gzip compressed file")] {
+        match Cpio::load(&mem) {
+            Err(Error::InvalidArchiveError(message)) => assert!(message.contains(expected), "{message}"),
+            other => panic!("expected {expected}, got {:?}", other.is_ok()),
+        }
+    }
+    assert!(matches!(Cpio::load(b"random bytes"), Err(Error::InvalidArchiveError(_))));
+}
+
+#[test]
+fn regular_files_are_borrowed_from_the_archive() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("etc", 0o755).unwrap();
+    builder.insert_data("etc/passwd", b"root", 0o644).unwrap();
+    builder.insert_symlink("passwd", "etc/passwd").unwrap();
+    builder.insert_header("fifo", rcpio::CpioHeader::synthetic(9, 0o010644), vec![]).unwrap();
+    builder.insert_data("empty", b"", 0o644).unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let files: Vec<_> = Cpio::load(&mem).unwrap().iter_regular_files().collect().unwrap();
+    assert_eq!(files, [(&b"etc/passwd"[..], &b"root"[..]), (b"empty", b"")]);
+    for (name, content) in files {
+        assert!(mem.as_ptr_range().contains(&name.as_ptr()));
+        assert!(content.is_empty() || mem.as_ptr_range().contains(&content.as_ptr()));
+    }
+}
+
+#[test]
+fn garbage_in_trailer_padding_is_a_warning() {
+    let mut mem = archive();
+    assert_eq!(mem.len() % 512, 0);
+    assert_eq!(Cpio::load(&mem).unwrap().check_trailer_padding().unwrap(), None);
+
+    let offset = mem.len() - 3;
+    mem[offset] = 0xff;
+    let cpio = Cpio::load(&mem).unwrap();
+    assert_eq!(cpio.check_trailer_padding().unwrap(), Some(offset));
+    // reading isn't affected
+    assert_eq!(cpio.iter_files_strict().count().unwrap(), cpio.iter_files().count().unwrap());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("check").arg(&path)
+        .output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("non-zero padding after the trailer at offset {offset:#x}")), "{stdout}");
+}
+
+#[test]
+fn missing_directory_entries_are_listed() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("a/b/c", b"c", 0o644).unwrap();
+    builder.insert_dir("x", 0o755).unwrap();
+    builder.insert_data("x/y/z", b"z", 0o644).unwrap();
+    builder.insert_data("a/b/d", b"d", 0o644).unwrap();
+    builder.insert_data("top", b"top", 0o644).unwrap();
+    let mem = builder.to_vec().unwrap();
+    assert_eq!(Cpio::load(&mem).unwrap().check_directory_entries().unwrap().missing, ["a", "a/b", "x/y"]);
+
+    // a directory entry after the entries inside it is too late
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("late/file", b"file", 0o644).unwrap();
+    builder.insert_dir("late", 0o755).unwrap();
+    let mem = builder.to_vec().unwrap();
+    assert_eq!(Cpio::load(&mem).unwrap().check_directory_entries().unwrap().missing, ["late"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio");
+    std::fs::write(&path, &mem).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("check").arg(&path)
+        .output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("'late' has no directory entry before 'late/file'"), "{stdout}");
+}
+
+#[test]
+fn invalid_names_are_listed_not_fatal() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("pppp", b"", 0o644).unwrap();
+    builder.insert_data("a/b", b"b", 0o644).unwrap();
+    builder.insert_data("qqqqqq", b"", 0o644).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    for (placeholder, name) in [(&b"pppp\0"[..], &b"/foo"[..]), (b"qqqqqq\0", b"../etc")] {
+        let at = mem.windows(placeholder.len()).position(|w| w == placeholder).unwrap();
+        mem[at..at + name.len() + 1].copy_from_slice(&[name, b"\0"].concat());
+    }
+
+    let directories = Cpio::load(&mem).unwrap().check_directory_entries().unwrap();
+    assert_eq!(directories.missing, ["a"]);
+    assert_eq!(directories.invalid_names, ["/foo", "../etc"]);
+}
+
+#[test]
+fn trailer_only_archives_are_empty() {
+    let padded = CpioBuilder::new(CpioFormat::Newc).to_vec().unwrap();
+    assert_eq!(padded.len(), 512);
+
+    // block padded, cut after the aligned trailer name and cut right after the name
+    for len in [512, 124, 121] {
+        let mem = &padded[..len];
+        let cpio = Cpio::load(mem).unwrap();
+        let entries: Vec<_> = cpio.iter_files().collect().unwrap();
+        assert_eq!(entries.len(), 1, "{len}");
+        assert!(entries[0].is_trailer().unwrap());
+        assert_eq!(entries[0].info().unwrap().name, "TRAILER!!!");
+        assert!(cpio.peek_first().unwrap().is_none());
+
+        assert_eq!(cpio.stats(rcpio::SizePolicy::default()).unwrap().entries, 0);
+        let overhead = cpio.overhead().unwrap();
+        assert_eq!(overhead.archive_size, len);
+        assert_eq!(
+            overhead.header_bytes + overhead.name_bytes + overhead.content_bytes + overhead.padding_bytes,
+            len
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        cpio.unarchive(&out).unwrap();
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0, "{len}");
+    }
+}
+
+#[test]
+fn check_names_flags_names_outside_the_encoding() {
+    use rcpio::NameEncoding;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("plain", b"", 0o644).unwrap();
+    builder.insert_data("café", b"", 0o644).unwrap();
+    builder.insert_data("cafX", b"", 0o644).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    // "café" in Latin-1
+    let placeholder = mem.windows(5).position(|w| w == b"cafX\0").unwrap();
+    mem[placeholder + 3] = 0xe9;
+
+    let cpio = Cpio::load(&mem).unwrap();
+    let entries: Vec<_> = cpio.iter_files().collect().unwrap();
+    let (utf8, latin1) = (entries[1].index, entries[2].index);
+    assert_eq!(entries[2].name().unwrap(), b"caf\xe9\0");
+
+    assert_eq!(cpio.check_names(NameEncoding::Utf8).unwrap(), [latin1]);
+    assert_eq!(cpio.check_names(NameEncoding::Ascii).unwrap(), [utf8, latin1]);
+    assert!(cpio.check_names(NameEncoding::AnyBytes).unwrap().is_empty());
+}
+
+#[test]
+fn gzip_archives_are_inflated() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    let plain = builder.to_vec().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.cpio.gz");
+    builder.write(&path, rcpio::Compression::Gzip).unwrap();
+    let gzipped = std::fs::read(&path).unwrap();
+
+    let mut buf = vec![];
+    let cpio = Cpio::load_compressed(&gzipped[..], &mut buf).unwrap();
+    assert_eq!(cpio.read_file("init").unwrap(), b"#!/bin/sh\n");
+    assert_eq!(buf, plain);
+
+    // concatenated gzip members are inflated one after another
+    Cpio::load_compressed(&[&gzipped[..], &gzipped[..]].concat()[..], &mut buf).unwrap();
+    assert_eq!(buf, [&plain[..], &plain[..]].concat());
+
+    // uncompressed archives are read as they are
+    Cpio::load_compressed(&plain[..], &mut buf).unwrap();
+    assert_eq!(buf, plain);
+
+    let truncated = &gzipped[..gzipped.len() / 2];
+    assert!(matches!(Cpio::load_compressed(truncated, &mut buf), Err(Error::DecompressionError(_))));
+
+    let rcpio = |args: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .args(args)
+        .output().unwrap();
+    let path = path.to_str().unwrap();
+    let cat = rcpio(&["cat", path, "init"]);
+    assert!(cat.status.success(), "{}", String::from_utf8_lossy(&cat.stderr));
+    assert_eq!(cat.stdout, b"#!/bin/sh\n");
+    assert!(rcpio(&["ls", path]).status.success());
+
+    // rewriting would store the archive uncompressed, so push refuses it
+    let src = dir.path().join("src");
+    std::fs::write(&src, b"data").unwrap();
+    let push = rcpio(&["push", path, src.to_str().unwrap(), "data"]);
+    assert!(!push.status.success());
+    assert!(String::from_utf8_lossy(&push.stderr).contains("a gzip compressed file"));
+    assert_eq!(std::fs::read(path).unwrap(), gzipped);
+}
+
+#[test]
+fn content_may_end_at_the_end_of_the_buffer() {
+    let mem = archive();
+    // "a" has a one byte name, its content starts right after the header and name padding
+    let end = 112 + 4;
+    assert_eq!(&mem[112..end], b"aaaa");
+
+    let entry = Cpio::load(&mem[..end]).unwrap().find("a").unwrap().unwrap();
+    assert_eq!(entry.file_content().unwrap(), b"aaaa");
+
+    let entry = Cpio::load(&mem[..end - 1]).unwrap().find("a").unwrap().unwrap();
+    assert!(matches!(entry.file_content(), Err(Error::EarlyEOFError)));
+}
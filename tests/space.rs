@@ -0,0 +1,56 @@
+use std::path::Path;
+use std::process::Command;
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat, Error, ExtractOptions};
+
+/// Archive holding the root directory entry and, when `content_size` is non-zero, a file of
+/// that size
+fn archive(dir: &Path, content_size: usize) -> Vec<u8> {
+    let src = dir.join("src");
+    std::fs::create_dir(&src).unwrap();
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert(&src, "").unwrap();
+    if content_size > 0 {
+        std::fs::write(src.join("big"), vec![0x55; content_size]).unwrap();
+        builder.insert(&src.join("big"), "big").unwrap();
+    }
+    let path = dir.join("a.cpio");
+    builder.write(&path, rcpio::Compression::None).unwrap();
+    std::fs::read(path).unwrap()
+}
+
+#[test]
+fn check_space_passes_and_creates_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out");
+    let mem = archive(dir.path(), 0);
+
+    let options = ExtractOptions { check_space: true, ..ExtractOptions::default() };
+    Cpio::load(&mem).unwrap().unarchive_with_options(&output, &options).unwrap();
+    assert!(output.is_dir());
+}
+
+#[test]
+fn failed_check_leaves_no_output_directory() {
+    let src = tempfile::tempdir().unwrap();
+    let mem = archive(src.path(), 1 << 20);
+    assert_eq!(Cpio::load(&mem).unwrap().content_size().unwrap(), 1 << 20);
+
+    let dir = tempfile::tempdir().unwrap();
+    // needs a small filesystem, skip where tmpfs can't be mounted
+    let mounted = Command::new("mount")
+        .args(["-t", "tmpfs", "-o", "size=64k", "tmpfs"]).arg(dir.path())
+        .output().is_ok_and(|output| output.status.success());
+    if !mounted {
+        return
+    }
+
+    let output = dir.path().join("out");
+    let options = ExtractOptions { check_space: true, ..ExtractOptions::default() };
+    let result = Cpio::load(&mem).unwrap().unarchive_with_options(&output, &options);
+
+    let exists = output.exists();
+    Command::new("umount").arg(dir.path()).status().unwrap();
+    assert!(matches!(result, Err(Error::InsufficientSpaceError(_))), "{result:?}");
+    assert!(!exists);
+}
@@ -0,0 +1,60 @@
+use rcpio::{CpioBuilder, CpioFormat, CpioStream, Error};
+
+/// Overwrite a field of the newc header at `offset`, `field` counts the fields after the magic
+fn set_field(mem: &mut [u8], offset: usize, field: usize, value: u32) {
+    let start = offset + 6 + field * 8;
+    mem[start..start + 8].copy_from_slice(format!("{value:08x}").as_bytes());
+}
+
+const FILESIZE: usize = 6;
+const NAMESIZE: usize = 11;
+
+#[test]
+fn stream_reads_entries() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    builder.insert_symlink("linuxrc", "init").unwrap();
+    let mem = builder.to_vec().unwrap();
+
+    let mut stream = CpioStream::new(&mem[..]);
+    assert_eq!(stream.next_entry().unwrap().unwrap().name, "init");
+    assert_eq!(stream.read_content().unwrap(), b"#!/bin/sh\n");
+    assert_eq!(stream.next_entry().unwrap().unwrap().name, "linuxrc");
+    assert_eq!(stream.read_content().unwrap(), b"init");
+    assert!(stream.next_entry().unwrap().is_none());
+}
+
+#[test]
+fn huge_namesize_is_rejected() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", b"", 0o755).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    set_field(&mut mem, 0, NAMESIZE, u32::MAX);
+
+    let mut stream = CpioStream::new(&mem[..]);
+    assert!(matches!(stream.next_entry(), Err(Error::InvalidArchiveError(_))));
+}
+
+#[test]
+fn huge_symlink_target_is_rejected() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_symlink("linuxrc", "init").unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    set_field(&mut mem, 0, FILESIZE, u32::MAX);
+
+    let mut stream = CpioStream::new(&mem[..]);
+    assert!(matches!(stream.next_entry(), Err(Error::InvalidArchiveError(_))));
+}
+
+#[test]
+fn truncated_content_is_an_early_eof() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("init", b"#!/bin/sh\n", 0o755).unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    // a regular file can be that large, but the stream doesn't hold it
+    set_field(&mut mem, 0, FILESIZE, u32::MAX);
+
+    let mut stream = CpioStream::new(&mem[..]);
+    stream.next_entry().unwrap().unwrap();
+    assert!(matches!(stream.read_content(), Err(Error::EarlyEOFError)));
+}
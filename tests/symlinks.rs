@@ -0,0 +1,178 @@
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+
+use rcpio::{Cpio, CpioBuilder, CpioFormat, Error, ExtractOptions, ExtractReport, SymlinkPolicy};
+
+#[test]
+fn existing_symlinks_are_replaced_not_followed() {
+    for overwrite in [false, true] {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("passwd"), b"original").unwrap();
+        let outside_mode = fs::metadata(&outside).unwrap().permissions().mode();
+
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+        symlink(outside.join("passwd"), out.join("passwd")).unwrap();
+        symlink(&outside, out.join("etc")).unwrap();
+
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.insert_data("passwd", b"replaced", 0o600).unwrap();
+        builder.insert_dir("etc", 0o700).unwrap();
+        builder.insert_data("etc/shadow", b"shadow", 0o600).unwrap();
+        let mem = builder.to_vec().unwrap();
+
+        let options = ExtractOptions { overwrite, ..Default::default() };
+        Cpio::load(&mem).unwrap().unarchive_with_options(&out, &options).unwrap();
+
+        assert_eq!(fs::read(outside.join("passwd")).unwrap(), b"original", "overwrite: {overwrite}");
+        assert!(!outside.join("shadow").exists(), "overwrite: {overwrite}");
+        assert_eq!(fs::metadata(&outside).unwrap().permissions().mode(), outside_mode);
+
+        assert!(!fs::symlink_metadata(out.join("passwd")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(out.join("passwd")).unwrap(), b"replaced");
+        assert!(!fs::symlink_metadata(out.join("etc")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(out.join("etc/shadow")).unwrap(), b"shadow");
+    }
+}
+
+#[test]
+fn symlink_report_flags_dangling_links_and_cycles() {
+    use rcpio::SymlinkStatus;
+
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_data("pppp", b"", 0o644).unwrap();
+    builder.insert_data("bin/busybox", b"busybox", 0o755).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    builder.insert_symlink("usr/bin/sh", "../../bin/sh").unwrap();
+    builder.insert_symlink("lib/missing", "/lib/nothing").unwrap();
+    builder.insert_symlink("a", "b").unwrap();
+    builder.insert_symlink("b", "./a").unwrap();
+    let mut mem = builder.to_vec().unwrap();
+    // an absolute name, which extraction refuses, is left out of the graph
+    let at = mem.windows(5).position(|w| w == b"pppp\0").unwrap();
+    mem[at..at + 5].copy_from_slice(b"/foo\0");
+
+    let report = Cpio::load(&mem).unwrap().symlink_report().unwrap();
+    let links: Vec<_> = report.links.iter()
+        .map(|link| (link.name.as_str(), link.target.as_str(), link.status.clone()))
+        .collect();
+    assert_eq!(links, [
+        ("bin/sh", "busybox", SymlinkStatus::Resolved("bin/busybox".into())),
+        ("usr/bin/sh", "../../bin/sh", SymlinkStatus::Resolved("bin/busybox".into())),
+        ("lib/missing", "/lib/nothing", SymlinkStatus::Dangling),
+        ("a", "b", SymlinkStatus::Cycle),
+        ("b", "./a", SymlinkStatus::Cycle),
+    ]);
+    // the cycle is listed once, whichever member it was found from
+    assert_eq!(report.cycles.len(), 1);
+    let mut cycle = report.cycles[0].clone();
+    cycle.sort();
+    assert_eq!(cycle, ["a", "b"]);
+    assert_eq!(report.invalid_names, ["/foo"]);
+}
+
+fn extract_links(
+    links: &[(&str, &str)],
+    symlink_policy: SymlinkPolicy
+) -> (tempfile::TempDir, Result<ExtractReport, Error>) {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("usr", 0o755).unwrap();
+    builder.insert_dir("usr/lib", 0o755).unwrap();
+    for (name, target) in links {
+        builder.insert_symlink(name, target).unwrap();
+    }
+    let mem = builder.to_vec().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let options = ExtractOptions { symlink_policy, ..Default::default() };
+    let result = Cpio::load(&mem).unwrap().unarchive_with_options(&dir.path().join("out"), &options);
+    (dir, result)
+}
+
+#[test]
+fn symlink_policy_checks_relative_targets() {
+    let contained = [
+        ("lib", "usr/lib"),
+        ("usr/lib/libc.so", "../../lib/libc.so.6"),
+        ("usr/lib/self", "."),
+        ("sh", "/bin/busybox"),
+    ];
+    let escaping = [("usr/lib/passwd", "../../../etc/passwd"), ("up", "..")];
+
+    for policy in [SymlinkPolicy::AllowAny, SymlinkPolicy::WarnEscaping, SymlinkPolicy::RejectEscaping] {
+        let (dir, result) = extract_links(&contained, policy);
+        assert!(result.unwrap().escaping_symlinks.is_empty());
+        for (name, target) in contained {
+            assert_eq!(fs::read_link(dir.path().join("out").join(name)).unwrap().to_str(), Some(target));
+        }
+    }
+
+    for policy in [SymlinkPolicy::AllowAny, SymlinkPolicy::WarnEscaping] {
+        let (dir, result) = extract_links(&escaping, policy);
+        let reported: Vec<(&str, &str)> = result.as_ref().unwrap().escaping_symlinks.iter()
+            .map(|(name, target)| (name.as_str(), target.as_str()))
+            .collect();
+        if policy == SymlinkPolicy::WarnEscaping {
+            assert_eq!(reported, escaping);
+        } else {
+            assert!(reported.is_empty());
+        }
+        for (name, target) in escaping {
+            assert_eq!(fs::read_link(dir.path().join("out").join(name)).unwrap().to_str(), Some(target));
+        }
+    }
+
+    for link in escaping {
+        let (dir, result) = extract_links(&[link], SymlinkPolicy::RejectEscaping);
+        match result {
+            Err(Error::FileSystemError(message)) => assert!(message.contains(link.0), "{message}"),
+            other => panic!("expected FileSystemError, got {other:?}"),
+        }
+        assert!(fs::symlink_metadata(dir.path().join("out").join(link.0)).is_err());
+    }
+}
+
+#[test]
+fn no_extraction_through_symlinks_leading_outside() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = dir.path().join("outside");
+    fs::create_dir(&outside).unwrap();
+    let absolute = outside.to_str().unwrap().to_string();
+
+    for (link, target, through) in [
+        ("evil", absolute.as_str(), "evil/passwd"),
+        ("evil", "../outside", "evil/passwd"),
+        ("evil", absolute.as_str(), "evil/sub/passwd"),
+        ("dir/evil", "../../outside", "dir/evil/passwd"),
+    ] {
+        let mut builder = CpioBuilder::new(CpioFormat::Newc);
+        builder.insert_dir("dir", 0o755).unwrap();
+        builder.insert_symlink(link, target).unwrap();
+        builder.insert_data(through, b"pwned", 0o644).unwrap();
+        let mem = builder.to_vec().unwrap();
+
+        let out = dir.path().join("out");
+        let result = Cpio::load(&mem).unwrap().unarchive_with_options(&out, &ExtractOptions::default());
+        match result {
+            Err(Error::FileSystemError(message)) => {
+                assert!(message.contains("outside the output directory"), "{message}")
+            },
+            other => panic!("expected FileSystemError for {through}, got {other:?}"),
+        }
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0, "wrote through {link} -> {target}");
+        fs::remove_dir_all(&out).unwrap();
+    }
+
+    // symlinks staying inside the output directory are followed
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("usr", 0o755).unwrap();
+    builder.insert_dir("usr/lib", 0o755).unwrap();
+    builder.insert_symlink("lib", "usr/lib").unwrap();
+    builder.insert_data("lib/libc.so.6", b"libc", 0o755).unwrap();
+    let mem = builder.to_vec().unwrap();
+    let out = dir.path().join("out");
+    Cpio::load(&mem).unwrap().unarchive_with_options(&out, &ExtractOptions::default()).unwrap();
+    assert_eq!(fs::read(out.join("usr/lib/libc.so.6")).unwrap(), b"libc");
+}
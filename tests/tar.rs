@@ -0,0 +1,81 @@
+use std::process::Command;
+
+use rcpio::{CpioBuilder, CpioFormat, CpioHeader};
+
+fn special(ino: u32, mode: u32, rdev: (u32, u32)) -> CpioHeader {
+    CpioHeader { c_rdevmajor: rdev.0, c_rdevminor: rdev.1, ..CpioHeader::synthetic(ino, mode) }
+}
+
+/// Run `rcpio unar <archive> - --as-tar` and return its output
+fn unar_as_tar(mem: &[u8]) -> std::process::Output {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("archive.cpio");
+    std::fs::write(&archive, mem).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_rcpio"))
+        .arg("unar").arg(&archive).arg("-").arg("--as-tar")
+        .output().unwrap()
+}
+
+#[test]
+fn stdout_tar_carries_every_entry_type() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_dir("dev", 0o755).unwrap();
+    builder.insert_header("dev/null", special(100, 0o020666, (1, 3)), vec![]).unwrap();
+    builder.insert_header("dev/sda", special(101, 0o060660, (8, 0)), vec![]).unwrap();
+    builder.insert_header("dev/initctl", special(102, 0o010600, (0, 0)), vec![]).unwrap();
+    builder.insert_data("bin/busybox", b"\x7fELF busybox", 0o755).unwrap();
+    builder.insert_data("bin/ls", b"\x7fELF busybox", 0o755).unwrap();
+    builder.hardlink_group(&["bin/busybox", "bin/ls"]).unwrap();
+    builder.insert_symlink("bin/sh", "busybox").unwrap();
+    let output = unar_as_tar(&builder.to_vec().unwrap());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut archive = tar::Archive::new(&output.stdout[..]);
+    let mut seen: Vec<(String, tar::EntryType)> = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let header = entry.header().clone();
+        let kind = header.entry_type();
+        match name.as_str() {
+            "dev" => assert!(kind.is_dir()),
+            "dev/null" => {
+                assert!(kind.is_character_special());
+                assert_eq!((header.device_major().unwrap(), header.device_minor().unwrap()),
+                    (Some(1), Some(3)));
+                assert_eq!(header.mode().unwrap(), 0o666);
+            }
+            "dev/sda" => assert!(kind.is_block_special()),
+            "dev/initctl" => assert!(kind.is_fifo()),
+            "bin/sh" => {
+                assert!(kind.is_symlink());
+                assert_eq!(entry.link_name().unwrap().unwrap().to_str(), Some("busybox"));
+            }
+            "bin/busybox" | "bin/ls" if kind.is_hard_link() => {
+                // the link points back at the member that carried the data
+                let target = entry.link_name().unwrap().unwrap().to_string_lossy().into_owned();
+                assert!(seen.iter().any(|(seen, _)| *seen == target));
+            }
+            "bin/busybox" | "bin/ls" => {
+                let mut content = Vec::new();
+                std::io::Read::read_to_end(&mut entry, &mut content).unwrap();
+                assert_eq!(content, b"\x7fELF busybox");
+            }
+            other => panic!("unexpected entry {other}"),
+        }
+        seen.push((name, kind));
+    }
+    let mut names: Vec<_> = seen.iter().map(|(name, _)| name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, ["bin/busybox", "bin/ls", "bin/sh", "dev", "dev/initctl", "dev/null", "dev/sda"]);
+    assert_eq!(seen.iter().filter(|(_, kind)| kind.is_hard_link()).count(), 1);
+}
+
+#[test]
+fn sockets_are_an_error() {
+    let mut builder = CpioBuilder::new(CpioFormat::Newc);
+    builder.insert_header("run/sock", special(100, 0o140755, (0, 0)), vec![]).unwrap();
+    let output = unar_as_tar(&builder.to_vec().unwrap());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("run/sock"));
+}